@@ -0,0 +1,391 @@
+//! Statistics gathered from a stream
+
+use std::cmp::min;
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::io;
+use {Decoder, ErrorPolicy, Frame, SimplemadError};
+
+/// The distribution of bit rates in a stream, see `bitrate_stats`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitrateStats {
+    /// Number of frames for each bit rate
+    pub histogram: BTreeMap<u32, u64>,
+    /// Lowest bit rate
+    pub min: u32,
+    /// Highest bit rate
+    pub max: u32,
+    /// Mean bit rate over all frames
+    pub mean: u32,
+    /// Number of frames
+    pub frames: u64,
+}
+
+/// Scan the frame headers of a stream and collect its bit rates
+///
+/// Returns `SimplemadError::EOF` if the stream has no frames.
+pub fn bitrate_stats<R>(reader: R) -> Result<BitrateStats, SimplemadError>
+    where R: io::Read
+{
+    let headers = Decoder::builder(reader)
+                      .error_policy(ErrorPolicy::SkipRecoverable)
+                      .build()?
+                      .headers();
+
+    let mut histogram = BTreeMap::new();
+    let mut frames = 0u64;
+    let mut sum = 0u64;
+    for header in headers {
+        let bit_rate = header?.bit_rate;
+        *histogram.entry(bit_rate).or_insert(0) += 1;
+        frames += 1;
+        sum += u64::from(bit_rate);
+    }
+
+    let (min, max) = match (histogram.keys().next(), histogram.keys().next_back()) {
+        (Some(&min), Some(&max)) => (min, max),
+        _ => return Err(SimplemadError::EOF),
+    };
+
+    Ok(BitrateStats {
+        histogram,
+        min,
+        max,
+        mean: (sum / frames) as u32,
+        frames,
+    })
+}
+
+/// The levels of one time bucket of a waveform, see `waveform`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WaveformBucket {
+    /// Lowest sample value over all channels
+    pub min: f32,
+    /// Highest sample value over all channels
+    pub max: f32,
+    /// Root mean square of the samples of all channels
+    pub rms: f32,
+}
+
+// Number of samples per channel summarized before bucketing
+const WAVEFORM_BLOCK: usize = 256;
+
+/// Decode a stream and reduce it to `buckets` levels of equal duration
+///
+/// Use `Decoder::waveform` to decode with other options, such as
+/// `half_sample_rate`, which is faster and usually precise enough for an
+/// overview. Returns fewer buckets for very short streams.
+pub fn waveform<R>(reader: R, buckets: usize) -> Result<Vec<WaveformBucket>, SimplemadError>
+    where R: io::Read
+{
+    Decoder::builder(reader)
+        .error_policy(ErrorPolicy::SkipRecoverable)
+        .build()?
+        .waveform(buckets)
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    /// Decode the rest of the stream and reduce it to `buckets` levels of
+    /// equal duration, see `waveform`
+    ///
+    /// Recoverable errors are skipped.
+    pub fn waveform(self, buckets: usize) -> Result<Vec<WaveformBucket>, SimplemadError> {
+        let mut blocks = Vec::new();
+        let mut block = WaveformBlock::default();
+        for result in self {
+            let frame = match result {
+                Ok(frame) => frame,
+                Err(ref e) if e.is_recoverable() => continue,
+                Err(e) => return Err(e),
+            };
+            for index in 0..frame.n_samples() {
+                for channel in &frame.samples {
+                    block.add(channel[index].to_f32());
+                }
+                block.len += 1;
+                if block.len == WAVEFORM_BLOCK {
+                    blocks.push(block);
+                    block = WaveformBlock::default();
+                }
+            }
+        }
+        if block.len > 0 {
+            blocks.push(block);
+        }
+
+        let buckets = min(buckets, blocks.len());
+        Ok((0..buckets)
+               .map(|bucket| {
+                   let start = bucket * blocks.len() / buckets;
+                   let end = (bucket + 1) * blocks.len() / buckets;
+                   let mut merged = WaveformBlock::default();
+                   for block in &blocks[start..end] {
+                       merged.merge(block);
+                   }
+                   merged.bucket()
+               })
+               .collect())
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct WaveformBlock {
+    min: f32,
+    max: f32,
+    sum_squares: f64,
+    values: u64,
+    len: usize,
+}
+
+impl WaveformBlock {
+    fn add(&mut self, value: f32) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum_squares += f64::from(value) * f64::from(value);
+        self.values += 1;
+    }
+
+    fn merge(&mut self, other: &WaveformBlock) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum_squares += other.sum_squares;
+        self.values += other.values;
+    }
+
+    fn bucket(&self) -> WaveformBucket {
+        let rms = match self.values {
+            0 => 0.0,
+            values => (self.sum_squares / values as f64).sqrt() as f32,
+        };
+        WaveformBucket {
+            min: self.min,
+            max: self.max,
+            rms,
+        }
+    }
+}
+
+/// The loudness of a stream, see `loudness`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS as defined by ITU-R BS.1770, negative
+    /// infinity if the stream is too quiet to measure
+    pub integrated: f64,
+    /// Largest absolute sample value in dBFS
+    pub peak: f64,
+}
+
+/// Decode a stream and measure its loudness
+///
+/// Returns `SimplemadError::EOF` if the stream has no frames.
+pub fn loudness<R>(reader: R) -> Result<Loudness, SimplemadError>
+    where R: io::Read
+{
+    let decoder = Decoder::builder(reader)
+                      .error_policy(ErrorPolicy::SkipRecoverable)
+                      .build()?;
+
+    let mut meter = LoudnessMeter::default();
+    let mut peak = 0.0f64;
+    let mut frames = 0u64;
+    for frame in decoder {
+        let frame = frame?;
+        peak = peak.max(frame.peak().to_f64());
+        meter.add(&frame);
+        frames += 1;
+    }
+
+    if frames == 0 {
+        return Err(SimplemadError::EOF);
+    }
+
+    Ok(Loudness {
+        integrated: meter.integrated(),
+        peak: 20.0 * peak.log10(),
+    })
+}
+
+// Mean square of 100 ms of K-weighted audio, summed over the channels
+#[derive(Default)]
+struct LoudnessMeter {
+    sample_rate: u32,
+    filters: Vec<KWeighting>,
+    energy: f64,
+    count: u32,
+    quarters: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    fn add(&mut self, frame: &Frame) {
+        if frame.sample_rate != self.sample_rate || frame.samples.len() != self.filters.len() {
+            self.sample_rate = frame.sample_rate;
+            self.filters = frame.samples
+                                .iter()
+                                .map(|_| KWeighting::new(frame.sample_rate))
+                                .collect();
+            self.energy = 0.0;
+            self.count = 0;
+        }
+
+        let quarter = self.sample_rate / 10;
+        for index in 0..frame.n_samples() {
+            for (filter, channel) in self.filters.iter_mut().zip(&frame.samples) {
+                let weighted = filter.process(channel[index].to_f64());
+                self.energy += weighted * weighted;
+            }
+            self.count += 1;
+            if self.count == quarter {
+                self.quarters.push(self.energy / f64::from(quarter));
+                self.energy = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+
+    // Gated loudness over blocks of 400 ms overlapping by 75%
+    fn integrated(&self) -> f64 {
+        let blocks: Vec<f64> = self.quarters
+                                   .windows(4)
+                                   .map(|w| w.iter().sum::<f64>() / 4.0)
+                                   .filter(|&z| block_loudness(z) > -70.0)
+                                   .collect();
+        if blocks.is_empty() {
+            return f64::NEG_INFINITY;
+        }
+
+        let mean = blocks.iter().sum::<f64>() / blocks.len() as f64;
+        let gate = block_loudness(mean) - 10.0;
+        let gated: Vec<f64> = blocks.into_iter().filter(|&z| block_loudness(z) > gate).collect();
+        block_loudness(gated.iter().sum::<f64>() / gated.len() as f64)
+    }
+}
+
+fn block_loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+// The BS.1770 pre-filter and high-pass, designed for any sample rate
+struct KWeighting {
+    stages: [Biquad; 2],
+}
+
+impl KWeighting {
+    fn new(sample_rate: u32) -> KWeighting {
+        let rate = f64::from(sample_rate);
+
+        let k = (PI * 1681.974450955533 / rate).tan();
+        let q = 0.7071752369554196;
+        let vh = 10f64.powf(3.999843853973347 / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new([(vh + vb * k / q + k * k) / a0,
+                                 2.0 * (k * k - vh) / a0,
+                                 (vh - vb * k / q + k * k) / a0],
+                                [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]);
+
+        let k = (PI * 38.13547087602444 / rate).tan();
+        let q = 0.5003270373238773;
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new([1.0, -2.0, 1.0],
+                                    [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]);
+
+        KWeighting { stages: [shelf, high_pass] }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.stages.iter_mut().fold(sample, |x, stage| stage.process(x))
+    }
+}
+
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Biquad {
+        Biquad { b, a, state: [0.0; 2] }
+    }
+
+    // Transposed direct form II
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+
+    fn stats_of(path: &str) -> BitrateStats {
+        bitrate_stats(File::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_bitrate_stats() {
+        let stats = stats_of("sample_mp3s/constant_stereo_320.mp3");
+        assert_eq!((stats.min, stats.max, stats.mean), (320_000, 320_000, 320_000));
+        assert_eq!(stats.histogram.len(), 1);
+        assert_eq!(stats.histogram[&320_000], stats.frames);
+
+        let stats = stats_of("sample_mp3s/variable_stereo.mp3");
+        assert!(stats.histogram.len() > 1);
+        assert!(stats.min < stats.mean && stats.mean < stats.max);
+        assert_eq!(stats.histogram.values().sum::<u64>(), stats.frames);
+    }
+
+    #[test]
+    fn test_loudness() {
+        let loudness = loudness(File::open("sample_mp3s/constant_stereo_128.mp3").unwrap())
+                           .unwrap();
+        assert!(loudness.integrated < loudness.peak);
+        assert!(loudness.integrated > -40.0 && loudness.integrated < 0.0);
+        assert!(loudness.peak <= 0.0 && loudness.peak > -20.0);
+    }
+
+    #[test]
+    fn test_k_weighting() {
+        // A 1 kHz sine at full scale reads about -3 LUFS
+        let mut filter = KWeighting::new(48_000);
+        let mut energy = 0.0;
+        for i in 0..48_000 {
+            let x = (2.0 * PI * 1_000.0 * f64::from(i) / 48_000.0).sin();
+            let y = filter.process(x);
+            if i >= 24_000 {
+                energy += y * y;
+            }
+        }
+        let lufs = block_loudness(energy / 24_000.0);
+        assert!((lufs + 3.01).abs() < 0.1, "{}", lufs);
+    }
+
+    #[test]
+    fn test_waveform() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let buckets = waveform(File::open(path).unwrap(), 100).unwrap();
+        assert_eq!(buckets.len(), 100);
+        assert!(buckets.iter().all(|b| b.min <= 0.0 && b.max >= 0.0 && b.rms >= 0.0));
+        assert!(buckets.iter().all(|b| b.rms <= b.max.max(-b.min)));
+        assert!(buckets.iter().any(|b| b.max > 0.1));
+
+        // Decoding at half the sample rate gives a similar overview
+        let half = Decoder::builder(File::open(path).unwrap())
+                       .half_sample_rate(true)
+                       .build()
+                       .unwrap()
+                       .waveform(100)
+                       .unwrap();
+        assert_eq!(half.len(), 100);
+        for (full, half) in buckets.iter().zip(&half) {
+            assert!((full.rms - half.rms).abs() < 0.05);
+        }
+
+        let few = waveform(File::open(path).unwrap(), 1_000_000).unwrap();
+        assert!(few.len() < 1_000 && few.len() > 800);
+    }
+}
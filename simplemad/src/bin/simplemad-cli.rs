@@ -0,0 +1,483 @@
+//! Decode an MPEG audio file to WAV or raw PCM, or report on its frames
+
+extern crate simplemad;
+
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::process;
+use std::time::Duration;
+use simplemad::{inspect, Decoder, ErrorPolicy, Frame, SimplemadError};
+
+const USAGE: &str = "\
+Usage: simplemad-cli [OPTIONS] INPUT [OUTPUT]
+       simplemad-cli inspect [-f text|csv|json] INPUT
+
+Decode INPUT to OUTPUT, or to standard output if OUTPUT is missing or `-`.
+`inspect` prints the duration, bit rate and encoder of INPUT, or every frame
+header as CSV or JSON.
+
+Options:
+  -f, --format FORMAT      Output `wav` or `raw` PCM [default: wav, or raw
+                           for outputs ending in .raw or .pcm]
+  -e, --encoding ENCODING  Sample encoding, `s16`, `s32` or `f32`
+                           [default: s16]
+  -s, --start SECONDS      Start decoding at this position
+  -t, --end SECONDS        Stop decoding at this position
+  -g, --gain DB            Apply a gain in decibels
+  -h, --help               Print this message
+";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Wav,
+    Raw,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReportFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    S16,
+    S32,
+    F32,
+}
+
+impl Encoding {
+    fn bytes_per_sample(self) -> u16 {
+        match self {
+            Encoding::S16 => 2,
+            Encoding::S32 | Encoding::F32 => 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Options {
+    input: String,
+    output: Option<String>,
+    format: Format,
+    encoding: Encoding,
+    start: Option<Duration>,
+    end: Option<Duration>,
+    gain_db: Option<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Command {
+    Decode(Options),
+    Inspect { input: String, format: ReportFormat },
+}
+
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Io(io::Error),
+    Decode(SimplemadError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CliError::Usage(ref message) => write!(f, "{}\n\n{}", message, USAGE),
+            CliError::Io(ref error) => write!(f, "{}", error),
+            CliError::Decode(ref error) => write!(f, "decoding failed: {}", error),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> CliError {
+        CliError::Io(error)
+    }
+}
+
+impl From<SimplemadError> for CliError {
+    fn from(error: SimplemadError) -> CliError {
+        match error {
+            SimplemadError::Read(error) | SimplemadError::Write(error) => CliError::Io(error),
+            error => CliError::Decode(error),
+        }
+    }
+}
+
+// Parse the arguments after the program name, `None` asks for the usage
+fn parse_command<I>(args: I) -> Result<Option<Command>, CliError>
+    where I: IntoIterator<Item = String>
+{
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(String::as_str) != Some("inspect") {
+        return Ok(parse_args(args)?.map(Command::Decode));
+    }
+    args.next();
+
+    let mut input = None;
+    let mut format = ReportFormat::Text;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-f" | "--format" => {
+                let value = args.next()
+                                .ok_or_else(|| CliError::Usage(format!("{} needs a value", arg)))?;
+                format = match value.as_str() {
+                    "text" => ReportFormat::Text,
+                    "csv" => ReportFormat::Csv,
+                    "json" => ReportFormat::Json,
+                    other => return Err(CliError::Usage(format!("unknown format `{}`", other))),
+                };
+            }
+            flag if flag.starts_with('-') => {
+                return Err(CliError::Usage(format!("unknown option `{}`", flag)));
+            }
+            _ if input.is_some() => {
+                return Err(CliError::Usage(format!("unexpected argument `{}`", arg)));
+            }
+            _ => input = Some(arg),
+        }
+    }
+
+    match input {
+        Some(input) => Ok(Some(Command::Inspect { input, format })),
+        None => Err(CliError::Usage("missing INPUT".to_string())),
+    }
+}
+
+// Parse the arguments of the decode command
+fn parse_args<I>(args: I) -> Result<Option<Options>, CliError>
+    where I: IntoIterator<Item = String>
+{
+    let mut args = args.into_iter();
+    let mut paths = Vec::new();
+    let mut format = None;
+    let mut encoding = Encoding::S16;
+    let (mut start, mut end, mut gain_db) = (None, None, None);
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next().ok_or_else(|| CliError::Usage(format!("{} needs a value", name)))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-f" | "--format" => {
+                format = Some(match value(&arg)?.as_str() {
+                    "wav" => Format::Wav,
+                    "raw" => Format::Raw,
+                    other => return Err(CliError::Usage(format!("unknown format `{}`", other))),
+                });
+            }
+            "-e" | "--encoding" => {
+                encoding = match value(&arg)?.as_str() {
+                    "s16" => Encoding::S16,
+                    "s32" => Encoding::S32,
+                    "f32" => Encoding::F32,
+                    other => {
+                        return Err(CliError::Usage(format!("unknown encoding `{}`", other)))
+                    }
+                };
+            }
+            "-s" | "--start" => start = Some(parse_seconds(&arg, &value(&arg)?)?),
+            "-t" | "--end" => end = Some(parse_seconds(&arg, &value(&arg)?)?),
+            "-g" | "--gain" => {
+                let gain = value(&arg)?;
+                gain_db = Some(gain.parse::<f32>().map_err(|_| {
+                    CliError::Usage(format!("invalid gain `{}`", gain))
+                })?);
+            }
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(CliError::Usage(format!("unknown option `{}`", flag)));
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    let mut paths = paths.into_iter();
+    let input = match paths.next() {
+        Some(input) => input,
+        None => return Err(CliError::Usage("missing INPUT".to_string())),
+    };
+    let output = paths.next().filter(|output| output != "-");
+    if let Some(extra) = paths.next() {
+        return Err(CliError::Usage(format!("unexpected argument `{}`", extra)));
+    }
+
+    let format = format.unwrap_or_else(|| {
+        match output {
+            Some(ref output) if output.ends_with(".raw") || output.ends_with(".pcm") => Format::Raw,
+            _ => Format::Wav,
+        }
+    });
+    Ok(Some(Options {
+        input,
+        output,
+        format,
+        encoding,
+        start,
+        end,
+        gain_db,
+    }))
+}
+
+fn parse_seconds(name: &str, value: &str) -> Result<Duration, CliError> {
+    value.parse::<f64>()
+         .ok()
+         .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+         .ok_or_else(|| CliError::Usage(format!("invalid {} `{}`", name, value)))
+}
+
+// The stream parameters written to a WAV header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct WavSpec {
+    sample_rate: u32,
+    channels: u16,
+    encoding: Encoding,
+}
+
+// Write a WAV header for `data_len` bytes of samples, `u32::MAX` if unknown
+fn write_wav_header<W: Write>(writer: &mut W, spec: WavSpec, data_len: u32) -> io::Result<()> {
+    let bytes_per_sample = spec.encoding.bytes_per_sample();
+    let block_align = spec.channels * bytes_per_sample;
+    // PCM or IEEE float
+    let format_tag: u16 = if spec.encoding == Encoding::F32 { 3 } else { 1 };
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&data_len.saturating_add(36).to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&format_tag.to_le_bytes())?;
+    writer.write_all(&spec.channels.to_le_bytes())?;
+    writer.write_all(&spec.sample_rate.to_le_bytes())?;
+    writer.write_all(&(spec.sample_rate * u32::from(block_align)).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&(bytes_per_sample * 8).to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())
+}
+
+fn write_frame<W: Write>(writer: &mut W, frame: &Frame, encoding: Encoding) -> io::Result<u64> {
+    match encoding {
+        Encoding::S16 => frame.write_pcm::<i16>(writer),
+        Encoding::S32 => frame.write_pcm::<i32>(writer),
+        Encoding::F32 => frame.write_pcm::<f32>(writer),
+    }
+}
+
+// Decode `reader` to `writer`, returning the WAV parameters and the number
+// of bytes of samples written
+fn decode<R, W>(reader: R, writer: &mut W, options: &Options) -> Result<(WavSpec, u64), CliError>
+    where R: io::Read,
+          W: Write
+{
+    let mut builder = Decoder::builder(reader).error_policy(ErrorPolicy::SkipRecoverable);
+    if let Some(start) = options.start {
+        builder = builder.start_time(start);
+    }
+    if let Some(end) = options.end {
+        builder = builder.end_time(end);
+    }
+    if let Some(gain_db) = options.gain_db {
+        builder = builder.gain_db(gain_db);
+    }
+    let mut decoder = builder.build()?;
+
+    let mut spec = None;
+    let mut data_len = 0;
+    loop {
+        let frame = match decoder.get_frame() {
+            Ok(frame) => frame,
+            Err(SimplemadError::EOF) => break,
+            Err(error) => return Err(error.into()),
+        };
+        if spec.is_none() {
+            let first = WavSpec {
+                sample_rate: frame.sample_rate,
+                channels: frame.n_channels() as u16,
+                encoding: options.encoding,
+            };
+            if options.format == Format::Wav {
+                write_wav_header(writer, first, u32::MAX)?;
+            }
+            spec = Some(first);
+        }
+        data_len += write_frame(writer, &frame, options.encoding)?;
+    }
+
+    match spec {
+        Some(spec) => Ok((spec, data_len)),
+        None => Err(CliError::Decode(SimplemadError::EOF)),
+    }
+}
+
+fn run(options: &Options) -> Result<(), CliError> {
+    let input = BufReader::new(File::open(&options.input).map_err(|e| with_path(e, &options.input))?);
+    match options.output {
+        Some(ref path) => {
+            let mut output = BufWriter::new(File::create(path).map_err(|e| with_path(e, path))?);
+            let (spec, data_len) = decode(input, &mut output, options)?;
+            // The length is known now, unless the data is too long for WAV
+            if options.format == Format::Wav && data_len <= u64::from(u32::MAX - 36) {
+                output.seek(SeekFrom::Start(0))?;
+                write_wav_header(&mut output, spec, data_len as u32)?;
+            }
+            output.flush()?;
+        }
+        None => {
+            let stdout = io::stdout();
+            let mut output = BufWriter::new(stdout.lock());
+            decode(input, &mut output, options)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn run_inspect(input: &str, format: ReportFormat) -> Result<(), CliError> {
+    let file = BufReader::new(File::open(input).map_err(|e| with_path(e, input))?);
+    let inspection = inspect(file)?;
+    let stdout = io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+    match format {
+        ReportFormat::Text => writeln!(output, "{}", inspection)?,
+        ReportFormat::Csv => inspection.write_csv(&mut output)?,
+        ReportFormat::Json => inspection.write_json(&mut output)?,
+    }
+    output.flush()?;
+    Ok(())
+}
+
+fn with_path(error: io::Error, path: &str) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path, error))
+}
+
+fn main() {
+    let result = parse_command(env::args().skip(1)).and_then(|command| {
+        match command {
+            Some(Command::Decode(options)) => run(&options),
+            Some(Command::Inspect { input, format }) => run_inspect(&input, format),
+            None => {
+                print!("{}", USAGE);
+                Ok(())
+            }
+        }
+    });
+    if let Err(error) = result {
+        eprintln!("simplemad-cli: {}", error);
+        process::exit(match error {
+            CliError::Usage(_) => 2,
+            _ => 1,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn args(args: &[&str]) -> Result<Option<Options>, CliError> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = args(&["-s", "1.5", "--end", "3", "-g", "-6", "in.mp3", "out.raw"])
+                          .unwrap()
+                          .unwrap();
+        assert_eq!(options.input, "in.mp3");
+        assert_eq!(options.output, Some("out.raw".to_string()));
+        assert_eq!(options.format, Format::Raw);
+        assert_eq!(options.encoding, Encoding::S16);
+        assert_eq!(options.start, Some(Duration::from_millis(1500)));
+        assert_eq!(options.end, Some(Duration::from_secs(3)));
+        assert_eq!(options.gain_db, Some(-6.0));
+
+        let options = args(&["-e", "f32", "-f", "wav", "in.mp3", "-"]).unwrap().unwrap();
+        assert_eq!(options.output, None);
+        assert_eq!(options.format, Format::Wav);
+        assert_eq!(options.encoding, Encoding::F32);
+
+        assert!(args(&["--help"]).unwrap().is_none());
+        assert!(matches!(args(&[]), Err(CliError::Usage(_))));
+        assert!(matches!(args(&["-s", "soon", "in.mp3"]), Err(CliError::Usage(_))));
+        assert!(matches!(args(&["--verbose", "in.mp3"]), Err(CliError::Usage(_))));
+        assert!(matches!(args(&["in.mp3", "-g"]), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let command = |args: &[&str]| parse_command(args.iter().map(|arg| arg.to_string()));
+
+        assert_eq!(command(&["inspect", "in.mp3"]).unwrap(),
+                   Some(Command::Inspect {
+                       input: "in.mp3".to_string(),
+                       format: ReportFormat::Text,
+                   }));
+        assert_eq!(command(&["inspect", "--format", "json", "in.mp3"]).unwrap(),
+                   Some(Command::Inspect {
+                       input: "in.mp3".to_string(),
+                       format: ReportFormat::Json,
+                   }));
+        match command(&["-t", "1", "in.mp3"]).unwrap() {
+            Some(Command::Decode(options)) => assert_eq!(options.input, "in.mp3"),
+            other => panic!("expected a decode command, got {:?}", other),
+        }
+
+        assert!(command(&["inspect", "-h"]).unwrap().is_none());
+        assert!(matches!(command(&["inspect"]), Err(CliError::Usage(_))));
+        assert!(matches!(command(&["inspect", "-f", "xml", "in.mp3"]), Err(CliError::Usage(_))));
+        assert!(matches!(command(&["inspect", "a.mp3", "b.mp3"]), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn test_decode_wav() {
+        let options = args(&["-t", "1", "sample_mp3s/constant_stereo_128.mp3"]).unwrap().unwrap();
+        let input = File::open(&options.input).unwrap();
+        let mut output = Cursor::new(Vec::new());
+        let (spec, data_len) = decode(input, &mut output, &options).unwrap();
+        assert_eq!(spec,
+                   WavSpec { sample_rate: 44100, channels: 2, encoding: Encoding::S16 });
+
+        let output = output.into_inner();
+        assert_eq!(output.len() as u64, 44 + data_len);
+        assert_eq!(&output[..4], b"RIFF");
+        assert_eq!(&output[8..16], b"WAVEfmt ");
+        assert_eq!(&output[40..44], &u32::MAX.to_le_bytes());
+        // 1 second is just over 38 frames of 1152 samples of 2 channels
+        assert_eq!(data_len, 39 * 1152 * 2 * 2);
+    }
+
+    #[test]
+    fn test_decode_raw() {
+        let options = args(&["-e", "f32", "-f", "raw", "-t", "1",
+                             "sample_mp3s/constant_single_channel_128.mp3"])
+                          .unwrap()
+                          .unwrap();
+        let input = File::open(&options.input).unwrap();
+        let mut output = Vec::new();
+        let (spec, data_len) = decode(input, &mut output, &options).unwrap();
+        assert_eq!(spec.channels, 1);
+        assert_eq!(output.len() as u64, data_len);
+        assert!(output.chunks(4).all(|bytes| {
+            let sample = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            (-1.0..=1.0).contains(&sample)
+        }));
+    }
+
+    #[test]
+    fn test_write_wav_header() {
+        let mut header = Vec::new();
+        let spec = WavSpec { sample_rate: 48000, channels: 2, encoding: Encoding::F32 };
+        write_wav_header(&mut header, spec, 800).unwrap();
+        assert_eq!(header.len(), 44);
+        assert_eq!(&header[4..8], &836u32.to_le_bytes());
+        assert_eq!(&header[20..22], &3u16.to_le_bytes());
+        assert_eq!(&header[28..32], &(48000u32 * 8).to_le_bytes());
+        assert_eq!(&header[32..34], &8u16.to_le_bytes());
+        assert_eq!(&header[34..36], &32u16.to_le_bytes());
+    }
+}
@@ -0,0 +1,551 @@
+use std::io;
+use std::os::raw::c_int;
+use std::time::Duration;
+use simplemad_sys::*;
+use format::FormatObserver;
+use processor::Processor;
+use {Channel, Decoder, ErrorContext, FadeCurve, FormatChange, Frame, Layer, SampleFormat,
+     SimplemadError};
+
+/// The default size of the decoder's input buffer in bytes
+pub const DEFAULT_BUFFER_SIZE: usize = 32_768;
+
+/// The smallest input buffer that can hold any MPEG audio frame
+pub const MIN_BUFFER_SIZE: usize = 4_096;
+
+/// How the decoder treats errors reported by libmad
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Return every error to the caller
+    #[default]
+    Report,
+    /// Silently skip errors that libmad can recover from
+    SkipRecoverable,
+}
+
+/// What replaces the samples of a frame whose audio data can't be decoded,
+/// see `DecoderBuilder::concealment`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Concealment {
+    /// Silence
+    Silence,
+    /// The samples of the previous frame, or silence if there is none
+    Repeat,
+}
+
+/// How strictly the decoder treats damaged streams
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Resynchronize and keep decoding after lost sync or bad frame data
+    #[default]
+    Lenient,
+    /// Stop at the first lost sync or bad frame data after the start of the
+    /// audio data, other than at trailing ID3 or APE tags
+    Strict,
+}
+
+/// How stereo output is mixed down to a single channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Downmix {
+    /// Average both channels, -6 dB for correlated content, never clips
+    Average,
+    /// Sum both channels at -3 dB, keeping the loudness of uncorrelated
+    /// content
+    ConstantPower,
+}
+
+// 1/sqrt(2) in libmad's fixed-point format
+const MINUS_3_DB: i64 = 0x0b50_4f33;
+
+impl Downmix {
+    pub(crate) fn mix(self, left: i32, right: i32) -> i32 {
+        let sum = i64::from(left) + i64::from(right);
+        let mixed = match self {
+            Downmix::Average => sum / 2,
+            Downmix::ConstantPower => (sum * MINUS_3_DB) >> 28,
+        };
+        mixed.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+    }
+}
+
+pub(crate) type ErrorObserver = Box<dyn FnMut(&SimplemadError, ErrorContext) + Send>;
+pub(crate) type FrameObserver = Box<dyn FnMut(&Frame) + Send>;
+
+/// Options controlling the decoding operation
+#[derive(Clone, Debug)]
+pub struct DecoderOptions {
+    /// Skip frames before this position
+    pub start_time: Option<Duration>,
+    /// Stop decoding at this position
+    pub end_time: Option<Duration>,
+    /// Decode only the header information of each frame
+    pub headers_only: bool,
+    /// Size of the input buffer in bytes
+    pub buffer_size: usize,
+    /// How errors reported by libmad are handled
+    pub error_policy: ErrorPolicy,
+    /// Decode frames even if their CRC check fails
+    pub ignore_crc: bool,
+    /// Synthesize output at half the stream's sample rate
+    pub half_sample_rate: bool,
+    /// libmad `MAD_OPTION_*` bits set in addition to the named options
+    pub raw_mad_options: c_int,
+    /// Only accept frames of these layers
+    pub layers: Option<Vec<Layer>>,
+    /// Give up after this many errors without a good frame in between
+    pub max_consecutive_errors: Option<usize>,
+    /// How strictly damaged streams are treated
+    pub strictness: Strictness,
+    /// Give up after scanning this many bytes without finding a frame
+    pub max_resync_bytes: Option<u64>,
+    /// Total length of the input in bytes, if known
+    pub input_len: Option<u64>,
+    /// Mix stereo output down to a single channel
+    pub downmix: Option<Downmix>,
+    /// The input channel of each output channel
+    pub channels: Option<Vec<Channel>>,
+    /// Gain applied to the samples in decibels
+    pub gain_db: Option<f32>,
+    /// Fade in over this long from the first decoded frame
+    pub fade_in: Option<(Duration, FadeCurve)>,
+    /// Fade out over this long before `end_time` or the end of the stream
+    pub fade_out: Option<(Duration, FadeCurve)>,
+    /// Keep a copy of each frame's encoded bytes in `Frame::encoded`
+    pub keep_encoded: bool,
+    /// Keep frames decoded by `Decoder::decode_at` up to this many bytes
+    pub frame_cache_bytes: Option<usize>,
+    /// Undo the pre-emphasis signalled in the frame headers
+    pub de_emphasis: bool,
+    /// Replace frames with damaged audio data instead of returning errors
+    pub concealment: Option<Concealment>,
+    /// Stop with an error when the format changes mid-stream
+    pub consistent_format: bool,
+    /// The sample format `Decoder::write_pcm` writes
+    pub sample_format: SampleFormat,
+}
+
+impl Default for DecoderOptions {
+    fn default() -> DecoderOptions {
+        DecoderOptions {
+            start_time: None,
+            end_time: None,
+            headers_only: false,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            error_policy: ErrorPolicy::Report,
+            ignore_crc: false,
+            half_sample_rate: false,
+            raw_mad_options: 0,
+            layers: None,
+            max_consecutive_errors: None,
+            strictness: Strictness::Lenient,
+            max_resync_bytes: None,
+            input_len: None,
+            downmix: None,
+            channels: None,
+            gain_db: None,
+            fade_in: None,
+            fade_out: None,
+            keep_encoded: false,
+            frame_cache_bytes: None,
+            de_emphasis: false,
+            concealment: None,
+            consistent_format: false,
+            sample_format: SampleFormat::Fixed,
+        }
+    }
+}
+
+impl DecoderOptions {
+    pub(crate) fn mad_options(&self) -> c_int {
+        let mut options = self.raw_mad_options;
+        if self.ignore_crc {
+            options |= MAD_OPTION_IGNORECRC;
+        }
+        if self.half_sample_rate {
+            options |= MAD_OPTION_HALFSAMPLERATE;
+        }
+        options
+    }
+
+    // Carry raw libmad bits over to the named options, so concealed frames
+    // match decoded ones. libmad itself doesn't act on the channel bits.
+    pub(crate) fn normalized(mut self) -> DecoderOptions {
+        let raw = self.raw_mad_options;
+        self.ignore_crc |= raw & MAD_OPTION_IGNORECRC != 0;
+        self.half_sample_rate |= raw & MAD_OPTION_HALFSAMPLERATE != 0;
+        match raw & MAD_OPTION_SINGLECHANNEL {
+            0 => {}
+            MAD_OPTION_LEFTCHANNEL if self.channels.is_none() => {
+                self.channels = Some(vec![Channel::Left]);
+            }
+            MAD_OPTION_RIGHTCHANNEL if self.channels.is_none() => {
+                self.channels = Some(vec![Channel::Right]);
+            }
+            MAD_OPTION_SINGLECHANNEL if self.downmix.is_none() => {
+                self.downmix = Some(Downmix::Average);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    // The gain as a factor in libmad's fixed-point format
+    pub(crate) fn gain_factor(&self) -> Option<i64> {
+        self.gain_db.map(db_to_factor)
+    }
+}
+
+pub(crate) fn db_to_factor(gain_db: f32) -> i64 {
+    (10f64.powf(f64::from(gain_db) / 20.0) * f64::from(1 << 28)) as i64
+}
+
+pub(crate) fn apply_gain(sample: i32, factor: i64) -> i32 {
+    let scaled = (i64::from(sample) * factor) >> 28;
+    scaled.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+}
+
+/// A builder for configuring a `Decoder`
+///
+/// Create a builder using `Decoder::builder`, chain the setters and
+/// finish with `build`.
+pub struct DecoderBuilder<R>
+    where R: io::Read
+{
+    reader: R,
+    options: DecoderOptions,
+    error_observer: Option<ErrorObserver>,
+    format_observer: Option<FormatObserver>,
+    frame_observer: Option<FrameObserver>,
+    processors: Vec<Box<dyn Processor>>,
+    tee: Option<Box<dyn io::Write + Send>>,
+}
+
+impl<R> DecoderBuilder<R> where R: io::Read {
+    pub(crate) fn new(reader: R) -> DecoderBuilder<R> {
+        DecoderBuilder {
+            reader,
+            options: Default::default(),
+            error_observer: None,
+            format_observer: None,
+            frame_observer: None,
+            processors: Vec::new(),
+            tee: None,
+        }
+    }
+
+    /// Replace all options at once
+    pub fn options(mut self, options: DecoderOptions) -> DecoderBuilder<R> {
+        self.options = options;
+        self
+    }
+
+    /// Decode part of a file from `start_time` to `end_time`
+    pub fn interval(mut self, start_time: Duration, end_time: Duration) -> DecoderBuilder<R> {
+        self.options.start_time = Some(start_time);
+        self.options.end_time = Some(end_time);
+        self
+    }
+
+    /// Skip frames before `start_time`
+    pub fn start_time(mut self, start_time: Duration) -> DecoderBuilder<R> {
+        self.options.start_time = Some(start_time);
+        self
+    }
+
+    /// Stop decoding at `end_time`
+    pub fn end_time(mut self, end_time: Duration) -> DecoderBuilder<R> {
+        self.options.end_time = Some(end_time);
+        self
+    }
+
+    /// Decode only the header information of each frame
+    pub fn headers_only(mut self, headers_only: bool) -> DecoderBuilder<R> {
+        self.options.headers_only = headers_only;
+        self
+    }
+
+    /// Keep a copy of each frame's encoded bytes in `Frame::encoded`
+    ///
+    /// Without copying, `Decoder::frame_bytes` borrows the bytes of the
+    /// most recent frame instead.
+    pub fn keep_encoded(mut self, keep_encoded: bool) -> DecoderBuilder<R> {
+        self.options.keep_encoded = keep_encoded;
+        self
+    }
+
+    /// Keep the most recently used frames of `Decoder::decode_at` and
+    /// `decode_window` in memory, up to `max_bytes`
+    ///
+    /// Frames requested again are returned from the cache instead of being
+    /// decoded, which helps while scrubbing back and forth over a region.
+    pub fn frame_cache(mut self, max_bytes: usize) -> DecoderBuilder<R> {
+        self.options.frame_cache_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Return a frame of made up samples for each frame whose header was
+    /// read but whose audio data is damaged, instead of an error
+    ///
+    /// The frame has the duration and position of the damaged one and
+    /// `Frame::concealed` set, so the timeline has no gaps. The error still
+    /// reaches `on_error` and the decoder's statistics. Errors without a
+    /// frame header, such as lost synchronization, aren't concealed.
+    pub fn concealment(mut self, concealment: Concealment) -> DecoderBuilder<R> {
+        self.options.concealment = Some(concealment);
+        self
+    }
+
+    /// Stop with `SimplemadError::FormatChanged` when the sample rate,
+    /// channel count or layer differs from the previous frame's
+    ///
+    /// By default such frames are returned like any other and only reported
+    /// to `on_format_change`. Ingestion pipelines that must reject malformed
+    /// uploads want this.
+    pub fn consistent_format(mut self, consistent_format: bool) -> DecoderBuilder<R> {
+        self.options.consistent_format = consistent_format;
+        self
+    }
+
+    /// Set the size of the input buffer in bytes
+    ///
+    /// Sizes below `MIN_BUFFER_SIZE` are rounded up.
+    pub fn buffer_size(mut self, buffer_size: usize) -> DecoderBuilder<R> {
+        self.options.buffer_size = buffer_size;
+        self
+    }
+
+    /// Set the sample format `Decoder::write_pcm` writes
+    pub fn sample_format(mut self, sample_format: SampleFormat) -> DecoderBuilder<R> {
+        self.options.sample_format = sample_format;
+        self
+    }
+
+    /// Set how errors reported by libmad are handled
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> DecoderBuilder<R> {
+        self.options.error_policy = error_policy;
+        self
+    }
+
+    /// Decode frames even if their CRC check fails
+    ///
+    /// By default frames with a damaged CRC word are reported as
+    /// `LibmadError::BadCRC`, most players ignore them instead.
+    pub fn ignore_crc(mut self, ignore_crc: bool) -> DecoderBuilder<R> {
+        self.options.ignore_crc = ignore_crc;
+        self
+    }
+
+    /// Synthesize output at half the stream's sample rate
+    ///
+    /// Decoding is roughly twice as fast, which is useful for previews and
+    /// analysis that don't need full fidelity. Frames report the reduced
+    /// sample rate and contain half as many samples, their duration is
+    /// unchanged.
+    pub fn half_sample_rate(mut self, half_sample_rate: bool) -> DecoderBuilder<R> {
+        self.options.half_sample_rate = half_sample_rate;
+        self
+    }
+
+    /// Set libmad options by their `MAD_OPTION_*` bits, such as
+    /// `simplemad::MAD_OPTION_IGNORECRC`
+    ///
+    /// The bits are passed to libmad as they are, together with the options
+    /// set by `ignore_crc` and `half_sample_rate`. `MAD_OPTION_LEFTCHANNEL`
+    /// and `MAD_OPTION_RIGHTCHANNEL` select that channel unless `channels` is
+    /// set, and `MAD_OPTION_SINGLECHANNEL` averages both unless `downmix` is.
+    pub fn raw_mad_options(mut self, bits: c_int) -> DecoderBuilder<R> {
+        self.options.raw_mad_options = bits;
+        self
+    }
+
+    /// Only accept frames of the given layers
+    ///
+    /// Frames of any other layer are skipped and reported as
+    /// `SimplemadError::RejectedLayer`.
+    pub fn layers(mut self, layers: &[Layer]) -> DecoderBuilder<R> {
+        self.options.layers = Some(layers.to_vec());
+        self
+    }
+
+    /// Give up after `count` errors without a good frame in between
+    ///
+    /// The decoder then returns `SimplemadError::TooManyErrors` and stops,
+    /// which keeps inputs that aren't MPEG audio from being scanned forever.
+    /// Errors skipped by `ErrorPolicy::SkipRecoverable` are counted too.
+    pub fn max_consecutive_errors(mut self, count: usize) -> DecoderBuilder<R> {
+        self.options.max_consecutive_errors = Some(count);
+        self
+    }
+
+    /// Set how strictly damaged streams are treated
+    ///
+    /// In strict mode the first error after the start of the audio data
+    /// is returned and decoding stops. Validation tools want this, players
+    /// usually don't.
+    pub fn strictness(mut self, strictness: Strictness) -> DecoderBuilder<R> {
+        self.options.strictness = strictness;
+        self
+    }
+
+    /// Give up after scanning `bytes` bytes without finding a frame
+    ///
+    /// The decoder then returns `SimplemadError::SyncLimitExceeded` and
+    /// stops instead of reading a large input that isn't MPEG audio to the
+    /// end. Metadata at the start of a file counts towards the limit.
+    pub fn max_resync_bytes(mut self, bytes: u64) -> DecoderBuilder<R> {
+        self.options.max_resync_bytes = Some(bytes);
+        self
+    }
+
+    /// Set the total length of the input in bytes
+    ///
+    /// Streams without a Xing or Info header use this to estimate their
+    /// number of frames for `Iterator::size_hint` and
+    /// `Decoder::estimated_total_duration`. `Decoder::progress` uses it for
+    /// every stream. See `measure_input_len` for seekable inputs.
+    pub fn input_len(mut self, input_len: u64) -> DecoderBuilder<R> {
+        self.options.input_len = Some(input_len);
+        self
+    }
+
+    /// Mix stereo output down to a single channel
+    ///
+    /// Frames of stereo streams then have one channel of samples, which
+    /// halves the output for speech recognition and other mono consumers.
+    pub fn downmix(mut self, downmix: Downmix) -> DecoderBuilder<R> {
+        self.options.downmix = Some(downmix);
+        self
+    }
+
+    /// Choose the output channels
+    ///
+    /// Each output channel is a copy of the given input channel, so
+    /// `&[Channel::Left]` keeps only the left channel and
+    /// `&[Channel::Right, Channel::Left]` swaps them. The right channel of a
+    /// single channel stream is its only channel. Channels that aren't
+    /// listed aren't converted at all.
+    pub fn channels(mut self, channels: &[Channel]) -> DecoderBuilder<R> {
+        self.options.channels = Some(channels.to_vec());
+        self
+    }
+
+    /// Apply the de-emphasis filter to frames whose header signals 50/15 µs
+    /// or CCITT J.17 emphasis, see `DeEmphasis`
+    pub fn de_emphasis(mut self, de_emphasis: bool) -> DecoderBuilder<R> {
+        self.options.de_emphasis = de_emphasis;
+        self
+    }
+
+    /// Scale the samples by `gain_db` decibels
+    ///
+    /// The gain is applied in fixed point while decoding. Samples have
+    /// 3 bits of headroom above full scale, conversions to integer and
+    /// floating-point formats clip.
+    pub fn gain_db(mut self, gain_db: f32) -> DecoderBuilder<R> {
+        self.options.gain_db = Some(gain_db);
+        self
+    }
+
+    /// Fade in over `duration` from the first decoded frame
+    ///
+    /// The fade starts at `start_time` if one is set, which avoids a click
+    /// at the start of an extracted clip.
+    pub fn fade_in(mut self, duration: Duration, curve: FadeCurve) -> DecoderBuilder<R> {
+        self.options.fade_in = Some((duration, curve));
+        self
+    }
+
+    /// Fade out over `duration` before the end
+    ///
+    /// The fade ends at `end_time` if one is set, otherwise at the end of
+    /// the stream if its length is known from a Xing header or `input_len`.
+    pub fn fade_out(mut self, duration: Duration, curve: FadeCurve) -> DecoderBuilder<R> {
+        self.options.fade_out = Some((duration, curve));
+        self
+    }
+
+    /// Call `observer` for every error the decoder encounters
+    ///
+    /// This includes errors skipped by `ErrorPolicy::SkipRecoverable`, so
+    /// problems can be logged or counted without changing the frame loop.
+    pub fn on_error<F>(mut self, observer: F) -> DecoderBuilder<R>
+        where F: FnMut(&SimplemadError, ErrorContext) + Send + 'static
+    {
+        self.error_observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Call `observer` when the format of the decoded audio changes
+    ///
+    /// It is called for the first frame and whenever the sample rate,
+    /// number of channels or layer differs from the previous frame, e.g.
+    /// between chained streams, so sinks can be reconfigured before the
+    /// frame is used.
+    pub fn on_format_change<F>(mut self, observer: F) -> DecoderBuilder<R>
+        where F: FnMut(&FormatChange) + Send + 'static
+    {
+        self.format_observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Call `observer` for every decoded frame
+    ///
+    /// It sees each frame however the frames are consumed, including by
+    /// `pcm_stream`, `samples` and the other adapters, which makes it a
+    /// place for metering, logging or visualization.
+    pub fn on_frame<F>(mut self, observer: F) -> DecoderBuilder<R>
+        where F: FnMut(&Frame) + Send + 'static
+    {
+        self.frame_observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Add a processor to run on every decoded frame
+    ///
+    /// Processors run in the order they were added, after the built-in
+    /// options such as `gain_db` and `downmix`.
+    pub fn with_processor<P>(mut self, processor: P) -> DecoderBuilder<R>
+        where P: Processor + 'static
+    {
+        self.processors.push(Box::new(processor));
+        self
+    }
+
+    /// Copy every byte read from the input to `writer`
+    ///
+    /// The bytes are written as they are read, so the original stream can
+    /// be recorded while it is decoded. Data read again after `reset` is
+    /// written again.
+    pub fn tee<W>(mut self, writer: W) -> DecoderBuilder<R>
+        where W: io::Write + Send + 'static
+    {
+        self.tee = Some(Box::new(writer));
+        self
+    }
+
+    /// Create the `Decoder`
+    pub fn build(self) -> Result<Decoder<R>, SimplemadError> {
+        let mut decoder = Decoder::new(self.reader, self.options)?;
+        decoder.error_observer = self.error_observer;
+        decoder.format_observer = self.format_observer;
+        decoder.frame_observer = self.frame_observer;
+        decoder.processors = self.processors;
+        if let Some(mut tee) = self.tee {
+            // The first read happened while creating the decoder
+            tee.write_all(decoder.buffered_bytes())?;
+            decoder.tee = Some(tee);
+        }
+        Ok(decoder)
+    }
+}
+
+impl<R> DecoderBuilder<R> where R: io::Read + io::Seek {
+    /// Set `input_len` to the length of the rest of a seekable input
+    pub fn measure_input_len(mut self) -> io::Result<DecoderBuilder<R>> {
+        let start = self.reader.stream_position()?;
+        let end = self.reader.seek(io::SeekFrom::End(0))?;
+        self.reader.seek(io::SeekFrom::Start(start))?;
+        self.options.input_len = Some(end - start);
+        Ok(self)
+    }
+}
@@ -0,0 +1,159 @@
+//! Decoding several sources back to back
+
+use std::collections::VecDeque;
+use std::io;
+use std::iter::FusedIterator;
+use std::time::Duration;
+use {Decoder, DecoderOptions, Frame, SimplemadError};
+
+/// An item produced by `Chain`
+#[derive(Clone, Debug)]
+pub enum ChainItem {
+    /// The following frames come from another source
+    SourceStart {
+        /// Index of the source in the order they were given
+        index: usize,
+        /// Position of the start of the source in the chained stream
+        position: Duration,
+    },
+    /// A decoded frame, its position counts from the start of the first
+    /// source while its byte offset is within its own source
+    Frame(Frame),
+}
+
+/// An iterator decoding several sources one after another
+///
+/// Created by `Decoder::chain`. Each source is announced by a
+/// `ChainItem::SourceStart`, and errors of one source don't stop the
+/// others from being decoded. Every source starts with a fresh decoder, so
+/// for one stream split across several files, join the readers with
+/// `io::Read::chain` instead to decode the frames spanning them.
+pub struct Chain<R>
+    where R: io::Read
+{
+    readers: VecDeque<R>,
+    options: DecoderOptions,
+    decoder: Option<Decoder<R>>,
+    index: usize,
+    offset: Duration,
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    /// Decode `readers` back to back with continuous positions
+    pub fn chain<I>(readers: I) -> Chain<R>
+        where I: IntoIterator<Item = R>
+    {
+        Chain {
+            readers: readers.into_iter().collect(),
+            options: DecoderOptions::default(),
+            decoder: None,
+            index: 0,
+            offset: Duration::new(0, 0),
+        }
+    }
+}
+
+impl<R> Chain<R> where R: io::Read {
+    /// Set the options used to decode every source
+    ///
+    /// Intervals apply to each source separately.
+    pub fn options(mut self, options: DecoderOptions) -> Chain<R> {
+        self.options = options;
+        self
+    }
+
+    /// Get the index of the source being decoded
+    pub fn source_index(&self) -> Option<usize> {
+        self.decoder.as_ref().map(|_| self.index - 1)
+    }
+
+    /// Get the decoder of the current source
+    pub fn decoder(&self) -> Option<&Decoder<R>> {
+        self.decoder.as_ref()
+    }
+}
+
+impl<R> Iterator for Chain<R> where R: io::Read {
+    type Item = Result<ChainItem, SimplemadError>;
+    fn next(&mut self) -> Option<Result<ChainItem, SimplemadError>> {
+        loop {
+            let decoder = match self.decoder {
+                Some(ref mut decoder) => decoder,
+                None => {
+                    let reader = self.readers.pop_front()?;
+                    let index = self.index;
+                    self.index += 1;
+                    return Some(match Decoder::new(reader, self.options.clone()) {
+                        Ok(decoder) => {
+                            self.decoder = Some(decoder);
+                            Ok(ChainItem::SourceStart {
+                                index,
+                                position: self.offset,
+                            })
+                        }
+                        Err(e) => Err(e),
+                    });
+                }
+            };
+
+            match decoder.get_frame() {
+                Ok(mut frame) => {
+                    frame.position += self.offset;
+                    return Some(Ok(ChainItem::Frame(frame)));
+                }
+                Err(SimplemadError::EOF) => {
+                    self.offset += decoder.position;
+                    self.decoder = None;
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl<R> FusedIterator for Chain<R> where R: io::Read {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_chain() {
+        let paths = ["sample_mp3s/constant_stereo_128.mp3",
+                     "sample_mp3s/constant_single_channel_128.mp3"];
+        let lengths: Vec<usize> = paths.iter()
+                                       .map(|path| {
+                                           Decoder::decode(File::open(path).unwrap())
+                                               .unwrap()
+                                               .filter(|r| r.is_ok())
+                                               .count()
+                                       })
+                                       .collect();
+
+        let items: Vec<ChainItem> = Decoder::chain(paths.iter().map(|p| File::open(p).unwrap()))
+                                        .filter_map(|r| r.ok())
+                                        .collect();
+        let starts: Vec<(usize, Duration)> = items.iter()
+                                                  .filter_map(|item| match *item {
+                                                      ChainItem::SourceStart { index, position } => {
+                                                          Some((index, position))
+                                                      }
+                                                      _ => None,
+                                                  })
+                                                  .collect();
+        assert_eq!(starts.len(), 2);
+        assert_eq!(starts[0], (0, Duration::new(0, 0)));
+        assert_eq!(starts[1].0, 1);
+
+        let frames: Vec<&Frame> = items.iter()
+                                       .filter_map(|item| match *item {
+                                           ChainItem::Frame(ref frame) => Some(frame),
+                                           _ => None,
+                                       })
+                                       .collect();
+        assert_eq!(frames.len(), lengths[0] + lengths[1]);
+        assert_eq!(frames[lengths[0]].position, starts[1].1);
+        assert!(frames.windows(2).all(|w| w[0].position + w[0].duration == w[1].position));
+    }
+}
@@ -0,0 +1,127 @@
+use std::io;
+use std::mem;
+use std::time::Duration;
+use {Decoder, DecoderOptions, DecoderStats, SimplemadError};
+
+// Layer III frames can refer to up to 511 bytes of earlier frames' data,
+// resuming this far back refills libmad's bit reservoir and synth filter
+const WARM_UP_BYTES: u64 = 1_024;
+
+// Number of recent frames remembered for choosing a warm-up frame
+pub(crate) const RECENT_FRAMES: usize = 64;
+
+/// A saved decoding position that can be resumed later
+///
+/// Byte offsets count from the start of the input. The fields are public so
+/// that the checkpoint can be stored in any format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeCheckpoint {
+    /// Byte offset of the next frame
+    pub byte_offset: u64,
+    /// The position at the start of the next frame
+    pub position: Duration,
+    /// The number of frames decoded before the checkpoint
+    pub frame_index: u64,
+    /// Byte offset of an earlier frame to start decoding from, so that
+    /// the next frame decodes as it would have without interruption
+    pub warm_up_offset: u64,
+    /// The total number of frames, if known
+    pub total_frames: Option<u64>,
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    /// Save the current decoding position
+    pub fn checkpoint(&self) -> DecodeCheckpoint {
+        let warm_up_offset = self.recent_frames
+                                 .iter()
+                                 .rev()
+                                 .find(|&&offset| offset + WARM_UP_BYTES <= self.sync_offset)
+                                 .or_else(|| self.recent_frames.front())
+                                 .cloned()
+                                 .unwrap_or(self.sync_offset);
+
+        DecodeCheckpoint {
+            byte_offset: self.sync_offset,
+            position: self.position,
+            frame_index: self.frame_index,
+            warm_up_offset,
+            total_frames: self.total_frames,
+        }
+    }
+}
+
+impl<R> Decoder<R> where R: io::Read + io::Seek {
+    /// Continue decoding `reader` from a saved position
+    ///
+    /// `reader` has to be the same input the checkpoint was taken from.
+    /// The frames between the warm-up offset and the checkpoint are decoded
+    /// and discarded.
+    pub fn resume(reader: R,
+                  options: DecoderOptions,
+                  checkpoint: DecodeCheckpoint)
+                  -> Result<Decoder<R>, SimplemadError> {
+        let mut reader = reader;
+        reader.seek(io::SeekFrom::Start(checkpoint.warm_up_offset))?;
+
+        let mut decoder = Decoder::new(reader, options)?;
+        decoder.buffer_offset = checkpoint.warm_up_offset;
+        decoder.sync_offset = checkpoint.warm_up_offset;
+        // The start of the audio data wasn't seen, `reset` rewinds to the
+        // start of the input
+        decoder.audio_offset = Some(0);
+        decoder.decode_to_checkpoint(checkpoint)?;
+        decoder.stats = DecoderStats::default();
+        decoder.bit_rate_sum = 0;
+        Ok(decoder)
+    }
+
+    // Continue from a checkpoint of the same input, keeping the observers,
+    // processors and counters. The discarded frames before the checkpoint
+    // don't reach them.
+    pub(crate) fn restore(&mut self, checkpoint: DecodeCheckpoint) -> Result<(), SimplemadError> {
+        self.jump_to(checkpoint.warm_up_offset)?;
+
+        let stats = self.stats;
+        let bit_rate_sum = self.bit_rate_sum;
+        let error_observer = self.error_observer.take();
+        let format_observer = self.format_observer.take();
+        let frame_observer = self.frame_observer.take();
+        let processors = mem::take(&mut self.processors);
+        let events = self.events.take();
+        let result = self.decode_to_checkpoint(checkpoint);
+        self.stats = stats;
+        self.bit_rate_sum = bit_rate_sum;
+        self.error_observer = error_observer;
+        self.format_observer = format_observer;
+        self.frame_observer = frame_observer;
+        self.processors = processors;
+        self.events = events;
+        result
+    }
+
+    // Decode and discard the frames before the checkpoint, then continue
+    // with its position
+    fn decode_to_checkpoint(&mut self, checkpoint: DecodeCheckpoint) -> Result<(), SimplemadError> {
+        let start_time = self.options.start_time.take();
+        let result = loop {
+            if self.offset_of(self.mad.stream.next_frame) >= checkpoint.byte_offset {
+                break Ok(());
+            }
+            match self.get_frame() {
+                Ok(frame) => self.recycle(frame),
+                Err(ref e) if e.is_recoverable() => {}
+                Err(SimplemadError::EOF) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.options.start_time = start_time;
+        result?;
+
+        self.position = checkpoint.position;
+        self.frame_index = checkpoint.frame_index;
+        self.total_frames = checkpoint.total_frames;
+        self.sync_offset = checkpoint.byte_offset;
+        self.consecutive_errors = 0;
+        Ok(())
+    }
+}
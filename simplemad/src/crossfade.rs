@@ -0,0 +1,207 @@
+//! Crossfading from the end of one stream into the start of another
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+use {PcmStream, SimplemadError};
+
+// Samples read from a stream at a time
+const CHUNK_LEN: usize = 4_096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    // Playing the first stream, holding back the overlap
+    First,
+    // Mixing the held back samples with the start of the second stream
+    Overlap { index: usize, total: usize },
+    // Playing the rest of the second stream
+    Second,
+}
+
+/// A stream of interleaved `f32` samples that fades from one decoder into
+/// another
+///
+/// The last `overlap` of the first stream fades out while the second
+/// stream fades in. Both streams need the same sample rate and number of
+/// channels. The length of the first stream doesn't have to be known, its
+/// output is delayed by the overlap instead.
+pub struct Crossfade<A, B>
+    where A: io::Read,
+          B: io::Read
+{
+    first: PcmStream<A>,
+    second: PcmStream<B>,
+    overlap: Duration,
+    overlap_len: Option<usize>,
+    held: VecDeque<f32>,
+    incoming: VecDeque<f32>,
+    phase: Phase,
+    error: Option<SimplemadError>,
+}
+
+impl<A, B> Crossfade<A, B>
+    where A: io::Read,
+          B: io::Read
+{
+    /// Crossfade from `first` into `second` over `overlap`
+    pub fn new(first: PcmStream<A>, second: PcmStream<B>, overlap: Duration) -> Crossfade<A, B> {
+        Crossfade {
+            first,
+            second,
+            overlap,
+            overlap_len: None,
+            held: VecDeque::new(),
+            incoming: VecDeque::new(),
+            phase: Phase::First,
+            error: None,
+        }
+    }
+
+    /// Fill `out` with interleaved samples
+    ///
+    /// Behaves like `PcmStream::next_samples`, returning `Ok(0)` once both
+    /// streams are exhausted.
+    pub fn next_samples(&mut self, out: &mut [f32]) -> Result<usize, SimplemadError> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        let mut written = 0;
+        while written < out.len() {
+            match self.step(&mut out[written..]) {
+                Ok(0) if self.phase == Phase::Second => break,
+                Ok(count) => written += count,
+                Err(error) => {
+                    if written == 0 {
+                        return Err(error);
+                    }
+                    if error != SimplemadError::WouldBlock {
+                        self.error = Some(error);
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(written)
+    }
+
+    // Write some samples to `out`, returns 0 if none were available
+    fn step(&mut self, out: &mut [f32]) -> Result<usize, SimplemadError> {
+        match self.phase {
+            Phase::First => {
+                if let Some(overlap_len) = self.overlap_len {
+                    if self.held.len() > overlap_len {
+                        let count = (self.held.len() - overlap_len).min(out.len());
+                        for (sample, held) in out.iter_mut().zip(self.held.drain(..count)) {
+                            *sample = held;
+                        }
+                        return Ok(count);
+                    }
+                }
+
+                if fill(&mut self.first, &mut self.held)? == 0 {
+                    self.phase = Phase::Overlap {
+                        index: 0,
+                        total: self.held.len(),
+                    };
+                } else if self.overlap_len.is_none() {
+                    let rate = f64::from(self.first.sample_rate());
+                    let frames = (self.overlap.as_secs_f64() * rate) as usize;
+                    self.overlap_len = Some(frames * self.first.channels());
+                }
+                Ok(0)
+            }
+            Phase::Overlap { index, total } => {
+                if index == total {
+                    self.phase = Phase::Second;
+                    return Ok(0);
+                }
+                if self.incoming.is_empty() && fill(&mut self.second, &mut self.incoming)? == 0 {
+                    // The second stream is shorter than the overlap
+                    self.incoming.push_back(0.0);
+                }
+
+                let channels = self.first.channels().max(1);
+                let frames = (total / channels).max(1) as f32;
+                let count = out.len().min(total - index).min(self.incoming.len());
+                for (offset, sample) in out[..count].iter_mut().enumerate() {
+                    let progress = ((index + offset) / channels) as f32 / frames;
+                    let held = self.held.pop_front().unwrap_or(0.0);
+                    let incoming = self.incoming.pop_front().unwrap_or(0.0);
+                    *sample = held * (1.0 - progress) + incoming * progress;
+                }
+                self.phase = Phase::Overlap {
+                    index: index + count,
+                    total,
+                };
+                Ok(count)
+            }
+            Phase::Second => {
+                if !self.incoming.is_empty() {
+                    let count = self.incoming.len().min(out.len());
+                    for (sample, incoming) in out.iter_mut().zip(self.incoming.drain(..count)) {
+                        *sample = incoming;
+                    }
+                    return Ok(count);
+                }
+                self.second.next_samples(out)
+            }
+        }
+    }
+}
+
+// Read a chunk of samples from `stream` into `queue`
+fn fill<R>(stream: &mut PcmStream<R>, queue: &mut VecDeque<f32>) -> Result<usize, SimplemadError>
+    where R: io::Read
+{
+    let mut chunk = [0.0; CHUNK_LEN];
+    let count = stream.next_samples(&mut chunk)?;
+    queue.extend(&chunk[..count]);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use Decoder;
+
+    fn stream(path: &str) -> PcmStream<File> {
+        Decoder::decode(File::open(path).unwrap()).unwrap().pcm_stream()
+    }
+
+    fn read_all(next: &mut dyn FnMut(&mut [f32]) -> usize) -> Vec<f32> {
+        let mut samples = Vec::new();
+        let mut buffer = [0.0; 1_000];
+        loop {
+            match next(&mut buffer) {
+                0 => return samples,
+                count => samples.extend_from_slice(&buffer[..count]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_crossfade() {
+        let a = "sample_mp3s/constant_stereo_128.mp3";
+        let b = "sample_mp3s/constant_stereo_320.mp3";
+        let mut first = stream(a);
+        let first = read_all(&mut |out| first.next_samples(out).unwrap());
+        let mut second = stream(b);
+        let second = read_all(&mut |out| second.next_samples(out).unwrap());
+
+        let mut crossfade = Crossfade::new(stream(a), stream(b), Duration::from_millis(500));
+        let mixed = read_all(&mut |out| crossfade.next_samples(out).unwrap());
+
+        // Half a second of stereo samples at 44.1 kHz overlap
+        let overlap = 22_050 * 2;
+        assert_eq!(mixed.len(), first.len() + second.len() - overlap);
+        let start = first.len() - overlap;
+        assert_eq!(&mixed[..start], &first[..start]);
+        assert_eq!(&mixed[first.len()..], &second[overlap..]);
+        assert_eq!(mixed[start], first[start]);
+        let middle = start + overlap / 2;
+        let expected = (first[middle] + second[overlap / 2]) * 0.5;
+        assert!((mixed[middle] - expected).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,253 @@
+//! Splitting a stream into the tracks of a cue sheet
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+use {Decoder, DecoderBuilder, SimplemadError, Trim};
+
+// Cue sheet positions count frames of 1/75 seconds
+const CUE_FRAMES_PER_SECOND: u64 = 75;
+// Decoding starts this far before a track, as `start_time` skips the frame
+// containing the start
+const LEAD_IN: Duration = Duration::from_millis(100);
+
+/// The tracks of a single-file album
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CueSheet {
+    /// Title of the album
+    pub title: Option<String>,
+    /// Performer of the album
+    pub performer: Option<String>,
+    /// The tracks in order
+    pub tracks: Vec<CueTrack>,
+}
+
+/// A track of a `CueSheet`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CueTrack {
+    /// Track number
+    pub number: u32,
+    /// Title of the track
+    pub title: Option<String>,
+    /// Performer of the track
+    pub performer: Option<String>,
+    /// Position of the track's first sample (`INDEX 01`)
+    pub start: Duration,
+}
+
+/// The interval of a track, see `CueSheet::tracks`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrackInterval {
+    /// Track number
+    pub number: u32,
+    /// Position of the track's first sample
+    pub start: Duration,
+    /// Position after the track's last sample, `None` for the last track
+    pub end: Option<Duration>,
+}
+
+/// An error parsing a cue sheet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CueParseError {
+    /// The line the error was found on, starting at 1
+    pub line: usize,
+}
+
+impl CueSheet {
+    /// Parse the text of a cue sheet
+    ///
+    /// Only `TRACK`, `TITLE`, `PERFORMER` and `INDEX 01` commands are used,
+    /// all tracks are assumed to be in the same file.
+    pub fn parse(text: &str) -> Result<CueSheet, CueParseError> {
+        let mut sheet = CueSheet::default();
+        for (index, line) in text.lines().enumerate() {
+            let error = CueParseError { line: index + 1 };
+            let line = line.trim();
+            let (command, rest) = match line.find(char::is_whitespace) {
+                Some(split) => (&line[..split], line[split..].trim()),
+                None => (line, ""),
+            };
+
+            match command {
+                "TRACK" => {
+                    let number = rest.split_whitespace().next().ok_or(error)?;
+                    sheet.tracks.push(CueTrack {
+                        number: number.parse().map_err(|_| error)?,
+                        ..CueTrack::default()
+                    });
+                }
+                "TITLE" | "PERFORMER" => {
+                    let value = Some(rest.trim_matches('"').to_string());
+                    let (title, performer) = match sheet.tracks.last_mut() {
+                        Some(track) => (&mut track.title, &mut track.performer),
+                        None => (&mut sheet.title, &mut sheet.performer),
+                    };
+                    if command == "TITLE" {
+                        *title = value;
+                    } else {
+                        *performer = value;
+                    }
+                }
+                "INDEX" => {
+                    let mut fields = rest.split_whitespace();
+                    if fields.next() == Some("01") {
+                        let track = sheet.tracks.last_mut().ok_or(error)?;
+                        track.start = fields.next().and_then(parse_time).ok_or(error)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(sheet)
+    }
+
+    /// Create a cue sheet of untitled tracks starting at `starts`
+    pub fn from_start_times(starts: &[Duration]) -> CueSheet {
+        CueSheet {
+            tracks: starts.iter()
+                          .enumerate()
+                          .map(|(index, &start)| {
+                              CueTrack {
+                                  number: index as u32 + 1,
+                                  start,
+                                  ..CueTrack::default()
+                              }
+                          })
+                          .collect(),
+            ..CueSheet::default()
+        }
+    }
+
+    /// Get the interval of each track, ending where the next track starts
+    pub fn tracks(&self) -> Vec<TrackInterval> {
+        self.tracks
+            .iter()
+            .enumerate()
+            .map(|(index, track)| {
+                TrackInterval {
+                    number: track.number,
+                    start: track.start,
+                    end: self.tracks.get(index + 1).map(|next| next.start),
+                }
+            })
+            .collect()
+    }
+}
+
+impl TrackInterval {
+    /// Create a builder for a decoder of the track
+    ///
+    /// Decoding starts a little before the track, and the frames at its
+    /// edges are cut to the sample, so frame positions are those of the
+    /// whole stream.
+    pub fn builder<R>(&self, reader: R) -> DecoderBuilder<R>
+        where R: io::Read
+    {
+        let mut builder = Decoder::builder(reader)
+                              .start_time(self.start.checked_sub(LEAD_IN).unwrap_or_default())
+                              .with_processor(Trim::new(self.start, self.end));
+        if let Some(end) = self.end {
+            builder = builder.end_time(end);
+        }
+        builder
+    }
+
+    /// Create a decoder of the track, see `builder`
+    pub fn decoder<R>(&self, reader: R) -> Result<Decoder<R>, SimplemadError>
+        where R: io::Read
+    {
+        self.builder(reader).build()
+    }
+}
+
+// Parse `mm:ss:ff`
+fn parse_time(time: &str) -> Option<Duration> {
+    let mut fields = time.split(':').map(|field| field.parse::<u64>().ok());
+    let (minutes, seconds, frames) = match (fields.next(), fields.next(), fields.next()) {
+        (Some(Some(m)), Some(Some(s)), Some(Some(f))) => (m, s, f),
+        _ => return None,
+    };
+    if fields.next().is_some() || seconds >= 60 || frames >= CUE_FRAMES_PER_SECOND {
+        return None;
+    }
+
+    let frames = (minutes * 60 + seconds) * CUE_FRAMES_PER_SECOND + frames;
+    Some(Duration::from_nanos(frames * 1_000_000_000 / CUE_FRAMES_PER_SECOND))
+}
+
+impl fmt::Display for CueParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid cue sheet at line {}", self.line)
+    }
+}
+
+impl error::Error for CueParseError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+
+    const SHEET: &str = "PERFORMER \"The Band\"
+TITLE \"The Album\"
+FILE \"album.mp3\" MP3
+  TRACK 01 AUDIO
+    TITLE \"First\"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE \"Second\"
+    PERFORMER \"Guest\"
+    INDEX 00 00:01:50
+    INDEX 01 00:02:30
+";
+
+    #[test]
+    fn test_parse() {
+        let sheet = CueSheet::parse(SHEET).unwrap();
+        assert_eq!(sheet.title.as_deref(), Some("The Album"));
+        assert_eq!(sheet.performer.as_deref(), Some("The Band"));
+        assert_eq!(sheet.tracks.len(), 2);
+        assert_eq!(sheet.tracks[1].number, 2);
+        assert_eq!(sheet.tracks[1].title.as_deref(), Some("Second"));
+        assert_eq!(sheet.tracks[1].performer.as_deref(), Some("Guest"));
+        assert_eq!(sheet.tracks[1].start, Duration::from_millis(2_400));
+
+        let tracks = sheet.tracks();
+        assert_eq!(tracks[0].end, Some(Duration::from_millis(2_400)));
+        assert_eq!(tracks[1].end, None);
+        assert_eq!(sheet.tracks(),
+                   CueSheet::from_start_times(&[Duration::new(0, 0),
+                                                Duration::from_millis(2_400)])
+                       .tracks());
+
+        assert_eq!(CueSheet::parse("TRACK 01 AUDIO\nINDEX 01 00:61:00"),
+                   Err(CueParseError { line: 2 }));
+        assert_eq!(CueSheet::parse("INDEX 01 00:00:00"), Err(CueParseError { line: 1 }));
+    }
+
+    #[test]
+    fn test_track_decoders() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let total: usize = Decoder::decode(File::open(path).unwrap())
+                               .unwrap()
+                               .filter_map(|r| r.ok())
+                               .map(|f| f.n_samples())
+                               .sum();
+
+        let sheet = CueSheet::parse(SHEET).unwrap();
+        let lengths: Vec<usize> = sheet.tracks()
+                                       .iter()
+                                       .map(|track| {
+                                           track.decoder(File::open(path).unwrap())
+                                                .unwrap()
+                                                .filter_map(|r| r.ok())
+                                                .map(|f| f.n_samples())
+                                                .sum()
+                                       })
+                                       .collect();
+        assert!(lengths[0] >= 105_839 && lengths[0] <= 105_841);
+        assert_eq!(lengths[0] + lengths[1], total);
+    }
+}
@@ -0,0 +1,173 @@
+//! Copying a time range of a stream without re-encoding
+
+use std::collections::VecDeque;
+use std::io;
+use std::ops::Range;
+use std::time::Duration;
+use reservoir;
+use {Decoder, ErrorPolicy, Layer, SimplemadError, MAX_MAIN_DATA_BEGIN};
+
+// A frame before the range, with the length of its audio data
+struct Preceding {
+    encoded: Vec<u8>,
+    position: Duration,
+    data_len: usize,
+}
+
+/// What `cut` wrote to its output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CutSummary {
+    /// Position of the first written frame in the input
+    pub start: Duration,
+    /// Position of the end of the last written frame in the input
+    pub end: Duration,
+    /// Number of frames written
+    pub frames: u64,
+    /// Number of bytes written
+    pub bytes: u64,
+}
+
+/// Copy the frames covering `range` from `reader` to `writer`
+///
+/// The frames are copied unchanged, so the cut is only accurate to a frame.
+/// Layer III frames can use data stored in the frames before them, the
+/// earlier frames holding the data of the first frame of the range are
+/// copied as well. A Xing or Info header is left out since its totals would no
+/// longer be correct. Errors writing to `writer` are returned as
+/// `SimplemadError::Write`.
+pub fn cut<R, W>(reader: R, writer: W, range: Range<Duration>) -> Result<CutSummary, SimplemadError>
+    where R: io::Read,
+          W: io::Write
+{
+    let mut writer = writer;
+    let mut decoder = Decoder::builder(reader)
+                          .headers_only(true)
+                          .keep_encoded(true)
+                          .error_policy(ErrorPolicy::SkipRecoverable)
+                          .build()?;
+
+    // The frames before the range that the first frame may depend on
+    let mut reservoir: VecDeque<Preceding> = VecDeque::new();
+    let mut reservoir_len = 0;
+    let mut summary: Option<CutSummary> = None;
+    loop {
+        let mut frame = match decoder.get_frame() {
+            Ok(frame) => frame,
+            Err(SimplemadError::EOF) => break,
+            Err(e) => return Err(e),
+        };
+        if frame.position >= range.end {
+            break;
+        }
+        if decoder.frame_index == 1 && decoder.xing.is_some() {
+            continue;
+        }
+        let encoded = frame.encoded.take().unwrap_or_default();
+
+        let frame_end = frame.position + frame.duration;
+        if frame_end <= range.start {
+            if frame.layer == Layer::III {
+                let data_len = reservoir::side_info(&encoded).map_or(0, |info| info.1 as usize);
+                reservoir_len += data_len;
+                reservoir.push_back(Preceding {
+                    encoded,
+                    position: frame.position,
+                    data_len,
+                });
+                trim_reservoir(&mut reservoir, &mut reservoir_len, MAX_MAIN_DATA_BEGIN as usize);
+            } else {
+                reservoir.clear();
+                reservoir_len = 0;
+            }
+            continue;
+        }
+
+        if summary.is_none() {
+            let needed = frame.main_data_begin as usize;
+            trim_reservoir(&mut reservoir, &mut reservoir_len, needed);
+        }
+        let summary = summary.get_or_insert_with(|| {
+            let start = reservoir.front().map_or(frame.position, |front| front.position);
+            CutSummary { start, end: start, frames: 0, bytes: 0 }
+        });
+        let preceding = reservoir.drain(..).map(|preceding| preceding.encoded);
+        for bytes in preceding.chain(Some(encoded)) {
+            writer.write_all(&bytes).map_err(SimplemadError::Write)?;
+            summary.frames += 1;
+            summary.bytes += bytes.len() as u64;
+        }
+        summary.end = frame_end;
+    }
+
+    writer.flush().map_err(SimplemadError::Write)?;
+    match summary {
+        Some(summary) => Ok(summary),
+        None => Err(SimplemadError::EOF),
+    }
+}
+
+// Drop the oldest frames that aren't needed to hold `needed` bytes of data
+fn trim_reservoir(reservoir: &mut VecDeque<Preceding>, len: &mut usize, needed: usize) {
+    while reservoir.front().is_some_and(|front| *len - front.data_len >= needed) {
+        let front = reservoir.pop_front().unwrap();
+        *len -= front.data_len;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+    use FrameHeader;
+
+    #[test]
+    fn test_cut() {
+        let data = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut output = Vec::new();
+        let summary = cut(Cursor::new(&data),
+                          &mut output,
+                          Duration::from_secs(1)..Duration::from_secs(2))
+                          .unwrap();
+        assert_eq!(summary.bytes, output.len() as u64);
+        assert!(summary.start < Duration::from_secs(1));
+        assert!(summary.end >= Duration::from_secs(2));
+        assert!(summary.end < Duration::from_millis(2_030));
+
+        // The output is a contiguous part of the input
+        let offset = data.windows(output.len()).position(|w| w == &output[..]).unwrap();
+        assert!(offset > 0);
+
+        // Apart from the frames before the range, whose data is incomplete,
+        // the cut decodes
+        let results: Vec<_> = Decoder::decode(Cursor::new(&output)).unwrap().collect();
+        let decoded = results.iter().filter(|r| r.is_ok()).count() as u64;
+        assert!(decoded + 3 >= summary.frames);
+
+        // Only the frames holding the data of the first frame are added
+        let headers: Vec<FrameHeader> = Decoder::decode_headers(Cursor::new(&output))
+                                            .unwrap()
+                                            .headers()
+                                            .filter_map(|r| r.ok())
+                                            .collect();
+        let preceding = headers.iter()
+                               .take_while(|h| {
+                                   summary.start + h.position + h.duration <= Duration::from_secs(1)
+                               })
+                               .count();
+        assert!((1..=2).contains(&preceding));
+        assert!(headers[preceding..].iter().all(|h| h.reservoir_complete));
+
+        let mut output = Vec::new();
+        let result = cut(Cursor::new(&data),
+                         &mut output,
+                         Duration::from_secs(100)..Duration::from_secs(200));
+        assert_eq!(result.unwrap_err(), SimplemadError::EOF);
+
+        let mut output = [0u8; 100];
+        let result = cut(Cursor::new(&data),
+                         &mut output[..],
+                         Duration::from_secs(1)..Duration::from_secs(2));
+        assert!(matches!(result, Err(SimplemadError::Write(_))));
+    }
+}
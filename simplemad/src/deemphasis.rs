@@ -0,0 +1,147 @@
+//! Undoing the pre-emphasis some streams were encoded with
+
+use processor::Processor;
+use {Emphasis, Frame};
+
+// Time constants of the pole and zero of each de-emphasis, in seconds.
+// CCITT J.17 is specified with corners at 3000 rad/s and √75 times that.
+const FIFTY_15_US: (f64, f64) = (50e-6, 15e-6);
+const CCITT_J17: (f64, f64) = (1.0 / 3000.0, 1.0 / (3000.0 * 8.660_254_037_844_386));
+
+/// A processor that undoes the pre-emphasis signalled in the frame headers
+///
+/// Applies the 50/15 µs or CCITT J.17 de-emphasis filter as a first-order
+/// shelf, leaving frames without emphasis alone. Enable it with
+/// `DecoderBuilder::de_emphasis`, or add it with `with_processor` to run it
+/// after other processors.
+#[derive(Clone, Debug, Default)]
+pub struct DeEmphasis {
+    // The emphasis and sample rate the coefficients were computed for
+    filter: Option<(Emphasis, u32, Coefficients)>,
+    // The last input and output sample of each channel
+    state: Vec<(f64, f64)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Coefficients {
+    b0: f64,
+    b1: f64,
+    a1: f64,
+}
+
+impl Coefficients {
+    // Bilinear transform of (1 + s * zero) / (1 + s * pole)
+    fn new((pole, zero): (f64, f64), sample_rate: u32) -> Coefficients {
+        let k = 2.0 * f64::from(sample_rate);
+        let norm = 1.0 + pole * k;
+        Coefficients {
+            b0: (1.0 + zero * k) / norm,
+            b1: (1.0 - zero * k) / norm,
+            a1: (1.0 - pole * k) / norm,
+        }
+    }
+}
+
+impl DeEmphasis {
+    /// Create a processor with no filter state
+    pub fn new() -> DeEmphasis {
+        DeEmphasis::default()
+    }
+
+    /// Forget the previous samples, as after a seek
+    pub fn reset(&mut self) {
+        self.state.clear();
+    }
+}
+
+impl Processor for DeEmphasis {
+    fn process(&mut self, frame: &mut Frame) {
+        let constants = match frame.emphasis {
+            Emphasis::Fifty15Us => FIFTY_15_US,
+            Emphasis::CcittJ17 => CCITT_J17,
+            Emphasis::None | Emphasis::Reserved => {
+                self.filter = None;
+                return;
+            }
+        };
+        let coefficients = match self.filter {
+            Some((emphasis, rate, coefficients))
+                if emphasis == frame.emphasis && rate == frame.sample_rate => coefficients,
+            _ => {
+                let coefficients = Coefficients::new(constants, frame.sample_rate);
+                self.filter = Some((frame.emphasis, frame.sample_rate, coefficients));
+                self.state.clear();
+                coefficients
+            }
+        };
+
+        self.state.resize(frame.samples.len(), (0.0, 0.0));
+        for (channel, state) in frame.samples.iter_mut().zip(&mut self.state) {
+            let (mut x1, mut y1) = *state;
+            for sample in channel.iter_mut() {
+                let x = f64::from(sample.value);
+                let y = coefficients.b0 * x + coefficients.b1 * x1 - coefficients.a1 * y1;
+                sample.value = y.round().clamp(f64::from(i32::MIN), f64::from(i32::MAX)) as i32;
+                x1 = x;
+                y1 = y;
+            }
+            *state = (x1, y1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use {Decoder, MadFixed32};
+
+    fn frame(emphasis: Emphasis, samples: Vec<i32>) -> Frame {
+        let file = File::open("sample_mp3s/constant_single_channel_128.mp3").unwrap();
+        let mut frame = Decoder::decode(file).unwrap().find_map(|r| r.ok()).unwrap();
+        frame.emphasis = emphasis;
+        frame.samples = vec![samples.into_iter().map(MadFixed32::new).collect()];
+        frame
+    }
+
+    #[test]
+    fn test_de_emphasis() {
+        let amplitude = 1 << 24;
+        let alternating: Vec<i32> = (0..1152)
+                                        .map(|i| if i % 2 == 0 { amplitude } else { -amplitude })
+                                        .collect();
+        let mut filter = DeEmphasis::new();
+
+        // The highest frequencies are attenuated by the ratio of the time
+        // constants, low frequencies pass unchanged
+        for &(emphasis, ratio) in &[(Emphasis::Fifty15Us, 0.3), (Emphasis::CcittJ17, 0.1155)] {
+            let mut high = frame(emphasis, alternating.clone());
+            filter.process(&mut high);
+            let peak = high.samples[0][1000].to_raw().abs() as f64 / f64::from(amplitude);
+            assert!((peak - ratio).abs() < 0.001, "{:?} {}", emphasis, peak);
+
+            let mut dc = frame(emphasis, vec![amplitude; 1152]);
+            filter.reset();
+            filter.process(&mut dc);
+            assert!((dc.samples[0][1151].to_raw() - amplitude).abs() <= 1);
+        }
+
+        let mut plain = frame(Emphasis::None, alternating.clone());
+        filter.process(&mut plain);
+        assert!(plain.samples[0].iter().zip(&alternating).all(|(a, &b)| a.to_raw() == b));
+    }
+
+    #[test]
+    fn test_de_emphasis_option() {
+        // Without emphasis in the headers the samples are unchanged
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let plain = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let filtered = Decoder::builder(File::open(path).unwrap())
+                           .de_emphasis(true)
+                           .build()
+                           .unwrap();
+        for (a, b) in plain.zip(filtered).take(20) {
+            assert_eq!(a.unwrap().samples, b.unwrap().samples);
+        }
+    }
+}
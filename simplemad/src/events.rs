@@ -0,0 +1,151 @@
+//! Decoding results as a sequence of typed events
+
+use std::collections::VecDeque;
+use std::io;
+use {Decoder, FormatChange, Frame, SimplemadError};
+
+/// Something that happened while decoding, see `Decoder::next_event`
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum DecodeEvent {
+    /// A decoded frame
+    Frame(Frame),
+    /// A metadata tag was found in the stream
+    Tag(Tag),
+    /// The format of the following frames differs from the previous ones
+    FormatChanged(FormatChange),
+    /// Data that isn't audio was skipped before the next frame
+    Skipped {
+        /// Number of bytes skipped, including tags
+        bytes: u64,
+    },
+    /// The end of the stream was reached
+    Eof,
+}
+
+/// A metadata tag found in the stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tag {
+    /// The kind of tag
+    pub kind: TagKind,
+    /// Byte offset of the tag in the input
+    pub byte_offset: u64,
+}
+
+/// The kinds of metadata tags that are recognized
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TagKind {
+    /// An ID3v1 tag
+    Id3v1,
+    /// An ID3v2 tag
+    Id3v2,
+    /// An APEv2 tag
+    Ape,
+}
+
+impl TagKind {
+    // Recognize the tag that `bytes` starts with
+    pub(crate) fn detect(bytes: &[u8]) -> Option<TagKind> {
+        if bytes.starts_with(b"TAG") {
+            Some(TagKind::Id3v1)
+        } else if bytes.starts_with(b"ID3") {
+            Some(TagKind::Id3v2)
+        } else if bytes.starts_with(b"APETAGEX") {
+            Some(TagKind::Ape)
+        } else {
+            None
+        }
+    }
+}
+
+// Events that were produced by a single call to `get_frame`
+#[derive(Default)]
+pub(crate) struct EventQueue {
+    events: VecDeque<DecodeEvent>,
+    last_tag: Option<u64>,
+}
+
+impl EventQueue {
+    pub(crate) fn push_tag(&mut self, tag: Tag) {
+        // libmad reports several errors while skipping over one tag
+        if self.last_tag != Some(tag.byte_offset) {
+            self.last_tag = Some(tag.byte_offset);
+            self.events.push_back(DecodeEvent::Tag(tag));
+        }
+    }
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    /// Get the next decoding event
+    ///
+    /// Unlike `get_frame`, recoverable errors are not returned. The data
+    /// they were caused by is reported as `Skipped` or `Tag` events
+    /// instead. Once the stream is exhausted, `Eof` is returned.
+    pub fn next_event(&mut self) -> Result<DecodeEvent, SimplemadError> {
+        loop {
+            let queue = self.events.get_or_insert_with(EventQueue::default);
+            if let Some(event) = queue.events.pop_front() {
+                return Ok(event);
+            }
+
+            let previous = self.format;
+            match self.get_frame() {
+                Ok(frame) => {
+                    let queue = self.events.get_or_insert_with(EventQueue::default);
+                    if frame.skipped_bytes > 0 {
+                        queue.events.push_back(DecodeEvent::Skipped { bytes: frame.skipped_bytes });
+                    }
+                    if let Some(current) = self.format.filter(|&f| Some(f) != previous) {
+                        let change = FormatChange { previous, current };
+                        queue.events.push_back(DecodeEvent::FormatChanged(change));
+                    }
+                    queue.events.push_back(DecodeEvent::Frame(frame));
+                }
+                Err(SimplemadError::EOF) => return Ok(DecodeEvent::Eof),
+                Err(ref e) if e.is_recoverable() => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_next_event() {
+        let path = "sample_mp3s/rodiomusic.mp3";
+        let expected = Decoder::decode(File::open(path).unwrap())
+                           .unwrap()
+                           .filter(|r| r.is_ok())
+                           .count();
+
+        let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let mut events = Vec::new();
+        loop {
+            match decoder.next_event().unwrap() {
+                DecodeEvent::Eof => break,
+                event => events.push(event),
+            }
+        }
+
+        match events[0] {
+            DecodeEvent::FormatChanged(change) => assert_eq!(change.previous, None),
+            ref event => panic!("unexpected event {:?}", event),
+        }
+        let frames = events.iter().filter(|e| matches!(e, DecodeEvent::Frame(_))).count();
+        assert_eq!(frames, expected);
+        let tags: Vec<Tag> = events.iter()
+                                   .filter_map(|e| match *e {
+                                       DecodeEvent::Tag(tag) => Some(tag),
+                                       _ => None,
+                                   })
+                                   .collect();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].kind, TagKind::Id3v1);
+        assert!(matches!(decoder.next_event(), Ok(DecodeEvent::Eof)));
+    }
+}
@@ -0,0 +1,134 @@
+//! Fades at the start and end of the decoded audio
+
+use std::io;
+use std::time::Duration;
+use builder::apply_gain;
+use {Decoder, MadFixed32};
+
+// Level at the start of an exponential fade
+const EXPONENTIAL_FLOOR_DB: f64 = -60.0;
+
+/// The shape of a fade
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FadeCurve {
+    /// The amplitude changes linearly
+    Linear,
+    /// The level in decibels changes linearly, from -60 dB
+    Exponential,
+}
+
+impl FadeCurve {
+    // The amplitude at `progress` from 0 (silent) to 1 (full level)
+    fn gain(self, progress: f64) -> f64 {
+        if progress <= 0.0 {
+            return 0.0;
+        }
+        if progress >= 1.0 {
+            return 1.0;
+        }
+        match self {
+            FadeCurve::Linear => progress,
+            FadeCurve::Exponential => {
+                10f64.powf((1.0 - progress) * EXPONENTIAL_FLOOR_DB / 20.0)
+            }
+        }
+    }
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    // Apply the fades to the samples of the frame starting at `position`
+    pub(crate) fn apply_fades(&mut self,
+                              samples: &mut [Vec<MadFixed32>],
+                              sample_rate: u32,
+                              duration: Duration) {
+        if self.options.fade_in.is_none() && self.options.fade_out.is_none() {
+            return;
+        }
+
+        let position = self.position;
+        let origin = *self.fade_origin.get_or_insert(position);
+        let end = self.options.end_time.or_else(|| {
+            self.total_frames.map(|frames| duration * frames as u32)
+        });
+
+        let fading_in = self.options
+                            .fade_in
+                            .is_some_and(|(length, _)| position < origin + length);
+        let fading_out = match (self.options.fade_out, end) {
+            (Some((length, _)), Some(end)) => position + duration + length > end,
+            _ => false,
+        };
+        if !fading_in && !fading_out {
+            return;
+        }
+
+        let length = samples.first().map_or(0, |channel| channel.len());
+        for index in 0..length {
+            let time = position +
+                       Duration::from_nanos(index as u64 * 1_000_000_000 / u64::from(sample_rate));
+            let mut gain = 1.0;
+            if let Some((fade, curve)) = self.options.fade_in {
+                let elapsed = time.saturating_sub(origin);
+                gain *= curve.gain(elapsed.as_secs_f64() / fade.as_secs_f64());
+            }
+            if let (Some((fade, curve)), Some(end)) = (self.options.fade_out, end) {
+                let remaining = end.saturating_sub(time);
+                gain *= curve.gain(remaining.as_secs_f64() / fade.as_secs_f64());
+            }
+
+            if gain < 1.0 {
+                let factor = (gain * f64::from(1 << 28)) as i64;
+                for channel in samples.iter_mut() {
+                    let sample = &mut channel[index];
+                    *sample = MadFixed32::new(apply_gain(sample.to_raw(), factor));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use Frame;
+
+    fn frames(fade_in: Option<Duration>, end_time: Option<Duration>) -> Vec<Frame> {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut builder = Decoder::builder(file).start_time(Duration::from_millis(500));
+        if let Some(fade_in) = fade_in {
+            builder = builder.fade_in(fade_in, FadeCurve::Linear);
+        }
+        if let Some(end_time) = end_time {
+            builder = builder.end_time(end_time)
+                             .fade_out(Duration::from_millis(100), FadeCurve::Exponential);
+        }
+        builder.build().unwrap().filter_map(|r| r.ok()).collect()
+    }
+
+    #[test]
+    fn test_fade_curve() {
+        assert_eq!(FadeCurve::Linear.gain(0.25), 0.25);
+        assert_eq!(FadeCurve::Exponential.gain(0.0), 0.0);
+        assert!((FadeCurve::Exponential.gain(0.5) - 0.0316).abs() < 1e-3);
+        assert_eq!(FadeCurve::Exponential.gain(1.0), 1.0);
+    }
+
+    #[test]
+    fn test_fades() {
+        let plain = frames(None, None);
+        let faded = frames(Some(Duration::from_millis(100)), None);
+        assert_eq!(faded[0].samples[0][0].to_raw(), 0);
+        // The fade covers the first 4410 samples, about 3.8 frames
+        let (sample, expected) = (faded[2].samples[0][500], plain[2].samples[0][500]);
+        let progress = (2.0 * 1152.0 + 500.0) / 4410.0;
+        assert!((f64::from(sample.to_raw()) - f64::from(expected.to_raw()) * progress).abs() <
+                1e3);
+        assert_eq!(faded[10].samples[0][0].to_raw(), plain[10].samples[0][0].to_raw());
+
+        let faded = frames(None, Some(Duration::from_secs(2)));
+        let last = faded.last().unwrap();
+        assert_eq!(last.samples[0].last().unwrap().to_raw(), 0);
+        assert_eq!(faded[0].samples[0][0].to_raw(), plain[0].samples[0][0].to_raw());
+    }
+}
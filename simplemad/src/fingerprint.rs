@@ -0,0 +1,401 @@
+//! Chromaprint-compatible audio fingerprints
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::io;
+use std::time::Duration;
+use {Decoder, Downmix, ErrorPolicy, SimplemadError};
+
+// The analysis runs on mono audio at this rate
+const SAMPLE_RATE: u32 = 11_025;
+const FRAME_SIZE: usize = 4_096;
+const FRAME_STEP: usize = FRAME_SIZE / 3;
+const MIN_FREQ: f64 = 28.0;
+const MAX_FREQ: f64 = 3_520.0;
+const BANDS: usize = 12;
+// Smoothing of the chroma features over time
+const CHROMA_FILTER: [f64; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+const NORM_THRESHOLD: f64 = 0.01;
+// Chromaprint's identifier for the classifiers below
+const ALGORITHM: u8 = 1;
+const GRAY_CODE: [u32; 4] = [0, 1, 3, 2];
+
+// Filter type, first band, number of bands, number of frames, and the
+// thresholds that quantize the filter's response
+const CLASSIFIERS: [(u8, usize, usize, usize, [f64; 3]); 16] = [
+    (0, 4, 3, 15, [1.98215, 2.35817, 2.63523]),
+    (4, 4, 6, 15, [-1.03809, -0.651211, -0.282167]),
+    (1, 0, 4, 16, [-0.298702, 0.119262, 0.558497]),
+    (3, 8, 2, 12, [-0.105439, 0.0153946, 0.135898]),
+    (3, 4, 4, 8, [-0.142891, 0.0258736, 0.200632]),
+    (4, 0, 3, 5, [-0.826319, -0.590612, -0.368214]),
+    (1, 2, 2, 9, [-0.557409, -0.233035, 0.0534525]),
+    (2, 7, 3, 4, [-0.0646826, 0.00620476, 0.0784847]),
+    (2, 6, 2, 16, [-0.192387, -0.029699, 0.215855]),
+    (2, 1, 3, 2, [-0.0397818, -0.00568076, 0.0292026]),
+    (5, 10, 1, 15, [-0.53823, -0.369934, -0.190235]),
+    (3, 6, 2, 10, [-0.124877, 0.0296483, 0.139239]),
+    (2, 1, 1, 14, [-0.101475, 0.0225617, 0.256772]),
+    (3, 5, 6, 4, [-0.0799915, -0.00729616, 0.063262]),
+    (1, 9, 2, 12, [-0.272556, 0.019424, 0.302559]),
+    (3, 4, 2, 14, [-0.164292, -0.0321188, 0.0846339]),
+];
+const MAX_FILTER_WIDTH: usize = 16;
+
+/// An acoustic fingerprint of a stream, see `fingerprint`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// The sub-fingerprints, one for every 1365 samples at 11025 Hz
+    pub raw: Vec<u32>,
+    /// Duration of the fingerprinted audio
+    pub duration: Duration,
+}
+
+impl Fingerprint {
+    /// Compress the fingerprint into the base64 format used by AcoustID
+    pub fn encode(&self) -> String {
+        let mut bits = Vec::with_capacity(self.raw.len() * 32);
+        let mut previous = 0;
+        for &sub in &self.raw {
+            // Encode the positions of changed bits as differences
+            let mut changed = sub ^ previous;
+            let mut bit = 1;
+            let mut last_bit = 0;
+            while changed != 0 {
+                if changed & 1 != 0 {
+                    bits.push(bit - last_bit);
+                    last_bit = bit;
+                }
+                changed >>= 1;
+                bit += 1;
+            }
+            bits.push(0);
+            previous = sub;
+        }
+
+        let len = self.raw.len();
+        let mut bytes = vec![ALGORITHM, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+        pack(&mut bytes, bits.iter().map(|&b| b.min(7)), 3);
+        pack(&mut bytes, bits.iter().filter(|&&b| b >= 7).map(|&b| b - 7), 5);
+        base64(&bytes)
+    }
+}
+
+/// Decode a stream and compute its fingerprint
+///
+/// The fingerprint matches the one computed by Chromaprint's default
+/// algorithm up to small differences from resampling, so it can be used for
+/// AcoustID lookups. Use `Decoder::fingerprint` to fingerprint an interval.
+pub fn fingerprint<R>(reader: R) -> Result<Fingerprint, SimplemadError>
+    where R: io::Read
+{
+    Decoder::builder(reader)
+        .error_policy(ErrorPolicy::SkipRecoverable)
+        .build()?
+        .fingerprint()
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    /// Decode the rest of the stream and compute its fingerprint, see
+    /// `fingerprint`
+    pub fn fingerprint(mut self) -> Result<Fingerprint, SimplemadError> {
+        self.options.downmix = Some(Downmix::Average);
+        let mut stream = self.resample(SAMPLE_RATE);
+        let mut chroma = ChromaExtractor::new();
+        let mut buffer = vec![0.0; FRAME_STEP];
+        let mut samples = 0u64;
+        loop {
+            match stream.next_samples(&mut buffer)? {
+                0 => break,
+                count => {
+                    chroma.add(&buffer[..count]);
+                    samples += count as u64;
+                }
+            }
+        }
+
+        Ok(Fingerprint {
+            raw: sub_fingerprints(&chroma.features),
+            duration: Duration::from_nanos(samples * 1_000_000_000 / u64::from(SAMPLE_RATE)),
+        })
+    }
+}
+
+// Turns audio into normalized, smoothed chroma features
+struct ChromaExtractor {
+    window: Vec<f64>,
+    notes: Vec<Option<usize>>,
+    pending: VecDeque<f64>,
+    smoothing: VecDeque<[f64; BANDS]>,
+    features: Vec<[f64; BANDS]>,
+}
+
+impl ChromaExtractor {
+    fn new() -> ChromaExtractor {
+        let window = (0..FRAME_SIZE)
+                         .map(|i| 0.54 - 0.46 * (2.0 * PI * i as f64 / (FRAME_SIZE - 1) as f64).cos())
+                         .collect();
+
+        let index_of = |freq: f64| {
+            (FRAME_SIZE as f64 * freq / f64::from(SAMPLE_RATE)).round() as usize
+        };
+        let (min_index, max_index) = (index_of(MIN_FREQ).max(1), index_of(MAX_FREQ));
+        let notes = (0..FRAME_SIZE / 2 + 1)
+                        .map(|i| {
+                            if i < min_index || i >= max_index {
+                                return None;
+                            }
+                            let freq = i as f64 * f64::from(SAMPLE_RATE) / FRAME_SIZE as f64;
+                            let octave = (freq / (440.0 / 16.0)).log2();
+                            Some((BANDS as f64 * (octave - octave.floor())) as usize)
+                        })
+                        .collect();
+
+        ChromaExtractor {
+            window,
+            notes,
+            pending: VecDeque::with_capacity(FRAME_SIZE),
+            smoothing: VecDeque::with_capacity(CHROMA_FILTER.len()),
+            features: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.pending.push_back(f64::from(sample));
+            if self.pending.len() == FRAME_SIZE {
+                self.analyze_frame();
+                self.pending.drain(..FRAME_STEP);
+            }
+        }
+    }
+
+    fn analyze_frame(&mut self) {
+        let mut re: Vec<f64> = self.pending.iter().zip(&self.window).map(|(s, w)| s * w).collect();
+        let mut im = vec![0.0; FRAME_SIZE];
+        fft(&mut re, &mut im);
+
+        let mut chroma = [0.0; BANDS];
+        for (i, note) in self.notes.iter().enumerate() {
+            if let Some(note) = *note {
+                chroma[note] += re[i] * re[i] + im[i] * im[i];
+            }
+        }
+
+        if self.smoothing.len() == CHROMA_FILTER.len() {
+            self.smoothing.pop_front();
+        }
+        self.smoothing.push_back(chroma);
+        if self.smoothing.len() < CHROMA_FILTER.len() {
+            return;
+        }
+
+        let mut smoothed = [0.0; BANDS];
+        for (coefficient, chroma) in CHROMA_FILTER.iter().zip(&self.smoothing) {
+            for (sum, value) in smoothed.iter_mut().zip(chroma) {
+                *sum += coefficient * value;
+            }
+        }
+        let norm = smoothed.iter().map(|x| x * x).sum::<f64>().sqrt();
+        for value in &mut smoothed {
+            *value = if norm < NORM_THRESHOLD { 0.0 } else { *value / norm };
+        }
+        self.features.push(smoothed);
+    }
+}
+
+// In-place iterative radix-2 FFT
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_re, w_im) = ((angle * k as f64).cos(), (angle * k as f64).sin());
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// Apply the classifiers to the integral image of the chroma features
+fn sub_fingerprints(features: &[[f64; BANDS]]) -> Vec<u32> {
+    if features.len() < MAX_FILTER_WIDTH {
+        return Vec::new();
+    }
+
+    let mut image = features.to_vec();
+    for row in 0..image.len() {
+        for band in 0..BANDS {
+            let mut sum = image[row][band];
+            if row > 0 {
+                sum += image[row - 1][band];
+            }
+            if band > 0 {
+                sum += image[row][band - 1];
+            }
+            if row > 0 && band > 0 {
+                sum -= image[row - 1][band - 1];
+            }
+            image[row][band] = sum;
+        }
+    }
+
+    // Sum over the frames `x1..x2` and bands `y1..y2`
+    let area = |x1: usize, y1: usize, x2: usize, y2: usize| {
+        if x2 <= x1 || y2 <= y1 {
+            return 0.0;
+        }
+        let mut sum = image[x2 - 1][y2 - 1];
+        if x1 > 0 {
+            sum -= image[x1 - 1][y2 - 1];
+        }
+        if y1 > 0 {
+            sum -= image[x2 - 1][y1 - 1];
+        }
+        if x1 > 0 && y1 > 0 {
+            sum += image[x1 - 1][y1 - 1];
+        }
+        sum
+    };
+    let compare = |a: f64, b: f64| (1.0 + a).ln() - (1.0 + b).ln();
+
+    (0..features.len() - MAX_FILTER_WIDTH + 1)
+        .map(|x| {
+            CLASSIFIERS.iter().fold(0, |bits, &(kind, y, h, w, thresholds)| {
+                let value = match kind {
+                    0 => compare(area(x, y, x + w, y + h), 0.0),
+                    1 => {
+                        let h_2 = h / 2;
+                        compare(area(x, y + h_2, x + w, y + h), area(x, y, x + w, y + h_2))
+                    }
+                    2 => {
+                        let w_2 = w / 2;
+                        compare(area(x + w_2, y, x + w, y + h), area(x, y, x + w_2, y + h))
+                    }
+                    3 => {
+                        let (w_2, h_2) = (w / 2, h / 2);
+                        compare(area(x, y + h_2, x + w_2, y + h) +
+                                area(x + w_2, y, x + w, y + h_2),
+                                area(x, y, x + w_2, y + h_2) +
+                                area(x + w_2, y + h_2, x + w, y + h))
+                    }
+                    4 => {
+                        let h_3 = h / 3;
+                        compare(area(x, y + h_3, x + w, y + 2 * h_3),
+                                area(x, y, x + w, y + h_3) + area(x, y + 2 * h_3, x + w, y + h))
+                    }
+                    _ => {
+                        let w_3 = w / 3;
+                        compare(area(x + w_3, y, x + 2 * w_3, y + h),
+                                area(x, y, x + w_3, y + h) + area(x + 2 * w_3, y, x + w, y + h))
+                    }
+                };
+                let level = thresholds.iter().filter(|&&t| value >= t).count();
+                (bits << 2) | GRAY_CODE[level]
+            })
+        })
+        .collect()
+}
+
+// Append `values` of `width` bits each, least significant bit first
+fn pack<I>(bytes: &mut Vec<u8>, values: I, width: u32)
+    where I: Iterator<Item = u32>
+{
+    let mut acc = 0u32;
+    let mut acc_bits = 0;
+    for value in values {
+        acc |= value << acc_bits;
+        acc_bits += width;
+        while acc_bits >= 8 {
+            bytes.push(acc as u8);
+            acc >>= 8;
+            acc_bits -= 8;
+        }
+    }
+    if acc_bits > 0 {
+        bytes.push(acc as u8);
+    }
+}
+
+// URL-safe base64 without padding
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..chunk.len() + 1 {
+            encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_fingerprint() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let print = fingerprint(File::open(path).unwrap()).unwrap();
+        let expected = (print.duration.as_secs_f64() * f64::from(SAMPLE_RATE)) as usize;
+        let frames = (expected - FRAME_SIZE) / FRAME_STEP + 1;
+        assert_eq!(print.raw.len(), frames - (CHROMA_FILTER.len() - 1) - (MAX_FILTER_WIDTH - 1));
+        assert!(print.duration > Duration::from_secs(5));
+
+        // The same audio at another bit rate gives a similar fingerprint
+        let other = fingerprint(File::open("sample_mp3s/constant_stereo_320.mp3").unwrap())
+                        .unwrap();
+        let len = print.raw.len().min(other.raw.len());
+        let differing: u32 = print.raw[..len]
+                                  .iter()
+                                  .zip(&other.raw[..len])
+                                  .map(|(a, b)| (a ^ b).count_ones())
+                                  .sum();
+        assert!(f64::from(differing) / ((len * 32) as f64) < 0.2, "{} bits differ", differing);
+
+        let encoded = print.encode();
+        assert!(encoded.starts_with("AQAA"));
+        assert!(encoded.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg");
+        assert_eq!(base64(b"fo"), "Zm8");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn test_pack() {
+        let mut bytes = Vec::new();
+        pack(&mut bytes, [1, 2, 3, 4, 5, 6, 7, 0].iter().cloned(), 3);
+        assert_eq!(bytes, [0xd1, 0x58, 0x1f]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Detection of changes in the format of the decoded audio
+
+use std::io;
+use {Decoder, Frame, Layer};
+
+pub(crate) type FormatObserver = Box<dyn FnMut(&FormatChange) + Send>;
+
+/// The properties of the decoded audio that sinks are configured for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AudioFormat {
+    /// Number of samples per second
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: usize,
+    /// Audio layer (I, II or III)
+    pub layer: Layer,
+}
+
+impl AudioFormat {
+    /// Get the format of a frame
+    pub fn of(frame: &Frame) -> AudioFormat {
+        AudioFormat {
+            sample_rate: frame.sample_rate,
+            channels: frame.n_channels(),
+            layer: frame.layer,
+        }
+    }
+}
+
+/// A change in the format of the decoded audio, see
+/// `DecoderBuilder::on_format_change`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatChange {
+    /// The format of the previous frame, `None` for the first frame
+    pub previous: Option<AudioFormat>,
+    /// The format of the frame that was just decoded
+    pub current: AudioFormat,
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    /// Get the format of the most recently decoded frame
+    pub fn format(&self) -> Option<AudioFormat> {
+        self.format
+    }
+
+    // Compare the format of a decoded frame to the previous one
+    pub(crate) fn check_format(&mut self, frame: &Frame) -> Option<FormatChange> {
+        let current = AudioFormat::of(frame);
+        if self.format == Some(current) {
+            return None;
+        }
+
+        let change = FormatChange {
+            previous: self.format,
+            current,
+        };
+        self.format = Some(current);
+        if let Some(ref mut observer) = self.format_observer {
+            observer(&change);
+        }
+        Some(change)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use SimplemadError;
+    use std::fs;
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_format_change() {
+        let mut data = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        data.extend(fs::read("sample_mp3s/constant_single_channel_128.mp3").unwrap());
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let observed = changes.clone();
+        let mut decoder = Decoder::builder(Cursor::new(data))
+                              .on_format_change(move |change| {
+                                  observed.lock().unwrap().push(*change)
+                              })
+                              .build()
+                              .unwrap();
+        assert_eq!(decoder.format(), None);
+        let frames = decoder.by_ref().filter(|r| r.is_ok()).count();
+        assert!(frames > 300);
+
+        let changes = changes.lock().unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].previous, None);
+        assert_eq!(changes[0].current.channels, 2);
+        assert_eq!(changes[1].previous, Some(changes[0].current));
+        assert_eq!(changes[1].current.channels, 1);
+        assert_eq!(changes[1].current.sample_rate, 44_100);
+        assert_eq!(decoder.format(), Some(changes[1].current));
+    }
+
+    #[test]
+    fn test_consistent_format() {
+        let stereo = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut data = stereo.clone();
+        data.extend(fs::read("sample_mp3s/constant_single_channel_128.mp3").unwrap());
+
+        let mut decoder = Decoder::builder(Cursor::new(data))
+                              .consistent_format(true)
+                              .build()
+                              .unwrap();
+        let results: Vec<_> = decoder.by_ref().collect();
+        match results.last() {
+            Some(Err(SimplemadError::FormatChanged { change, context })) => {
+                assert_eq!(change.previous.unwrap().channels, 2);
+                assert_eq!(change.current.channels, 1);
+                assert!(context.byte_offset >= stereo.len() as u64);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(results[..results.len() - 1].iter().all(|r| match *r {
+            Ok(ref frame) => frame.n_channels() == 2,
+            Err(ref error) => error.is_recoverable(),
+        }));
+        assert!(decoder.next().is_none());
+
+        // A stream that keeps its format decodes as usual
+        let decoder = Decoder::builder(Cursor::new(stereo))
+                          .consistent_format(true)
+                          .build()
+                          .unwrap();
+        assert!(decoder.filter_map(|r| r.err()).all(|error| error.is_recoverable()));
+    }
+}
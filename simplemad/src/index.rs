@@ -0,0 +1,459 @@
+//! Random access to the frames of a seekable stream
+
+use std::cmp::max;
+use std::collections::VecDeque;
+use std::io;
+use std::iter::FusedIterator;
+use std::mem;
+use std::ops::Range;
+use std::time::Duration;
+use {frame_duration, Decoder, Frame, LibmadError, MadFixed32, SimplemadError,
+     MAX_MAIN_DATA_BEGIN};
+
+// The longest header, CRC and side information of a Layer III frame
+const MAX_SIDE_INFO_LEN: u64 = 38;
+
+// Frames decoded at once by `ReverseFrames`, the warm-up is shared by them
+const REVERSE_BLOCK: usize = 16;
+
+/// A frame of a `FrameIndex`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Offset of the frame's header from the start of the input
+    pub byte_offset: u64,
+    /// Length of the frame in bytes
+    pub len: u64,
+    /// Position of the frame in the stream
+    pub position: Duration,
+    /// Duration of the frame
+    pub duration: Duration,
+}
+
+/// The offsets and positions of the frames of a stream
+///
+/// Created by `Decoder::build_index` from the headers alone.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameIndex {
+    /// Every frame, in the order of the stream
+    pub frames: Vec<IndexEntry>,
+}
+
+impl FrameIndex {
+    /// Get the number of frames
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Check whether the stream has no frames
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Find the frame playing at `time`
+    pub fn frame_at(&self, time: Duration) -> Option<usize> {
+        let next = self.frames.partition_point(|frame| frame.position <= time);
+        let frame = self.frames.get(next.checked_sub(1)?)?;
+        if time < frame.position + frame.duration {
+            Some(next - 1)
+        } else {
+            None
+        }
+    }
+
+    // The first frame to decode to get frame `index` right: enough frames
+    // to fill the bit reservoir, and one for the overlap of the transforms
+    fn warm_up_start(&self, index: usize) -> usize {
+        let mut start = index;
+        let mut data_len = 0;
+        while start > 0 && data_len < u64::from(MAX_MAIN_DATA_BEGIN) {
+            start -= 1;
+            data_len += self.frames[start].len.saturating_sub(MAX_SIDE_INFO_LEN);
+        }
+        start.saturating_sub(1)
+    }
+}
+
+// The most recently used frames of `decode_at`, least recent first
+#[derive(Default)]
+pub(crate) struct FrameCache {
+    frames: VecDeque<(usize, Frame)>,
+    bytes: usize,
+}
+
+impl FrameCache {
+    fn get(&mut self, index: usize) -> Option<Frame> {
+        let position = self.frames.iter().position(|&(i, _)| i == index)?;
+        let entry = self.frames.remove(position)?;
+        let frame = entry.1.clone();
+        self.frames.push_back(entry);
+        Some(frame)
+    }
+
+    fn insert(&mut self, index: usize, frame: Frame, max_bytes: usize) {
+        if let Some(position) = self.frames.iter().position(|&(i, _)| i == index) {
+            if let Some((_, old)) = self.frames.remove(position) {
+                self.bytes -= cached_len(&old);
+            }
+        }
+        self.bytes += cached_len(&frame);
+        self.frames.push_back((index, frame));
+        while self.bytes > max_bytes {
+            match self.frames.pop_front() {
+                Some((_, old)) => self.bytes -= cached_len(&old),
+                None => break,
+            }
+        }
+    }
+}
+
+// The memory used by a cached frame
+fn cached_len(frame: &Frame) -> usize {
+    let samples: usize = frame.samples.iter().map(|channel| channel.len()).sum();
+    mem::size_of::<Frame>() + samples * mem::size_of::<MadFixed32>() +
+    frame.encoded.as_ref().map_or(0, |encoded| encoded.len()) + frame.ancillary.len()
+}
+
+impl<R> Decoder<R> where R: io::Read + io::Seek {
+    /// Read the headers of the whole stream to find the offset and position
+    /// of every frame
+    ///
+    /// Rewinds the decoder like `reset` when done.
+    pub fn build_index(&mut self) -> Result<FrameIndex, SimplemadError> {
+        let start = self.audio_offset.unwrap_or(0);
+        self.jump_to(start)?;
+        let index = self.scan_frames()?;
+        self.reset()?;
+        Ok(index)
+    }
+
+    /// Decode the frame playing at `time`
+    ///
+    /// The first call reads the headers of the whole stream with
+    /// `build_index` and keeps the index, later calls jump straight to the
+    /// frame. Decoding starts a few frames earlier to fill the bit reservoir,
+    /// unless the frame follows the last one decoded, so stepping forward is
+    /// as fast as normal decoding. Returns `SimplemadError::EOF` past the end
+    /// of the stream.
+    pub fn decode_at(&mut self, time: Duration) -> Result<Frame, SimplemadError> {
+        let mut frames = self.decode_window(time, Duration::new(0, 0))?;
+        frames.pop().ok_or(SimplemadError::EOF)
+    }
+
+    /// Decode the frames playing from `start` for `len`, see `decode_at`
+    ///
+    /// At least the frame playing at `start` is decoded.
+    pub fn decode_window(&mut self,
+                         start: Duration,
+                         len: Duration)
+                         -> Result<Vec<Frame>, SimplemadError> {
+        let index = match self.index.take() {
+            Some(index) => index,
+            None => self.build_index()?,
+        };
+        let result = match index.frame_at(start) {
+            Some(first) => {
+                let end = index.frames.partition_point(|frame| frame.position < start + len);
+                let range = first..max(end, first + 1);
+                match self.options.frame_cache_bytes {
+                    Some(max_bytes) => self.decode_cached(&index, range, max_bytes),
+                    None => self.decode_indexed(&index, range),
+                }
+            }
+            None => Err(SimplemadError::EOF),
+        };
+        self.index = Some(index);
+        result
+    }
+
+    /// Iterate over the frames from the last to the first
+    ///
+    /// Frames are decoded in small blocks, each starting early enough to
+    /// fill the bit reservoir, so the samples are the same as those of a
+    /// forward decode. Frames with recoverable errors are skipped.
+    pub fn reverse(self, index: FrameIndex) -> ReverseFrames<R> {
+        ReverseFrames {
+            remaining: index.len(),
+            decoder: self,
+            index,
+            block: Vec::new(),
+        }
+    }
+
+    // Read headers until the end of the input, the positions count from
+    // the first frame found
+    pub(crate) fn scan_frames(&mut self) -> Result<FrameIndex, SimplemadError> {
+        let mut index = FrameIndex::default();
+        let mut position = Duration::new(0, 0);
+        loop {
+            match self.decode_header() {
+                Ok(()) => {
+                    let byte_offset = self.offset_of(self.mad.stream.this_frame);
+                    let duration = frame_duration(&self.mad.frame);
+                    self.sync_offset = self.offset_of(self.mad.stream.next_frame);
+                    index.frames.push(IndexEntry {
+                        byte_offset,
+                        len: self.sync_offset - byte_offset,
+                        position,
+                        duration,
+                    });
+                    position += duration;
+                }
+                Err(SimplemadError::Mad { error: LibmadError::BufLen, .. }) => {
+                    if self.refill_buffer()? == 0 {
+                        return Ok(index);
+                    }
+                }
+                Err(ref error) if error.is_recoverable() => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    // Decode the frames in `range` of `index` that aren't in the cache
+    fn decode_cached(&mut self,
+                     index: &FrameIndex,
+                     range: Range<usize>,
+                     max_bytes: usize)
+                     -> Result<Vec<Frame>, SimplemadError> {
+        let missing: Vec<usize> = range.clone()
+                                       .filter(|&i| {
+                                           self.frame_cache.frames.iter().all(|&(j, _)| i != j)
+                                       })
+                                       .collect();
+        let decoded = match (missing.first(), missing.last()) {
+            (Some(&first), Some(&last)) => self.decode_indexed(index, first..last + 1)?,
+            _ => Vec::new(),
+        };
+
+        let mut decoded = decoded.into_iter().peekable();
+        let mut frames = Vec::with_capacity(range.len());
+        for i in range {
+            let offset = index.frames[i].byte_offset;
+            while decoded.next_if(|frame| frame.byte_offset < offset).is_some() {}
+            let frame = match decoded.next_if(|frame| frame.byte_offset == offset) {
+                Some(frame) => {
+                    self.frame_cache.insert(i, frame.clone(), max_bytes);
+                    frame
+                }
+                None => {
+                    match self.frame_cache.get(i) {
+                        Some(frame) => frame,
+                        None => continue,
+                    }
+                }
+            };
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    // Decode the frames in `range` of `index`, the start and end times are
+    // ignored
+    fn decode_indexed(&mut self,
+                      index: &FrameIndex,
+                      range: Range<usize>)
+                      -> Result<Vec<Frame>, SimplemadError> {
+        let mut frames = Vec::with_capacity(range.len());
+        let last = match index.frames.get(range.end.wrapping_sub(1)) {
+            Some(last) if !range.is_empty() => last.byte_offset,
+            _ => return Ok(frames),
+        };
+        // The reservoir is already filled when the frame follows the last one
+        let first = &index.frames[range.start];
+        if self.done || self.sync_offset != first.byte_offset || self.position != first.position {
+            let warm_up = &index.frames[index.warm_up_start(range.start)];
+            self.jump_to(warm_up.byte_offset)?;
+            self.frame_index = range.start as u64;
+            self.position = warm_up.position;
+        }
+
+        let interval = (self.options.start_time, self.options.end_time);
+        self.options.start_time = Some(first.position);
+        self.options.end_time = None;
+        let result = loop {
+            match self.get_frame() {
+                Ok(frame) => {
+                    let done = frame.byte_offset >= last;
+                    frames.push(frame);
+                    if done {
+                        break Ok(frames);
+                    }
+                }
+                Err(SimplemadError::EOF) => break Ok(frames),
+                Err(ref error) if error.is_recoverable() => {}
+                Err(error) => break Err(error),
+            }
+        };
+        self.options.start_time = interval.0;
+        self.options.end_time = interval.1;
+        result
+    }
+}
+
+/// An iterator over the frames of a stream from the last to the first
+///
+/// Created by `Decoder::reverse`.
+pub struct ReverseFrames<R>
+    where R: io::Read + io::Seek
+{
+    decoder: Decoder<R>,
+    index: FrameIndex,
+    remaining: usize,
+    block: Vec<Frame>,
+}
+
+impl<R> ReverseFrames<R> where R: io::Read + io::Seek {
+    /// Get the underlying decoder
+    pub fn into_decoder(self) -> Decoder<R> {
+        self.decoder
+    }
+}
+
+impl<R> Iterator for ReverseFrames<R> where R: io::Read + io::Seek {
+    type Item = Result<Frame, SimplemadError>;
+    fn next(&mut self) -> Option<Result<Frame, SimplemadError>> {
+        loop {
+            if let Some(frame) = self.block.pop() {
+                return Some(Ok(frame));
+            }
+            if self.remaining == 0 {
+                return None;
+            }
+
+            let start = self.remaining.saturating_sub(REVERSE_BLOCK);
+            let block = self.decoder.decode_indexed(&self.index, start..self.remaining);
+            self.remaining = start;
+            match block {
+                Ok(frames) => self.block = frames,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+impl<R> FusedIterator for ReverseFrames<R> where R: io::Read + io::Seek {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use FrameHeader;
+
+    fn raw(frame: &Frame) -> Vec<Vec<i32>> {
+        frame.samples
+             .iter()
+             .map(|channel| channel.iter().map(|sample| sample.to_raw()).collect())
+             .collect()
+    }
+
+    #[test]
+    fn test_build_index() {
+        let path = "sample_mp3s/variable_stereo.mp3";
+        let frames: Vec<FrameHeader> = Decoder::decode(File::open(path).unwrap())
+                                           .unwrap()
+                                           .headers()
+                                           .filter_map(|r| r.ok())
+                                           .collect();
+        let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let index = decoder.build_index().unwrap();
+        assert_eq!(index.len(), frames.len());
+        for (entry, frame) in index.frames.iter().zip(&frames) {
+            assert_eq!(entry.byte_offset, frame.byte_offset);
+            assert_eq!(entry.position, frame.position);
+            assert_eq!(entry.duration, frame.duration);
+        }
+
+        // The decoder was rewound
+        assert_eq!(decoder.filter_map(|r| r.ok()).count(), frames.len());
+        let mut empty = Decoder::decode(io::Cursor::new(Vec::new())).unwrap();
+        assert!(empty.build_index().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decode_at() {
+        for path in &["sample_mp3s/constant_stereo_128.mp3",
+                      "sample_mp3s/variable_stereo.mp3"] {
+            let forward: Vec<Frame> = Decoder::decode(File::open(path).unwrap())
+                                          .unwrap()
+                                          .filter_map(|r| r.ok())
+                                          .collect();
+            let duration = forward[0].duration;
+            let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+
+            // Scattered, backwards and consecutive frames
+            for &i in &[100, 3, 150, 0, 192, 40, 41, 42, 10] {
+                let frame = decoder.decode_at(duration * i + duration / 2).unwrap();
+                let expected = &forward[i as usize];
+                assert_eq!(frame.byte_offset, expected.byte_offset, "{}", path);
+                assert_eq!(frame.position, expected.position, "{}", path);
+                assert_eq!(raw(&frame), raw(expected), "{} {}", path, i);
+            }
+
+            let frames = decoder.decode_window(duration * 20, duration * 3).unwrap();
+            assert_eq!(frames.len(), 3);
+            assert_eq!(raw(&frames[2]), raw(&forward[22]));
+            assert_eq!(decoder.decode_at(duration * 193).unwrap_err(), SimplemadError::EOF);
+        }
+    }
+
+    #[test]
+    fn test_frame_cache() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let forward: Vec<Frame> = Decoder::decode(File::open(path).unwrap())
+                                      .unwrap()
+                                      .filter_map(|r| r.ok())
+                                      .collect();
+        let duration = forward[0].duration;
+        let frame_len = cached_len(&forward[0]);
+        let mut decoder = Decoder::builder(File::open(path).unwrap())
+                              .frame_cache(frame_len * 8)
+                              .build()
+                              .unwrap();
+
+        let frames = decoder.decode_window(duration * 50, duration * 8).unwrap();
+        assert_eq!(frames.len(), 8);
+        let decoded = decoder.stats().frames_decoded;
+
+        // Scrubbing over the same frames doesn't decode them again
+        for &i in &[57, 50, 53, 50] {
+            let frame = decoder.decode_at(duration * i).unwrap();
+            assert_eq!(raw(&frame), raw(&forward[i as usize]));
+        }
+        assert_eq!(decoder.stats().frames_decoded, decoded);
+
+        // Only the frames missing from the cache are decoded
+        let frames = decoder.decode_window(duration * 54, duration * 6).unwrap();
+        assert_eq!(frames.len(), 6);
+        for (frame, expected) in frames.iter().zip(&forward[54..60]) {
+            assert_eq!(raw(frame), raw(expected));
+        }
+        assert_eq!(decoder.stats().frames_decoded, decoded + 2);
+
+        // The least recently used frames were dropped to stay within the cap
+        assert!(decoder.frame_cache.bytes <= frame_len * 8);
+        assert_eq!(decoder.frame_cache.frames.len(), 8);
+        assert!(decoder.frame_cache.frames.iter().all(|&(i, _)| i != 51));
+    }
+
+    #[test]
+    fn test_reverse() {
+        for path in &["sample_mp3s/constant_stereo_128.mp3",
+                      "sample_mp3s/variable_stereo.mp3"] {
+            let mut forward: Vec<Frame> = Decoder::decode(File::open(path).unwrap())
+                                              .unwrap()
+                                              .filter_map(|r| r.ok())
+                                              .collect();
+            forward.reverse();
+
+            let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+            let index = decoder.build_index().unwrap();
+            let backward: Vec<Frame> = decoder.reverse(index).map(|r| r.unwrap()).collect();
+            assert_eq!(backward.len(), forward.len(), "{}", path);
+            for (frame, expected) in backward.iter().zip(&forward) {
+                assert_eq!(frame.byte_offset, expected.byte_offset, "{}", path);
+                assert_eq!(frame.position, expected.position, "{}", path);
+                assert_eq!(raw(frame), raw(expected), "{} {}", path, frame.byte_offset);
+            }
+        }
+    }
+}
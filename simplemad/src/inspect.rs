@@ -0,0 +1,325 @@
+//! A report on the frames of a whole stream
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+use {BitrateMode, BitrateStats, CrcStatus, Decoder, ErrorPolicy, FrameHeader, Layer, Mode,
+     MpegVersion, SimplemadError};
+
+/// The properties and frame headers of a stream, see `inspect`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inspection {
+    /// Audio layer of the first frame
+    pub layer: Layer,
+    /// MPEG version of the first frame
+    pub version: MpegVersion,
+    /// Sample rate of the first frame
+    pub sample_rate: u32,
+    /// Channel mode of the first frame
+    pub mode: Mode,
+    /// Constant or variable bit rate
+    pub bitrate_mode: BitrateMode,
+    /// The distribution of bit rates
+    pub bitrates: BitrateStats,
+    /// The sum of the frames' durations
+    pub duration: Duration,
+    /// The encoder named by a LAME tag, e.g. `LAME3.99r`
+    pub encoder: Option<String>,
+    /// Whether the stream starts with a Xing, Info or VBRI header
+    pub has_xing: bool,
+    /// Byte offset of the first frame, usually the length of an ID3v2 tag
+    pub audio_offset: u64,
+    /// Number of bytes skipped between frames
+    pub bytes_skipped: u64,
+    /// Number of recoverable errors, e.g. frames with a bad CRC
+    pub errors: u64,
+    /// The header of every frame, including a Xing, Info or VBRI frame, as
+    /// counted by `probe` and `duration`
+    pub frames: Vec<FrameHeader>,
+}
+
+/// Scan every frame header of a stream
+///
+/// Returns `SimplemadError::EOF` if the stream has no audio frames.
+pub fn inspect<R>(reader: R) -> Result<Inspection, SimplemadError>
+    where R: io::Read
+{
+    let mut headers = Decoder::builder(reader)
+                          .error_policy(ErrorPolicy::SkipRecoverable)
+                          .build()?
+                          .headers();
+
+    let mut frames = Vec::new();
+    for header in &mut headers {
+        frames.push(header?);
+    }
+
+    let first = match frames.first() {
+        Some(&first) => first,
+        None => return Err(SimplemadError::EOF),
+    };
+    let decoder = headers.into_decoder();
+    let stats = decoder.stats();
+
+    let mut histogram = BTreeMap::new();
+    let mut sum = 0u64;
+    let mut duration = Duration::new(0, 0);
+    for frame in &frames {
+        *histogram.entry(frame.bit_rate).or_insert(0) += 1;
+        sum += u64::from(frame.bit_rate);
+        duration += frame.duration;
+    }
+    let bitrates = BitrateStats {
+        min: *histogram.keys().next().unwrap_or(&0),
+        max: *histogram.keys().next_back().unwrap_or(&0),
+        mean: (sum / frames.len() as u64) as u32,
+        frames: frames.len() as u64,
+        histogram,
+    };
+
+    let bitrate_mode = match decoder.xing {
+        Some(xing) if xing.vbr => BitrateMode::Variable,
+        _ if bitrates.min != bitrates.max => BitrateMode::Variable,
+        _ => BitrateMode::Constant,
+    };
+    let encoder = decoder.xing.and_then(|xing| xing.encoder).map(|encoder| {
+        let encoder = String::from_utf8_lossy(&encoder);
+        encoder.trim_end_matches(|c: char| c == '\0' || c.is_whitespace()).to_string()
+    });
+
+    Ok(Inspection {
+        layer: first.layer,
+        version: MpegVersion::from_flags(first.flags),
+        sample_rate: first.sample_rate,
+        mode: first.mode,
+        bitrate_mode,
+        bitrates,
+        duration,
+        encoder,
+        has_xing: decoder.xing.is_some(),
+        audio_offset: first.byte_offset,
+        bytes_skipped: stats.bytes_skipped,
+        errors: stats.recoverable_errors,
+        frames,
+    })
+}
+
+const CSV_COLUMNS: &str = "index,byte_offset,position,duration,bit_rate,sample_rate,mode,\
+                           padding,crc,main_data_begin";
+
+impl Inspection {
+    /// Write one line per frame header as CSV, after a line of column names
+    ///
+    /// Positions and durations are in seconds.
+    pub fn write_csv<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = writer;
+        writeln!(writer, "{}", CSV_COLUMNS)?;
+        for (index, frame) in self.frames.iter().enumerate() {
+            writeln!(writer,
+                     "{},{},{:.6},{:.6},{},{},{},{},{},{}",
+                     index,
+                     frame.byte_offset,
+                     frame.position.as_secs_f64(),
+                     frame.duration.as_secs_f64(),
+                     frame.bit_rate,
+                     frame.sample_rate,
+                     mode_name(frame.mode),
+                     frame.padding,
+                     crc_name(frame.crc),
+                     frame.main_data_begin)?;
+        }
+        Ok(())
+    }
+
+    /// Write the report and the frame headers as a JSON object
+    ///
+    /// Positions and durations are in seconds.
+    pub fn write_json<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = writer;
+        write!(writer, "{{")?;
+        write!(writer, "\"layer\":{},", self.layer as u32)?;
+        write!(writer, "\"version\":\"{}\",", version_name(self.version))?;
+        write!(writer, "\"sample_rate\":{},", self.sample_rate)?;
+        write!(writer, "\"mode\":\"{}\",", mode_name(self.mode))?;
+        write!(writer, "\"bitrate_mode\":\"{}\",", bitrate_mode_name(self.bitrate_mode))?;
+        write!(writer,
+               "\"bit_rate\":{{\"min\":{},\"max\":{},\"mean\":{}}},",
+               self.bitrates.min,
+               self.bitrates.max,
+               self.bitrates.mean)?;
+        write!(writer, "\"duration\":{:.6},", self.duration.as_secs_f64())?;
+        match self.encoder {
+            Some(ref encoder) => write!(writer, "\"encoder\":\"{}\",", json_escape(encoder))?,
+            None => write!(writer, "\"encoder\":null,")?,
+        }
+        write!(writer, "\"has_xing\":{},", self.has_xing)?;
+        write!(writer, "\"audio_offset\":{},", self.audio_offset)?;
+        write!(writer, "\"bytes_skipped\":{},", self.bytes_skipped)?;
+        write!(writer, "\"errors\":{},", self.errors)?;
+        write!(writer, "\"frame_count\":{},", self.frames.len())?;
+        write!(writer, "\"frames\":[")?;
+        for (index, frame) in self.frames.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer,
+                   "{{\"byte_offset\":{},\"position\":{:.6},\"duration\":{:.6},\
+                    \"bit_rate\":{},\"sample_rate\":{},\"mode\":\"{}\",\"padding\":{},\
+                    \"crc\":\"{}\",\"main_data_begin\":{}}}",
+                   frame.byte_offset,
+                   frame.position.as_secs_f64(),
+                   frame.duration.as_secs_f64(),
+                   frame.bit_rate,
+                   frame.sample_rate,
+                   mode_name(frame.mode),
+                   frame.padding,
+                   crc_name(frame.crc),
+                   frame.main_data_begin)?;
+        }
+        writeln!(writer, "]}}")
+    }
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.duration.as_secs();
+        writeln!(f,
+                 "{} Layer {}, {} Hz, {}",
+                 version_name(self.version),
+                 "I".repeat(self.layer as usize),
+                 self.sample_rate,
+                 mode_name(self.mode))?;
+        writeln!(f,
+                 "Duration:      {}:{:02}.{:03}",
+                 seconds / 60,
+                 seconds % 60,
+                 self.duration.subsec_millis())?;
+        writeln!(f, "Frames:        {}", self.frames.len())?;
+        writeln!(f,
+                 "Bit rate:      {}, {} to {} kbps, {} kbps on average",
+                 bitrate_mode_name(self.bitrate_mode),
+                 self.bitrates.min / 1000,
+                 self.bitrates.max / 1000,
+                 self.bitrates.mean / 1000)?;
+        writeln!(f, "Encoder:       {}", self.encoder.as_deref().unwrap_or("unknown"))?;
+        writeln!(f, "Xing header:   {}", if self.has_xing { "yes" } else { "no" })?;
+        writeln!(f, "Audio offset:  {}", self.audio_offset)?;
+        writeln!(f, "Bytes skipped: {}", self.bytes_skipped)?;
+        write!(f, "Errors:        {}", self.errors)
+    }
+}
+
+fn version_name(version: MpegVersion) -> &'static str {
+    match version {
+        MpegVersion::Mpeg1 => "MPEG-1",
+        MpegVersion::Mpeg2 => "MPEG-2",
+        MpegVersion::Mpeg25 => "MPEG-2.5",
+    }
+}
+
+fn mode_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::SingleChannel => "single channel",
+        Mode::DualChannel => "dual channel",
+        Mode::JointStereo => "joint stereo",
+        Mode::Stereo => "stereo",
+    }
+}
+
+fn bitrate_mode_name(mode: BitrateMode) -> &'static str {
+    match mode {
+        BitrateMode::Constant => "constant",
+        BitrateMode::Variable => "variable",
+    }
+}
+
+fn crc_name(crc: CrcStatus) -> &'static str {
+    match crc {
+        CrcStatus::Unprotected => "none",
+        CrcStatus::Valid => "valid",
+        CrcStatus::Invalid => "invalid",
+        CrcStatus::Unchecked => "unchecked",
+    }
+}
+
+fn json_escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use duration;
+
+    fn inspect_file(path: &str) -> Inspection {
+        inspect(File::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_inspect() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let inspection = inspect_file(path);
+        assert_eq!(inspection.layer, Layer::III);
+        assert_eq!(inspection.version, MpegVersion::Mpeg1);
+        assert_eq!(inspection.bitrate_mode, BitrateMode::Constant);
+        assert_eq!(inspection.bitrates.mean, 128_000);
+        assert_eq!(inspection.encoder, Some("LAME3.99r".to_string()));
+        assert!(inspection.has_xing);
+        assert_eq!(inspection.frames.len(), 193);
+        assert_eq!(inspection.duration.as_millis(), 5041);
+        assert_eq!(inspection.duration, duration(File::open(path).unwrap()).unwrap());
+        let decoded = Decoder::decode(File::open(path).unwrap()).unwrap().filter_map(|r| r.ok());
+        assert_eq!(inspection.frames.len(), decoded.count());
+
+        let inspection = inspect_file("sample_mp3s/variable_stereo.mp3");
+        assert_eq!(inspection.bitrate_mode, BitrateMode::Variable);
+        assert!(inspection.bitrates.min < inspection.bitrates.max);
+
+        let inspection = inspect_file("sample_mp3s/rodiomusic.mp3");
+        assert!(!inspection.has_xing);
+        assert_eq!(inspection.encoder, None);
+        assert_eq!(inspection.audio_offset, inspection.frames[0].byte_offset);
+    }
+
+    #[test]
+    fn test_report() {
+        let inspection = inspect_file("sample_mp3s/constant_single_channel_128.mp3");
+        let report = inspection.to_string();
+        assert!(report.starts_with("MPEG-1 Layer III, 44100 Hz, single channel\n"));
+        assert!(report.contains("Encoder:       LAME3.99r\n"));
+
+        let mut csv = Vec::new();
+        inspection.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), inspection.frames.len() + 1);
+        assert_eq!(lines[0], CSV_COLUMNS);
+        assert!(lines[1].starts_with(&format!("0,{},0.000000,", inspection.frames[0].byte_offset)));
+        assert!(lines.iter().all(|line| line.split(',').count() == 10));
+
+        let mut json = Vec::new();
+        inspection.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with("{\"layer\":3,\"version\":\"MPEG-1\","));
+        assert!(json.contains("\"encoder\":\"LAME3.99r\""));
+        assert_eq!(json.matches("\"byte_offset\"").count(), inspection.frames.len());
+        assert!(json.ends_with("]}\n"));
+    }
+
+    #[test]
+    fn test_json_escape() {
+        assert_eq!(json_escape("LAME3.99r"), "LAME3.99r");
+        assert_eq!(json_escape("a\"b\\c\u{1}"), "a\\\"b\\\\c\\u0001");
+    }
+}
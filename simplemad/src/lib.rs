@@ -16,7 +16,7 @@
  use std::path::Path;
 
  let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
- let file = File::open(&path).unwrap();
+ let file = File::open(path).unwrap();
  let decoder = Decoder::decode(file).unwrap();
 
  for decoding_result in decoder {
@@ -31,7 +31,7 @@
  }
 
  // Decode the interval from 1s to 2s (to the nearest frame),
- let file_b = File::open(&path).unwrap();
+ let file_b = File::open(path).unwrap();
  let partial_decoder = Decoder::decode_interval(file_b,
                                                 Duration::from_secs(1),
                                                 Duration::from_secs(2));
@@ -41,9 +41,31 @@
                                              Err(_) => None})
                                          .collect();
 
- // Decode only the headers to quickly calculate the file's length
- let file_c = File::open(&path).unwrap();
- let headers = Decoder::decode_headers(file_c).unwrap();
+ // Use a builder for more control over the decoding operation
+ let file_d = File::open(path).unwrap();
+ let configured_decoder = Decoder::builder(file_d)
+                              .start_time(Duration::from_secs(1))
+                              .buffer_size(8_192)
+                              .build()
+                              .unwrap();
+
+ // Iterate by reference to keep using the decoder afterwards
+ let file_e = File::open(path).unwrap();
+ let mut decoder_e = Decoder::decode(file_e).unwrap();
+ for decoding_result in &mut decoder_e {
+     if decoding_result.is_err() {
+         break;
+     }
+ }
+ let total_frames = decoder_e.total_len();
+
+ // Quickly calculate the file's length
+ let file_f = File::open(path).unwrap();
+ let length = simplemad::duration(file_f).unwrap();
+
+ // Decode only the headers to inspect each frame
+ let file_c = File::open(path).unwrap();
+ let headers = Decoder::decode(file_c).unwrap().headers();
  let duration = headers.filter_map(|r| {
                            match r {
                                Ok(f) => Some(f.duration),
@@ -59,12 +81,96 @@
         unused_import_braces)]
 
 extern crate simplemad_sys;
-use std::io::{self, Read};
+#[cfg(feature = "sys")]
+pub extern crate simplemad_sys as sys;
+use std::error;
+use std::fmt;
+use std::io;
+use std::iter::FusedIterator;
+use std::mem;
+use std::ops;
+use std::slice;
 use std::default::Default;
 use std::cmp::{min, max};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::time::Duration;
+use std::os::raw::{c_char, c_int, c_uint, c_ushort};
 use simplemad_sys::*;
 
+pub use analysis::{bitrate_stats, loudness, waveform, BitrateStats, Loudness, WaveformBucket};
+pub use builder::{Concealment, DecoderBuilder, Downmix, DecoderOptions, ErrorPolicy, Strictness,
+                  DEFAULT_BUFFER_SIZE, MIN_BUFFER_SIZE};
+pub use chain::{Chain, ChainItem};
+pub use checkpoint::DecodeCheckpoint;
+pub use crossfade::Crossfade;
+pub use cue::{CueParseError, CueSheet, CueTrack, TrackInterval};
+pub use cut::{cut, CutSummary};
+pub use deemphasis::DeEmphasis;
+pub use events::{DecodeEvent, Tag, TagKind};
+pub use fade::FadeCurve;
+#[cfg(feature = "fingerprint")]
+pub use fingerprint::{fingerprint, Fingerprint};
+pub use format::{AudioFormat, FormatChange};
+pub use index::{FrameIndex, IndexEntry, ReverseFrames};
+pub use inspect::{inspect, Inspection};
+pub use load::DecodedStream;
+#[cfg(feature = "resample")]
+pub use load::{load_pcm, LoadOptions};
+pub use looping::LoopingDecoder;
+pub use normalize::{normalization_gain, normalize, NormalizationTarget};
+pub use pcm::{PcmChunks, PcmStream, Samples};
+pub use processor::{Gain, Processor, Trim};
+pub use probe::{duration, probe, samples_per_frame, total_samples, BitrateMode, MpegVersion,
+                StreamInfo, LAYER_III_LSF_SAMPLES, LAYER_III_SAMPLES, LAYER_II_SAMPLES,
+                LAYER_I_SAMPLES};
+#[cfg(feature = "resample")]
+pub use resample::{ResampledStream, Resampler};
+pub use sample::{Sample, SampleFormat};
+pub use silence::{silence_extent, SilenceExtent, TrimSilence};
+pub use simplemad_sys::{MAD_OPTION_HALFSAMPLERATE, MAD_OPTION_IGNORECRC, MAD_OPTION_LEFTCHANNEL,
+                        MAD_OPTION_RIGHTCHANNEL, MAD_OPTION_SINGLECHANNEL};
+pub use sniff::{is_mpeg_audio, Confidence, DetectedFormat};
+pub use types::{Emphasis, Layer, LibmadError, Mode};
+pub use version::{libmad_build, libmad_version, LibmadBuild};
+use builder::{ErrorObserver, FrameObserver};
+use events::EventQueue;
+use format::FormatObserver;
+use index::FrameCache;
+use reservoir::Reservoir;
+
+mod analysis;
+mod builder;
+mod chain;
+mod checkpoint;
+mod crossfade;
+mod cue;
+mod cut;
+mod deemphasis;
+mod events;
+mod fade;
+#[cfg(feature = "fingerprint")]
+mod fingerprint;
+mod format;
+mod index;
+mod inspect;
+mod load;
+mod looping;
+mod normalize;
+mod pcm;
+mod probe;
+mod processor;
+#[cfg(feature = "resample")]
+mod resample;
+mod reservoir;
+mod sample;
+mod silence;
+mod sniff;
+mod tail;
+mod types;
+mod version;
+mod xing;
+
 /// A decoded frame
 #[derive(Clone, Debug)]
 pub struct Frame {
@@ -73,9 +179,9 @@ pub struct Frame {
     /// Stream bit rate
     pub bit_rate: u32,
     /// Audio layer (I, II or III)
-    pub layer: MadLayer,
+    pub layer: Layer,
     /// Single Channel, Dual Channel, Joint Stereo or Stereo
-    pub mode: MadMode,
+    pub mode: Mode,
     /// Samples are organized into a vector of channels. For
     /// stereo, the left channel is channel 0.
     pub samples: Vec<Vec<MadFixed32>>,
@@ -83,66 +189,685 @@ pub struct Frame {
     pub duration: Duration,
     /// The position at the start of the frame
     pub position: Duration,
+    /// Number of bytes between the previous frame and this one, skipped
+    /// while libmad resynchronized or taken by frames that failed to decode
+    pub skipped_bytes: u64,
+    /// Byte offset of the frame in the input
+    pub byte_offset: u64,
+    /// De-emphasis to apply to the decoded audio
+    pub emphasis: Emphasis,
+    /// Whether the copyright bit is set
+    pub copyright: bool,
+    /// Whether the original bit is set, i.e. the stream isn't a copy
+    pub original: bool,
+    /// Whether the private bit is set
+    pub private: bool,
+    /// Whether the frame has an extra padding slot
+    pub padding: bool,
+    /// Whether the frame has CRC protection and passed the check
+    pub crc: CrcStatus,
+    /// The encoded bytes of the frame, if `DecoderBuilder::keep_encoded`
+    /// is set
+    pub encoded: Option<Vec<u8>>,
+    /// Ancillary data following the audio data, padded with zero bits to a
+    /// whole byte
+    pub ancillary: Vec<u8>,
+    /// Number of bytes of audio data the frame takes from the frames before
+    /// it, always 0 for Layer I and II
+    pub main_data_begin: u32,
+    /// Whether the frames before this one that were read since the start or
+    /// the last gap in the stream hold `main_data_begin` bytes
+    pub reservoir_complete: bool,
+    /// Whether the samples were made up because the frame's audio data is
+    /// damaged, see `DecoderBuilder::concealment`
+    pub concealed: bool,
+    /// Estimated duration of the audio missing before this frame, if bytes
+    /// were skipped since the previous frame
+    ///
+    /// The skipped bytes, including those of frames that failed to decode,
+    /// are assumed to be frames as long as this one.
+    /// `position` doesn't include the gap, so it keeps counting the decoded
+    /// audio only.
+    pub gap: Option<Duration>,
+}
+
+impl Frame {
+    /// Get the number of channels of the samples
+    ///
+    /// Frames decoded without samples report 1 for single channel streams
+    /// and 2 otherwise.
+    pub fn n_channels(&self) -> usize {
+        if !self.samples.is_empty() {
+            self.samples.len()
+        } else if self.mode == Mode::SingleChannel {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Get the number of decoded samples per channel
+    ///
+    /// This is 0 for frames decoded without samples.
+    pub fn n_samples(&self) -> usize {
+        self.samples.first().map_or(0, |channel| channel.len())
+    }
+
+    /// Get the number of samples per channel a frame of this layer and
+    /// version encodes, see `samples_per_frame`
+    ///
+    /// The version is told from the sample rate. This doesn't depend on
+    /// whether samples were decoded, but frames decoded with
+    /// `DecoderBuilder::half_sample_rate` report a halved rate, use
+    /// `duration_samples` for them instead.
+    pub fn samples_per_frame(&self) -> usize {
+        let version = MpegVersion::from_sample_rate(self.sample_rate).unwrap_or(MpegVersion::Mpeg1);
+        samples_per_frame(self.layer, version)
+    }
+
+    /// Get the number of samples per channel the frame's duration covers
+    pub fn duration_samples(&self) -> usize {
+        let nanos = self.duration.as_nanos() * u128::from(self.sample_rate);
+        ((nanos + 500_000_000) / 1_000_000_000) as usize
+    }
+
+    /// Take the samples out of the frame, dropping its metadata
+    pub fn into_samples(self) -> Vec<Vec<MadFixed32>> {
+        self.samples
+    }
+
+    /// Convert the samples of each channel to `T`
+    pub fn convert<T: Sample>(&self) -> Vec<Vec<T>> {
+        self.samples
+            .iter()
+            .map(|channel| channel.iter().map(|&sample| T::from_fixed(sample)).collect())
+            .collect()
+    }
+
+    /// Append the samples to `out`, interleaved and converted to `T`
+    pub fn interleave<T: Sample>(&self, out: &mut Vec<T>) {
+        out.reserve(self.samples.len() * self.n_samples());
+        for index in 0..self.n_samples() {
+            for channel in &self.samples {
+                out.push(T::from_fixed(channel[index]));
+            }
+        }
+    }
+
+    /// Write the samples interleaved, converted to `T` and in little-endian
+    /// byte order, as in a WAV file
+    ///
+    /// Returns the number of bytes written.
+    pub fn write_pcm<T: Sample>(&self, writer: &mut dyn io::Write) -> io::Result<u64> {
+        for index in 0..self.n_samples() {
+            for channel in &self.samples {
+                T::from_fixed(channel[index]).write_le(writer)?;
+            }
+        }
+        Ok((self.samples.len() * self.n_samples() * mem::size_of::<T>()) as u64)
+    }
+
+    /// Write the samples like `write_pcm`, in the given format
+    pub fn write_pcm_as(&self,
+                        format: SampleFormat,
+                        writer: &mut dyn io::Write)
+                        -> io::Result<u64> {
+        match format {
+            SampleFormat::Fixed => self.write_pcm::<MadFixed32>(writer),
+            SampleFormat::I16 => self.write_pcm::<i16>(writer),
+            SampleFormat::I32 => self.write_pcm::<i32>(writer),
+            SampleFormat::F32 => self.write_pcm::<f32>(writer),
+            SampleFormat::F64 => self.write_pcm::<f64>(writer),
+        }
+    }
+
+    /// Split the frame into the samples before and after `offset`
+    ///
+    /// `offset` is relative to the start of the frame and is rounded down to
+    /// a sample. The positions and durations of both parts are adjusted, an
+    /// offset past the end of the frame leaves the second part empty.
+    pub fn split_at(mut self, offset: Duration) -> (Frame, Frame) {
+        let len = if self.samples.is_empty() {
+            self.duration_samples()
+        } else {
+            self.n_samples()
+        };
+        let index = offset.as_nanos() * u128::from(self.sample_rate) / 1_000_000_000;
+        let index = min(index, len as u128) as usize;
+        let head_duration = match len {
+            0 => Duration::new(0, 0),
+            len => self.duration * index as u32 / len as u32,
+        };
+
+        let mut head = mem::take(&mut self.samples);
+        let mut tail = self.clone();
+        tail.samples = head.iter_mut().map(|channel| channel.split_off(index)).collect();
+        tail.position += head_duration;
+        tail.duration -= head_duration;
+        tail.skipped_bytes = 0;
+        tail.gap = None;
+
+        self.samples = head;
+        self.duration = head_duration;
+        (self, tail)
+    }
+
+    /// Get the largest absolute sample value over all channels
+    pub fn peak(&self) -> MadFixed32 {
+        let peak = self.samples
+                       .iter()
+                       .flat_map(|channel| channel.iter())
+                       .map(|sample| sample.value.unsigned_abs())
+                       .max()
+                       .unwrap_or(0);
+        MadFixed32::new(min(peak, i32::MAX as u32) as i32)
+    }
+
+    /// Get the root mean square of the samples of all channels
+    pub fn rms(&self) -> MadFixed32 {
+        let mut sum = 0u128;
+        let mut count = 0u128;
+        for sample in self.samples.iter().flat_map(|channel| channel.iter()) {
+            let value = i64::from(sample.value);
+            sum += (value * value) as u128;
+            count += 1;
+        }
+        match sum.checked_div(count) {
+            Some(mean) => MadFixed32::new(min(mean.isqrt(), i32::MAX as u128) as i32),
+            None => MadFixed32::new(0),
+        }
+    }
+
+    /// Check whether no sample is louder than `threshold`
+    pub fn is_silent(&self, threshold: MadFixed32) -> bool {
+        self.peak().value <= threshold.value.saturating_abs()
+    }
+
+    /// Get the samples of one channel
+    ///
+    /// Returns `None` for `Channel::Right` of a single channel frame and for
+    /// frames decoded without samples.
+    pub fn channel(&self, channel: Channel) -> Option<&[MadFixed32]> {
+        self.samples.get(channel.index()).map(|samples| &samples[..])
+    }
+
+    /// Get the ancillary data carried by the frame
+    ///
+    /// This is empty for frames decoded without samples.
+    pub fn ancillary_data(&self) -> &[u8] {
+        &self.ancillary
+    }
+}
+
+/// A channel of a frame, see `Frame::channel`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// The left channel, or the only channel of a single channel stream
+    Left,
+    /// The right channel
+    Right,
+}
+
+impl Channel {
+    pub(crate) fn index(self) -> usize {
+        match self {
+            Channel::Left => 0,
+            Channel::Right => 1,
+        }
+    }
+}
+
+/// The result of a frame's CRC check
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrcStatus {
+    /// The frame has no CRC word
+    Unprotected,
+    /// The CRC word matches the frame
+    Valid,
+    /// The CRC word doesn't match, the frame was decoded because of
+    /// `DecoderBuilder::ignore_crc`
+    Invalid,
+    /// The frame has a CRC word, but only its header was decoded
+    Unchecked,
+}
+
+/// The header of a frame, without its audio data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Number of samples per second
+    pub sample_rate: u32,
+    /// Stream bit rate
+    pub bit_rate: u32,
+    /// Audio layer (I, II or III)
+    pub layer: Layer,
+    /// Single Channel, Dual Channel, Joint Stereo or Stereo
+    pub mode: Mode,
+    /// libmad's `MAD_FLAG_*` bits
+    pub flags: c_int,
+    /// The duration of the frame
+    pub duration: Duration,
+    /// The position at the start of the frame
+    pub position: Duration,
+    /// Byte offset of the frame in the input
+    pub byte_offset: u64,
+    /// De-emphasis to apply to the decoded audio
+    pub emphasis: Emphasis,
+    /// Whether the copyright bit is set
+    pub copyright: bool,
+    /// Whether the original bit is set, i.e. the stream isn't a copy
+    pub original: bool,
+    /// Whether the private bit is set
+    pub private: bool,
+    /// Whether the frame has an extra padding slot
+    pub padding: bool,
+    /// Whether the frame has CRC protection
+    pub crc: CrcStatus,
+    /// Number of bytes of audio data the frame takes from the frames before
+    /// it, see `Frame::main_data_begin`
+    pub main_data_begin: u32,
+    /// Whether the frames before this one hold `main_data_begin` bytes, see
+    /// `Frame::reservoir_complete`
+    pub reservoir_complete: bool,
+}
+
+impl FrameHeader {
+    /// Get the number of samples per channel the frame encodes, see
+    /// `samples_per_frame`
+    pub fn samples_per_frame(&self) -> usize {
+        samples_per_frame(self.layer, MpegVersion::from_flags(self.flags))
+    }
 }
 
 /// An interface for the decoding operation
 ///
-/// Create a decoder using `decode` or `decode_interval`. Fetch
-/// results with `get_frame` or the `Iterator` interface.
+/// Create a decoder using `decode`, `decode_interval` or `builder`.
+/// Fetch results with `get_frame` or the `Iterator` interface.
+///
+/// A `Decoder<R>` is `Send` if `R` is, so it can be moved to another thread.
 pub struct Decoder<R>
     where R: io::Read
 {
     reader: R,
-    buffer: Box<[u8; 32_768]>,
-    stream: MadStream,
-    synth: MadSynth,
-    frame: MadFrame,
+    buffer: Box<[u8]>,
+    mad: MadState,
     position: Duration,
-    headers_only: bool,
-    start_time: Option<Duration>,
-    end_time: Option<Duration>,
+    options: DecoderOptions,
+    // Stream offsets of the start of `buffer` and the end of the last frame
+    buffer_offset: u64,
+    sync_offset: u64,
+    frame_index: u64,
+    total_frames: Option<u64>,
+    xing: Option<xing::XingHeader>,
+    audio_offset: Option<u64>,
+    initial_bit_rate: Option<u32>,
+    recent_frames: VecDeque<u64>,
+    consecutive_errors: usize,
+    in_tag: bool,
+    error_observer: Option<ErrorObserver>,
+    format: Option<AudioFormat>,
+    format_observer: Option<FormatObserver>,
+    frame_observer: Option<FrameObserver>,
+    processors: Vec<Box<dyn Processor>>,
+    tee: Option<Box<dyn io::Write + Send>>,
+    events: Option<EventQueue>,
+    stats: DecoderStats,
+    bit_rate_sum: u64,
+    fade_origin: Option<Duration>,
+    de_emphasis: Option<DeEmphasis>,
+    reservoir: Reservoir,
+    // `main_data_begin` of the last header and whether the reservoir held it
+    main_data: (u32, bool),
+    // Sample buffers of recycled frames
+    sample_pool: Vec<Vec<Vec<MadFixed32>>>,
+    // Whether to decode frames in headers-only mode too, while skipping
+    warm_up_headers: bool,
+    // Whether `nth` is skipping frames, which bypass processors, observers
+    // and the format check
+    skipping: bool,
+    // Samples of the last frame, for `Concealment::Repeat`
+    repeat_samples: Vec<Vec<MadFixed32>>,
+    // The frames of the stream once read for `decode_at`
+    index: Option<FrameIndex>,
+    frame_cache: FrameCache,
+    done: bool,
 }
 
 impl<R> Decoder<R> where R: io::Read {
-    fn new(reader: R,
-           start_time: Option<Duration>,
-           end_time: Option<Duration>,
-           headers_only: bool)
-           -> Result<Decoder<R>, SimplemadError> {
+    fn new(reader: R, options: DecoderOptions) -> Result<Decoder<R>, SimplemadError> {
+        let options = options.normalized();
+        let buffer_size = max(options.buffer_size, MIN_BUFFER_SIZE);
+        let de_emphasis = if options.de_emphasis {
+            Some(DeEmphasis::new())
+        } else {
+            None
+        };
         let mut new_decoder = Decoder {
-            reader: reader,
-            buffer: Box::new([0u8; 32_768]),
-            stream: Default::default(),
-            synth: Default::default(),
-            frame: Default::default(),
+            reader,
+            buffer: vec![0u8; buffer_size].into_boxed_slice(),
+            mad: MadState::new(),
             position: Duration::new(0, 0),
-            headers_only: headers_only,
-            start_time: start_time,
-            end_time: end_time,
+            options,
+            buffer_offset: 0,
+            sync_offset: 0,
+            frame_index: 0,
+            total_frames: None,
+            xing: None,
+            audio_offset: None,
+            initial_bit_rate: None,
+            recent_frames: VecDeque::with_capacity(checkpoint::RECENT_FRAMES),
+            consecutive_errors: 0,
+            in_tag: false,
+            error_observer: None,
+            format: None,
+            format_observer: None,
+            frame_observer: None,
+            processors: Vec::new(),
+            tee: None,
+            events: None,
+            stats: DecoderStats::default(),
+            bit_rate_sum: 0,
+            fade_origin: None,
+            de_emphasis,
+            reservoir: Reservoir::default(),
+            main_data: (0, true),
+            sample_pool: Vec::new(),
+            warm_up_headers: false,
+            skipping: false,
+            repeat_samples: Vec::new(),
+            index: None,
+            frame_cache: FrameCache::default(),
+            done: false,
+        };
+
+        new_decoder.start()?;
+        Ok(new_decoder)
+    }
+
+    fn start(&mut self) -> Result<(), SimplemadError> {
+        let bytes_read = match self.reader.read(&mut self.buffer) {
+            Ok(n) => n,
+            // Nothing is buffered yet, `get_frame` will try again
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => 0,
+            Err(e) => return Err(SimplemadError::Read(e)),
         };
 
-        let bytes_read = try!(new_decoder.reader.read(&mut *new_decoder.buffer));
+        if let Some(format) = sniff::detect_format(&self.buffer[..bytes_read]) {
+            return Err(SimplemadError::InvalidFormat(format));
+        }
 
+        self.mad.stream.options = self.options.mad_options();
         unsafe {
-            mad_stream_init(&mut new_decoder.stream);
-            mad_frame_init(&mut new_decoder.frame);
-            mad_synth_init(&mut new_decoder.synth);
-            mad_stream_buffer(&mut new_decoder.stream,
-                              new_decoder.buffer.as_ptr(),
-                              bytes_read as c_ulong);
+            mad_stream_buffer(&mut self.mad.stream, self.buffer.as_ptr(), bytes_read as c_ulong);
         }
 
-        Ok(new_decoder)
+        Ok(())
+    }
+
+    /// Get the total number of frames in the stream
+    ///
+    /// This is known once the first frame has been decoded if the stream has
+    /// a Xing or Info header, or if it has a constant bit rate and its length
+    /// was given with `DecoderBuilder::input_len`.
+    pub fn total_len(&self) -> Option<u64> {
+        self.total_frames
+    }
+
+    /// Estimate the duration of a stream that is `total_bytes` long
+    ///
+    /// Assumes that every frame has the bit rate of the first one, which is
+    /// only accurate for constant bit rate streams. Returns `None` until the
+    /// first frame has been decoded.
+    pub fn estimated_duration(&self, total_bytes: u64) -> Option<Duration> {
+        let bit_rate = match self.initial_bit_rate? {
+            0 => return None,
+            bit_rate => u128::from(bit_rate),
+        };
+        let audio_len = total_bytes.saturating_sub(self.audio_offset.unwrap_or(0));
+        let nanos = u128::from(audio_len) * 8 * 1_000_000_000 / bit_rate;
+        Some(Duration::from_nanos(nanos as u64))
+    }
+
+    /// Estimate the duration of the whole stream
+    ///
+    /// Uses the frame count of a Xing or Info header, or else the length
+    /// given with `DecoderBuilder::input_len` and the bit rate of the first
+    /// frame. Returns `None` until the first frame has been decoded.
+    pub fn estimated_total_duration(&self) -> Option<Duration> {
+        let frames = u32::try_from(self.total_frames?).ok()?;
+        frame_duration(&self.mad.frame).checked_mul(frames)
+    }
+
+    /// Get the fraction of the input decoded so far, from 0 to 1
+    ///
+    /// Measured in bytes if the length of the input was given with
+    /// `DecoderBuilder::input_len`, otherwise in frames if the stream has a
+    /// Xing or Info header.
+    pub fn progress(&self) -> Option<f64> {
+        if self.done {
+            return Some(1.0);
+        }
+        let fraction = match (self.options.input_len, self.total_frames) {
+            (Some(len), _) if len > 0 => self.sync_offset as f64 / len as f64,
+            (_, Some(total)) if total > 0 => self.frame_index as f64 / total as f64,
+            _ => return None,
+        };
+        Some(fraction.min(1.0))
+    }
+
+    /// Iterate over the frames of a stream whose length is known
+    ///
+    /// Decodes up to the first frame and returns `None` if `total_len`
+    /// isn't known by then. Recoverable errors are skipped.
+    pub fn exact_frames(mut self) -> Option<ExactFrames<R>> {
+        let first = loop {
+            match self.get_frame() {
+                Ok(frame) => break frame,
+                Err(ref e) if e.is_recoverable() => continue,
+                Err(_) => return None,
+            }
+        };
+
+        let total = self.total_frames?;
+        Some(ExactFrames {
+            remaining: total.saturating_sub(self.frame_index - 1) as usize,
+            first: Some(first),
+            decoder: self,
+            error: None,
+        })
+    }
+
+    /// Release libmad's state and return the underlying reader
+    ///
+    /// Also returns the number of input bytes the decoder has processed.
+    /// The reader itself may be further ahead because of buffering, a
+    /// `Seek` reader can be moved back to this offset to continue from
+    /// where decoding stopped.
+    pub fn into_inner(self) -> (R, u64) {
+        let offset = self.bytes_consumed();
+        (self.reader, offset)
+    }
+
+    /// Get the number of input bytes the decoder has processed
+    ///
+    /// Data that has been read into the buffer but not parsed yet isn't
+    /// counted, so this is the offset of the end of the last frame or error.
+    pub fn bytes_consumed(&self) -> u64 {
+        self.offset_of(self.mad.stream.next_frame)
+    }
+
+    /// Get the encoded bytes of the most recent frame
+    ///
+    /// The bytes are borrowed from the input buffer, so they are only
+    /// available until the next call to `get_frame`. After an error they
+    /// are the bytes that were skipped.
+    pub fn frame_bytes(&self) -> &[u8] {
+        let buffer_start = self.mad.stream.buffer as usize;
+        let start = self.mad.stream.this_frame as usize - buffer_start;
+        let end = self.mad.stream.next_frame as usize - buffer_start;
+        &self.buffer[start..max(start, end)]
+    }
+
+    /// Get libmad's stream state
+    ///
+    /// # Safety
+    ///
+    /// The stream points into the decoder's input buffer. Changing its
+    /// pointers, buffer or options can make later calls read out of bounds,
+    /// and any change can leave the decoder's own bookkeeping inconsistent.
+    #[cfg(feature = "sys")]
+    pub unsafe fn raw_stream(&mut self) -> &mut MadStream {
+        &mut self.mad.stream
+    }
+
+    /// Get libmad's frame state, holding the last decoded header and
+    /// subband samples
+    ///
+    /// # Safety
+    ///
+    /// After a failed decode the header's `layer` and `mode` can hold values
+    /// outside their enums, reading them is undefined behavior. libmad
+    /// reuses a header marked with `MAD_FLAG_INCOMPLETE` instead of reading
+    /// the next one, so changes can affect the next frame.
+    #[cfg(feature = "sys")]
+    pub unsafe fn raw_frame(&mut self) -> &mut MadFrame {
+        &mut self.mad.frame
+    }
+
+    /// Get libmad's synthesis state, holding the PCM samples of the last
+    /// decoded frame
+    ///
+    /// The state is created when the first frame is synthesized, or by this
+    /// call.
+    ///
+    /// # Safety
+    ///
+    /// Changing the PCM length or channel count beyond the buffers' bounds
+    /// makes the decoder read out of bounds.
+    #[cfg(feature = "sys")]
+    pub unsafe fn raw_synth(&mut self) -> &mut MadSynth {
+        self.mad.synth.get_or_insert_with(init_synth)
+    }
+
+    /// Get the header of the next frame without decoding the frame
+    ///
+    /// The frame is still returned by the next call to `get_frame`, so
+    /// e.g. an audio output can be configured before the first samples are
+    /// pulled. Recoverable errors before the frame are reported to the
+    /// error observer and skipped. Returns `SimplemadError::EOF` at the end
+    /// of the stream.
+    pub fn peek_header(&mut self) -> Result<FrameHeader, SimplemadError> {
+        if self.done {
+            return Err(SimplemadError::EOF);
+        }
+        if let Some(t) = self.options.start_time {
+            if self.position < t {
+                self.seek_to_start()?;
+            }
+        }
+        if let Some(t) = self.options.end_time {
+            if self.position >= t {
+                return Err(SimplemadError::EOF);
+            }
+        }
+
+        let header = HeaderMark::new(&self.mad.frame.header);
+        let (reservoir, main_data) = (self.reservoir, self.main_data);
+        let result = loop {
+            let mark = StreamMark::new(&self.mad.stream);
+            match self.decode_header() {
+                Ok(()) => {
+                    let next = self.current_header();
+                    mark.restore(&mut self.mad.stream);
+                    break Ok(next);
+                }
+                Err(SimplemadError::Mad { error: LibmadError::BufLen, .. }) => {
+                    match self.refill_buffer() {
+                        Ok(0) => break Err(SimplemadError::EOF),
+                        Ok(_) => {}
+                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            break Err(SimplemadError::WouldBlock);
+                        }
+                        Err(e) => break Err(SimplemadError::Read(e)),
+                    }
+                }
+                Err(error) => {
+                    let error = self.report(error);
+                    if self.options.strictness == Strictness::Strict && self.frame_index > 0 {
+                        self.in_tag = self.in_tag || self.tag_at().is_some();
+                        if !self.in_tag {
+                            self.done = true;
+                            break Err(error);
+                        }
+                    }
+                    if !error.is_recoverable() {
+                        break Err(error);
+                    }
+                }
+            }
+        };
+        header.restore(&mut self.mad.frame.header);
+        self.reservoir = reservoir;
+        self.main_data = main_data;
+        result
+    }
+
+    /// Hand a frame that is no longer needed back to the decoder, whose
+    /// sample buffers are then reused for the next frames
+    ///
+    /// This saves allocating new buffers for every frame.
+    pub fn recycle(&mut self, frame: Frame) {
+        if self.sample_pool.len() < MAX_POOLED_FRAMES && !frame.samples.is_empty() {
+            self.sample_pool.push(frame.samples);
+        }
+    }
+
+    /// Decode the next frame and write its samples to `writer`, in the
+    /// format set by `DecoderBuilder::sample_format`
+    ///
+    /// The samples are interleaved and in little-endian byte order, as in a
+    /// WAV file. Returns the number of bytes written, and
+    /// `SimplemadError::EOF` at the end of the stream.
+    pub fn write_pcm(&mut self, writer: &mut dyn io::Write) -> Result<u64, SimplemadError> {
+        let frame = self.get_frame()?;
+        let written = frame.write_pcm_as(self.options.sample_format, writer);
+        self.recycle(frame);
+        written.map_err(SimplemadError::Write)
+    }
+
+    /// Get counters of the decoding so far
+    ///
+    /// Counts since the decoder was created or last reset.
+    pub fn stats(&self) -> DecoderStats {
+        let mut stats = self.stats;
+        stats.bytes_consumed = self.bytes_consumed();
+        if let Some(average) = self.bit_rate_sum.checked_div(stats.frames_decoded) {
+            stats.average_bit_rate = average as u32;
+        }
+        stats
+    }
+
+    /// Iterate over the headers of the remaining frames
+    ///
+    /// Like `decode_headers`, but yields `FrameHeader`s instead of `Frame`s
+    /// without samples.
+    pub fn headers(mut self) -> Headers<R> {
+        self.options.headers_only = true;
+        Headers { decoder: self }
+    }
+
+    /// Configure a decoder using a `DecoderBuilder`
+    pub fn builder(reader: R) -> DecoderBuilder<R> {
+        DecoderBuilder::new(reader)
     }
 
     /// Decode a file in full
     pub fn decode(reader: R) -> Result<Decoder<R>, SimplemadError> {
-        Decoder::new(reader, None, None, false)
+        Decoder::builder(reader).build()
     }
 
     /// Decode only the header information of each frame
     pub fn decode_headers(reader: R) -> Result<Decoder<R>, SimplemadError> {
-        Decoder::new(reader, None, None, true)
+        Decoder::builder(reader).headers_only(true).build()
     }
 
     /// Decode part of a file from `start_time` to `end_time`
@@ -150,165 +875,639 @@ impl<R> Decoder<R> where R: io::Read {
                            start_time: Duration,
                            end_time: Duration)
                            -> Result<Decoder<R>, SimplemadError> {
-        Decoder::new(reader, Some(start_time), Some(end_time), false)
+        Decoder::builder(reader).interval(start_time, end_time).build()
+    }
+
+    /// Decode only the header information of the frames from `start_time` to
+    /// `end_time`
+    pub fn decode_headers_interval(reader: R,
+                                   start_time: Duration,
+                                   end_time: Duration)
+                                   -> Result<Decoder<R>, SimplemadError> {
+        Decoder::builder(reader).headers_only(true).interval(start_time, end_time).build()
     }
 
     /// Get the next decoding result, either a `Frame` or a `SimplemadError`
     pub fn get_frame(&mut self) -> Result<Frame, SimplemadError> {
-        if let Some(t) = self.start_time {
-            if self.position < t {
-                return self.seek_to_start();
-            }
-        }
-
-        if let Some(t) = self.end_time {
-            if self.position >= t {
+        // Skipping an error or refilling the buffer tries again
+        loop {
+            if self.done {
                 return Err(SimplemadError::EOF);
             }
-        }
 
-        let decoding_result = if self.headers_only {
-            self.decode_header_only()
-        } else {
-            self.decode_frame()
-        };
+            if let Some(t) = self.options.start_time {
+                if self.position < t {
+                    self.seek_to_start()?;
+                }
+            }
 
-        match decoding_result {
-            Ok(frame) => {
-                self.position = self.position + frame_duration(&self.frame);
-                Ok(frame)
+            if let Some(t) = self.options.end_time {
+                if self.position >= t {
+                    self.done = true;
+                    return Err(SimplemadError::EOF);
+                }
             }
-            Err(SimplemadError::Mad(MadError::BufLen)) => {
-                // Refill buffer and try again
-                if try!(self.refill_buffer()) == 0 {
-                    Err(SimplemadError::EOF)
-                } else {
-                    self.get_frame()
+
+            let decoding_result = if self.options.headers_only {
+                self.decode_header_only()
+            } else {
+                self.decode_frame()
+            };
+
+            if let Err(SimplemadError::Mad { .. }) = decoding_result {
+                if let Some(limit) = self.options.max_resync_bytes {
+                    let scanned = self.offset_of(self.mad.stream.next_frame) - self.sync_offset;
+                    if scanned > limit {
+                        self.done = true;
+                        let error = SimplemadError::SyncLimitExceeded {
+                            scanned,
+                            limit,
+                            context: self.error_context(),
+                        };
+                        return Err(self.report(error));
+                    }
                 }
             }
-            Err(e) => Err(e),
+
+            return match decoding_result {
+                Ok(mut frame) => {
+                    if self.frame_index == 0 {
+                        self.xing = xing::parse(self.frame_bytes());
+                        self.total_frames = self.estimate_total_frames();
+                    }
+                    frame.gap = self.gap_before(&frame);
+                    if !self.skipping {
+                        for processor in &mut self.processors {
+                            processor.process(&mut frame);
+                        }
+                    }
+                    self.mark_audio_start();
+                    let change = if self.skipping { None } else { self.check_format(&frame) };
+                    if let Some(change) = change {
+                        if change.previous.is_some() && self.options.consistent_format {
+                            self.done = true;
+                            let error = SimplemadError::FormatChanged {
+                                change,
+                                context: self.error_context(),
+                            };
+                            self.recycle(frame);
+                            return Err(self.report(error));
+                        }
+                    }
+                    let frame_offset = self.offset_of(self.mad.stream.this_frame);
+                    if self.recent_frames.len() == checkpoint::RECENT_FRAMES {
+                        self.recent_frames.pop_front();
+                    }
+                    self.recent_frames.push_back(frame_offset);
+                    self.stats.frames_decoded += 1;
+                    self.stats.bytes_skipped += self.skipped_bytes();
+                    self.bit_rate_sum += u64::from(frame.bit_rate);
+                    self.sync_offset = self.offset_of(self.mad.stream.next_frame);
+                    self.position += frame_duration(&self.mad.frame);
+                    self.frame_index += 1;
+                    self.consecutive_errors = 0;
+                    self.in_tag = false;
+                    if let Some(ref mut observer) = self.frame_observer {
+                        if !self.skipping {
+                            observer(&frame);
+                        }
+                    }
+                    Ok(frame)
+                }
+                Err(SimplemadError::Mad { error: LibmadError::BufLen, .. }) => {
+                    // Refill buffer and try again
+                    match self.refill_buffer() {
+                        Ok(0) => {
+                            self.done = true;
+                            Err(SimplemadError::EOF)
+                        }
+                        Ok(_) => continue,
+                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            Err(SimplemadError::WouldBlock)
+                        }
+                        Err(e) => {
+                            self.done = true;
+                            Err(self.report(SimplemadError::Read(e)))
+                        }
+                    }
+                }
+                Err(error @ SimplemadError::Mad { .. }) => {
+                    let error = self.report(error);
+
+                    if self.options.strictness == Strictness::Strict && self.frame_index > 0 {
+                        // Tolerate errors from trailing tags until the next frame
+                        self.in_tag = self.in_tag || self.tag_at().is_some();
+                        if !self.in_tag {
+                            self.done = true;
+                            return Err(error);
+                        }
+                    }
+
+                    self.consecutive_errors += 1;
+                    if let Some(limit) = self.options.max_consecutive_errors {
+                        if self.consecutive_errors >= limit {
+                            self.done = true;
+                            let error = SimplemadError::TooManyErrors {
+                                count: self.consecutive_errors,
+                                context: self.error_context(),
+                            };
+                            return Err(self.report(error));
+                        }
+                    }
+
+                    if error.is_recoverable() &&
+                       self.options.error_policy == ErrorPolicy::SkipRecoverable {
+                        continue;
+                    }
+                    Err(error)
+                }
+                Err(error @ SimplemadError::RejectedLayer { .. }) => {
+                    let error = self.report(error);
+                    self.sync_offset = self.offset_of(self.mad.stream.next_frame);
+                    self.position += frame_duration(&self.mad.frame);
+                    if self.options.error_policy == ErrorPolicy::SkipRecoverable {
+                        continue;
+                    }
+                    Err(error)
+                }
+                Err(e) => Err(self.report(e)),
+            };
         }
     }
 
-    fn seek_to_start(&mut self) -> Result<Frame, SimplemadError> {
-        if let Some(start_time) = self.start_time {
+    // Skip the frames before the start time
+    fn seek_to_start(&mut self) -> Result<(), SimplemadError> {
+        if let Some(start_time) = self.options.start_time {
             while self.position < start_time {
-                match self.decode_header_only() {
-                    Ok(frame) => {
-                        self.position = self.position + frame.duration;
+                match self.decode_header() {
+                    Ok(()) => {
+                        self.mark_audio_start();
+                        let duration = frame_duration(&self.mad.frame);
+                        if !self.options.headers_only &&
+                           self.position + duration * self.warm_up_len() >= start_time {
+                            self.warm_up_frame();
+                        }
+                        self.sync_offset = self.offset_of(self.mad.stream.next_frame);
+                        self.position += duration;
                     }
-                    Err(SimplemadError::Mad(MadError::BufLen)) => {
-                        if try!(self.refill_buffer()) == 0 {
-                            return Err(SimplemadError::EOF);
+                    Err(SimplemadError::Mad { error: LibmadError::BufLen, .. }) => {
+                        match self.refill_buffer() {
+                            Ok(0) => {
+                                self.done = true;
+                                return Err(SimplemadError::EOF);
+                            }
+                            Ok(_) => {}
+                            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                                return Err(SimplemadError::WouldBlock);
+                            }
+                            Err(e) => {
+                                self.done = true;
+                                return Err(self.report(SimplemadError::Read(e)));
+                            }
                         }
                     }
-                    Err(e) => return Err(e),
+                    Err(e) => return Err(self.report(e)),
                 }
             }
         }
 
-        self.get_frame()
+        // The last header read mustn't be decoded again
+        self.mad.frame.header.flags &= !MAD_FLAG_INCOMPLETE;
+        Ok(())
     }
 
-    fn decode_header_only(&mut self) -> Result<Frame, SimplemadError> {
+    // Layer III frames use data from the frames before them, the number of
+    // frames before a frame to decode to start cleanly at it: enough to fill
+    // the reservoir, and one for the overlap of the transforms
+    fn warm_up_len(&self) -> u32 {
+        match reservoir::side_info(self.frame_bytes()) {
+            Some((_, data_len)) => MAX_MAIN_DATA_BEGIN.div_ceil(max(data_len, 1)) + 1,
+            None => 1,
+        }
+    }
+
+    // Decode a frame before the start time and discard the result
+    fn warm_up_frame(&mut self) {
+        unsafe {
+            mad_frame_decode(&mut self.mad.frame, &mut self.mad.stream);
+        }
+        // Errors are expected while the bit reservoir is filled
+        if self.check_error().is_none() {
+            self.mad.synth_frame();
+        }
+    }
+
+    fn decode_header(&mut self) -> Result<(), SimplemadError> {
         unsafe {
-            mad_header_decode(&mut self.frame.header, &mut self.stream);
+            mad_header_decode(&mut self.mad.frame.header, &mut self.mad.stream);
         }
 
         if let Some(error) = self.check_error() {
-            return Err(SimplemadError::Mad(error));
+            return Err(SimplemadError::Mad {
+                error,
+                context: self.error_context(),
+            });
+        }
+
+        let mut reservoir = self.reservoir;
+        let offset = self.offset_of(self.mad.stream.this_frame);
+        self.main_data = reservoir.push(offset, self.frame_bytes());
+        self.reservoir = reservoir;
+        Ok(())
+    }
+
+    fn check_layer(&mut self) -> Result<(), SimplemadError> {
+        if let Some(ref layers) = self.options.layers {
+            let layer = Layer::from(self.mad.frame.header.layer);
+            if !layers.contains(&layer) {
+                // Make sure the rejected frame's data isn't decoded later
+                self.mad.frame.header.flags &= !MAD_FLAG_INCOMPLETE;
+                return Err(SimplemadError::RejectedLayer {
+                    found: layer,
+                    context: self.error_context(),
+                });
+            }
         }
 
-        Ok(Frame {
-            sample_rate: self.frame.header.sample_rate,
-            mode: self.frame.header.mode,
-            layer: self.frame.header.layer,
-            bit_rate: self.frame.header.bit_rate as u32,
+        Ok(())
+    }
+
+    // The header libmad decoded last
+    fn current_header(&self) -> FrameHeader {
+        let header = &self.mad.frame.header;
+        FrameHeader {
+            sample_rate: header.sample_rate,
+            bit_rate: header.bit_rate as u32,
+            layer: header.layer.into(),
+            mode: header.mode.into(),
+            flags: header.flags,
+            duration: frame_duration(&self.mad.frame),
+            position: self.position,
+            byte_offset: self.offset_of(self.mad.stream.this_frame),
+            emphasis: header.emphasis.into(),
+            copyright: self.header_flag(MAD_FLAG_COPYRIGHT),
+            original: self.header_flag(MAD_FLAG_ORIGINAL),
+            private: header.private_bits & MAD_PRIVATE_HEADER != 0,
+            padding: self.header_flag(MAD_FLAG_PADDING),
+            crc: self.crc_status(false),
+            main_data_begin: self.main_data.0,
+            reservoir_complete: self.main_data.1,
+        }
+    }
+
+    fn decode_header_only(&mut self) -> Result<Frame, SimplemadError> {
+        self.decode_header()?;
+        self.check_layer()?;
+        if self.warm_up_headers {
+            self.warm_up_frame();
+        }
+        Ok(self.decode_header_only_frame())
+    }
+
+    // A frame without samples from the last header read
+    fn decode_header_only_frame(&self) -> Frame {
+        Frame {
+            sample_rate: self.mad.frame.header.sample_rate,
+            mode: self.mad.frame.header.mode.into(),
+            layer: self.mad.frame.header.layer.into(),
+            bit_rate: self.mad.frame.header.bit_rate as u32,
             samples: Vec::new(),
-            duration: frame_duration(&self.frame),
+            duration: frame_duration(&self.mad.frame),
             position: self.position,
-        })
+            skipped_bytes: self.skipped_bytes(),
+            byte_offset: self.offset_of(self.mad.stream.this_frame),
+            emphasis: self.mad.frame.header.emphasis.into(),
+            copyright: self.header_flag(MAD_FLAG_COPYRIGHT),
+            original: self.header_flag(MAD_FLAG_ORIGINAL),
+            private: self.mad.frame.header.private_bits & MAD_PRIVATE_HEADER != 0,
+            padding: self.header_flag(MAD_FLAG_PADDING),
+            crc: self.crc_status(false),
+            encoded: self.encoded_copy(),
+            ancillary: Vec::new(),
+            main_data_begin: self.main_data.0,
+            reservoir_complete: self.main_data.1,
+            concealed: false,
+            gap: None,
+        }
     }
 
     fn decode_frame(&mut self) -> Result<Frame, SimplemadError> {
+        // A header marked incomplete (e.g. the last one read while seeking)
+        // is picked up by libmad instead of being decoded again
+        if self.mad.frame.header.flags & MAD_FLAG_INCOMPLETE == 0 {
+            self.decode_header()?;
+        }
+        self.check_layer()?;
+
         unsafe {
-            mad_frame_decode(&mut self.frame, &mut self.stream);
+            mad_frame_decode(&mut self.mad.frame, &mut self.mad.stream);
         }
 
         if let Some(error) = self.check_error() {
-            return Err(SimplemadError::Mad(error));
+            return self.conceal(error);
         }
 
-        unsafe {
-            mad_synth_frame(&mut self.synth, &mut self.frame);
-        }
+        self.mad.synth_frame();
 
         if let Some(error) = self.check_error() {
-            return Err(SimplemadError::Mad(error));
-        }
-
-        let pcm = &self.synth.pcm;
-        let samples = pcm.samples
-                         .into_iter()
-                         .take(pcm.channels as usize)
-                         .map(|ch| {
-                             ch.into_iter()
-                               .take(pcm.length as usize)
-                               .map(|sample| MadFixed32::from(*sample))
-                               .collect()
-                         })
-                         .collect();
-
-        Ok(Frame {
-            sample_rate: pcm.sample_rate,
-            duration: frame_duration(&self.frame),
-            mode: self.frame.header.mode,
-            layer: self.frame.header.layer,
-            bit_rate: self.frame.header.bit_rate as u32,
+            return self.conceal(error);
+        }
+
+        let pcm = match self.mad.synth {
+            Some(ref synth) => &synth.pcm,
+            None => unreachable!("frame wasn't synthesized"),
+        };
+        let length = pcm.length as usize;
+        // Reuse the buffers of a recycled frame if there is one
+        let mut samples = self.sample_pool.pop().unwrap_or_default();
+        match self.options.downmix {
+            Some(downmix) if pcm.channels == 2 => {
+                let (left, right) = (&pcm.samples[0][..length], &pcm.samples[1][..length]);
+                let count = self.options.channels.as_ref().map_or(1, |map| map.len());
+                samples.resize_with(count, Vec::new);
+                if let Some((mixed, copies)) = samples.split_first_mut() {
+                    mixed.clear();
+                    mixed.extend(left.iter()
+                                     .zip(right)
+                                     .map(|(&l, &r)| MadFixed32::new(downmix.mix(l, r))));
+                    for copy in copies {
+                        copy.clear();
+                        copy.extend_from_slice(mixed);
+                    }
+                }
+            }
+            _ => {
+                let last = (pcm.channels as usize).saturating_sub(1);
+                let count = self.options.channels.as_ref().map_or(last + 1, |map| map.len());
+                samples.resize_with(count, Vec::new);
+                for (index, channel) in samples.iter_mut().enumerate() {
+                    let source = match self.options.channels {
+                        Some(ref map) => min(map[index].index(), last),
+                        None => index,
+                    };
+                    channel.clear();
+                    channel.extend(pcm.samples[source][..length]
+                                       .iter()
+                                       .map(|&sample| MadFixed32::new(sample)));
+                }
+            }
+        }
+        if let Some(factor) = self.options.gain_factor() {
+            for sample in samples.iter_mut().flat_map(|channel| channel.iter_mut()) {
+                sample.value = builder::apply_gain(sample.value, factor);
+            }
+        }
+        let (sample_rate, duration) = (pcm.sample_rate, frame_duration(&self.mad.frame));
+        self.apply_fades(&mut samples, sample_rate, duration);
+
+        let mut frame = Frame {
+            sample_rate,
+            duration,
+            mode: self.mad.frame.header.mode.into(),
+            layer: self.mad.frame.header.layer.into(),
+            bit_rate: self.mad.frame.header.bit_rate as u32,
             position: self.position,
-            samples: samples,
-        })
+            skipped_bytes: self.skipped_bytes(),
+            byte_offset: self.offset_of(self.mad.stream.this_frame),
+            emphasis: self.mad.frame.header.emphasis.into(),
+            copyright: self.header_flag(MAD_FLAG_COPYRIGHT),
+            original: self.header_flag(MAD_FLAG_ORIGINAL),
+            private: self.mad.frame.header.private_bits & MAD_PRIVATE_HEADER != 0,
+            padding: self.header_flag(MAD_FLAG_PADDING),
+            crc: self.crc_status(true),
+            encoded: self.encoded_copy(),
+            ancillary: self.ancillary_copy(),
+            main_data_begin: self.main_data.0,
+            reservoir_complete: self.main_data.1,
+            concealed: false,
+            gap: None,
+            samples,
+        };
+        if let Some(ref mut filter) = self.de_emphasis {
+            filter.process(&mut frame);
+        }
+        if self.options.concealment == Some(Concealment::Repeat) {
+            self.repeat_samples.clone_from(&frame.samples);
+        }
+        Ok(frame)
+    }
+
+    // Replace a frame whose audio data is damaged if concealment is enabled,
+    // or return the error
+    fn conceal(&mut self, error: LibmadError) -> Result<Frame, SimplemadError> {
+        let error = SimplemadError::Mad {
+            error,
+            context: self.error_context(),
+        };
+        let concealment = match self.options.concealment {
+            Some(concealment) if error.is_recoverable() => concealment,
+            _ => return Err(error),
+        };
+        self.report(error);
+
+        let mut frame = self.decode_header_only_frame();
+        let halve = if self.options.half_sample_rate { 2 } else { 1 };
+        let version = MpegVersion::from_flags(self.mad.frame.header.flags);
+        let length = samples_per_frame(frame.layer, version) / halve;
+        frame.sample_rate /= halve as u32;
+        frame.crc = self.crc_status(true);
+        frame.concealed = true;
+        frame.samples = match concealment {
+            Concealment::Repeat if self.repeat_samples.first().map(Vec::len) == Some(length) => {
+                self.repeat_samples.clone()
+            }
+            _ => vec![vec![MadFixed32::default(); length]; self.output_channels()],
+        };
+        Ok(frame)
+    }
+
+    // The number of channels of the decoded frames
+    fn output_channels(&self) -> usize {
+        let mode: Mode = self.mad.frame.header.mode.into();
+        let source = if mode == Mode::SingleChannel { 1 } else { 2 };
+        match self.options.channels {
+            Some(ref map) => map.len(),
+            None if self.options.downmix.is_some() => 1,
+            None => source,
+        }
     }
 
     fn refill_buffer(&mut self) -> Result<usize, io::Error> {
         let buffer_len = self.buffer.len();
-        let next_frame_position = self.stream.next_frame as usize - self.stream.buffer as usize;
-        let unused_byte_count = buffer_len - min(next_frame_position, buffer_len);
+        let data_len = self.mad.stream.buff_end as usize - self.mad.stream.buffer as usize;
+        let next_frame_position = min(self.mad.stream.next_frame as usize - self.mad.stream.buffer as usize,
+                                      data_len);
+        let unused_byte_count = data_len - next_frame_position;
+        self.buffer_offset += next_frame_position as u64;
 
-        // Shift unused data to front of buffer
-        for idx in 0..unused_byte_count {
-            self.buffer[idx] = self.buffer[idx + next_frame_position];
+        // Shift unused data to front of buffer, unless it's there already
+        if next_frame_position > 0 && unused_byte_count > 0 {
+            self.buffer.copy_within(next_frame_position..data_len, 0);
         }
 
         // Refill rest of buffer
         let mut free_region_start = unused_byte_count;
+        let mut read_error = None;
         while free_region_start != buffer_len {
             let slice = &mut self.buffer[free_region_start..buffer_len];
-            match try!(self.reader.read(slice)) {
-                0 => break,
-                n => free_region_start += n,
+            match self.reader.read(slice) {
+                Ok(0) => break,
+                Ok(n) => free_region_start += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    read_error = Some(e);
+                    break;
+                }
             }
         }
 
+        // The stream has to point at the shifted data even if reading failed
         unsafe {
-            mad_stream_buffer(&mut self.stream,
+            mad_stream_buffer(&mut self.mad.stream,
                               self.buffer.as_ptr(),
                               free_region_start as c_ulong);
         }
 
+        if let Some(ref mut tee) = self.tee {
+            tee.write_all(&self.buffer[unused_byte_count..free_region_start])?;
+        }
+
         let bytes_read = free_region_start - unused_byte_count;
-        Ok(bytes_read)
+        match read_error {
+            // Decode whatever arrived before the reader ran dry
+            Some(ref e) if e.kind() == io::ErrorKind::WouldBlock && bytes_read > 0 => {
+                Ok(bytes_read)
+            }
+            Some(e) => Err(e),
+            None => Ok(bytes_read),
+        }
     }
 
-    fn check_error(&mut self) -> Option<MadError> {
-        if self.stream.error != MadError::None {
-            let error = self.stream.error;
-            self.stream.error = MadError::None;
-            Some(error)
-        } else {
+    pub(crate) fn buffered_bytes(&self) -> &[u8] {
+        let len = self.mad.stream.buff_end as usize - self.mad.stream.buffer as usize;
+        &self.buffer[..len]
+    }
+
+    fn encoded_copy(&self) -> Option<Vec<u8>> {
+        if self.options.keep_encoded {
+            Some(self.frame_bytes().to_vec())
+        } else {
             None
         }
     }
+
+    fn ancillary_copy(&self) -> Vec<u8> {
+        // libmad's count wraps around when corrupt side information claims
+        // more audio data than the frame has
+        let bits = self.mad.stream.anc_bitlen;
+        let max_bits = (self.frame_bytes().len() as u64 + u64::from(MAX_MAIN_DATA_BEGIN)) * 8;
+        if u64::from(bits) > max_bits {
+            return Vec::new();
+        }
+
+        unsafe { read_bits(&self.mad.stream.anc_ptr, bits) }
+    }
+
+    // Called on the first frame, after looking for a Xing header
+    fn estimate_total_frames(&self) -> Option<u64> {
+        if let Some(frames) = self.xing.and_then(|xing| xing.frames) {
+            return Some(u64::from(frames));
+        }
+
+        // Assume a constant bit rate
+        let input_len = self.options.input_len?;
+        let audio_len = input_len.saturating_sub(self.offset_of(self.mad.stream.this_frame));
+        let audio_bits = u128::from(audio_len) * 8;
+        let frame_bits = u128::from(self.mad.frame.header.bit_rate as u32) *
+                         frame_duration(&self.mad.frame).as_nanos() / 1_000_000_000;
+        match frame_bits {
+            0 => None,
+            frame_bits => Some((audio_bits / frame_bits) as u64),
+        }
+    }
+
+    fn mark_audio_start(&mut self) {
+        if self.audio_offset.is_none() {
+            self.audio_offset = Some(self.offset_of(self.mad.stream.this_frame));
+        }
+        if self.initial_bit_rate.is_none() {
+            self.initial_bit_rate = Some(self.mad.frame.header.bit_rate as u32);
+        }
+    }
+
+    fn header_flag(&self, flag: c_int) -> bool {
+        self.mad.frame.header.flags & flag != 0
+    }
+
+    fn crc_status(&self, checked: bool) -> CrcStatus {
+        let header = &self.mad.frame.header;
+        if header.flags & MAD_FLAG_PROTECTION == 0 {
+            CrcStatus::Unprotected
+        } else if !checked {
+            CrcStatus::Unchecked
+        } else if header.crc_check == header.crc_target {
+            CrcStatus::Valid
+        } else {
+            CrcStatus::Invalid
+        }
+    }
+
+    // Estimate the audio lost in the bytes skipped before the current frame
+    fn gap_before(&self, frame: &Frame) -> Option<Duration> {
+        let len = self.frame_bytes().len() as u128;
+        if self.frame_index == 0 || frame.skipped_bytes == 0 || len == 0 {
+            return None;
+        }
+        let duration = frame_duration(&self.mad.frame);
+        let nanos = duration.as_nanos() * u128::from(frame.skipped_bytes) / len;
+        Some(Duration::from_nanos(nanos as u64))
+    }
+
+    fn skipped_bytes(&self) -> u64 {
+        self.offset_of(self.mad.stream.this_frame) - self.sync_offset
+    }
+
+    fn error_context(&self) -> ErrorContext {
+        ErrorContext {
+            byte_offset: self.offset_of(self.mad.stream.this_frame),
+            position: self.position,
+            frame_index: self.frame_index,
+        }
+    }
+
+    fn report(&mut self, error: SimplemadError) -> SimplemadError {
+        if error.is_recoverable() {
+            self.stats.recoverable_errors += 1;
+        }
+        let context = error.context().unwrap_or_else(|| self.error_context());
+        if let Some(kind) = self.tag_at() {
+            if let Some(ref mut queue) = self.events {
+                queue.push_tag(Tag { kind, byte_offset: context.byte_offset });
+            }
+        }
+        if let Some(ref mut observer) = self.error_observer {
+            observer(&error, context);
+        }
+        error
+    }
+
+    fn offset_of(&self, ptr: *const c_char) -> u64 {
+        self.buffer_offset + (ptr as usize - self.mad.stream.buffer as usize) as u64
+    }
+
+    // Metadata that may follow the audio data: ID3v1, APEv2 and appended ID3v2
+    fn tag_at(&self) -> Option<TagKind> {
+        let buffer_start = self.mad.stream.buffer as usize;
+        let start = self.mad.stream.this_frame as usize - buffer_start;
+        let end = self.mad.stream.buff_end as usize - buffer_start;
+        let bytes = &self.buffer[min(start, end)..end];
+        TagKind::detect(bytes)
+    }
+
+    fn check_error(&mut self) -> Option<LibmadError> {
+        let error = mem::take(&mut self.mad.stream.error);
+        LibmadError::try_from(error).ok()
+    }
 }
 
 impl<R> Iterator for Decoder<R> where R: io::Read {
@@ -320,9 +1519,365 @@ impl<R> Iterator for Decoder<R> where R: io::Read {
             Err(e) => Some(Err(e)),
         }
     }
+
+    /// Skip `n` results and return the next one
+    ///
+    /// The skipped frames are decoded as with `DecoderBuilder::headers_only`,
+    /// apart from the few the returned frame takes data from, so errors
+    /// only found by decoding a frame's audio data aren't counted. They
+    /// aren't passed to processors, frame or format observers either.
+    fn nth(&mut self, n: usize) -> Option<Result<Frame, SimplemadError>> {
+        if !self.options.headers_only {
+            self.options.headers_only = true;
+            self.skipping = true;
+            let mut skipped = 0;
+            while skipped < n {
+                self.warm_up_headers = n - skipped <= self.warm_up_len() as usize;
+                match self.get_frame() {
+                    Err(SimplemadError::EOF) => break,
+                    _ => skipped += 1,
+                }
+            }
+            self.options.headers_only = false;
+            self.skipping = false;
+            self.warm_up_headers = false;
+            // The last header read mustn't be decoded again
+            self.mad.frame.header.flags &= !MAD_FLAG_INCOMPLETE;
+            if skipped < n {
+                return None;
+            }
+        } else {
+            for _ in 0..n {
+                let _skipped = self.next()?;
+            }
+        }
+        self.next()
+    }
+
+    /// Estimate the number of remaining frames
+    ///
+    /// The estimate is taken from the Xing or Info header if the stream has
+    /// one, or else from `DecoderBuilder::input_len` and the size of the
+    /// first frame. No estimate is available before the first frame.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done {
+            return (0, Some(0));
+        }
+
+        let mut remaining = match self.total_frames {
+            Some(total) => total.saturating_sub(self.frame_index),
+            None => return (0, None),
+        };
+
+        if let Some(end_time) = self.options.end_time {
+            let frame_nanos = frame_duration(&self.mad.frame).as_nanos();
+            if frame_nanos > 0 {
+                let left = end_time.checked_sub(self.position).unwrap_or_default();
+                let frames = left.as_nanos().div_ceil(frame_nanos);
+                remaining = min(remaining, frames as u64);
+            }
+        }
+
+        (remaining as usize, None)
+    }
+}
+
+impl<R> Decoder<R> where R: io::Read + io::Seek {
+    /// Rewind to the first audio frame and start decoding again
+    ///
+    /// libmad's state is reinitialized, while the buffers and options are
+    /// kept, so the decoder can be reused for another pass over the stream.
+    pub fn reset(&mut self) -> Result<(), SimplemadError> {
+        let target = self.audio_offset.unwrap_or(0);
+        self.position = Duration::new(0, 0);
+        self.frame_index = 0;
+        self.events = None;
+        self.stats = DecoderStats::default();
+        self.bit_rate_sum = 0;
+        self.fade_origin = None;
+        self.jump_to(target)
+    }
+
+    // Continue reading the input at `offset` with libmad's state
+    // reinitialized, the position and counters are left to the caller
+    fn jump_to(&mut self, offset: u64) -> Result<(), SimplemadError> {
+        let read_len = self.read_len();
+        self.reader.seek(io::SeekFrom::Current(offset as i64 - read_len as i64))?;
+
+        self.mad = MadState::new();
+        self.buffer_offset = offset;
+        self.sync_offset = offset;
+        self.recent_frames.clear();
+        self.consecutive_errors = 0;
+        self.in_tag = false;
+        self.reservoir = Reservoir::default();
+        if let Some(ref mut filter) = self.de_emphasis {
+            filter.reset();
+        }
+        self.done = false;
+        self.start()
+    }
+
+    // The offset of the reader, as far as the input has been read
+    fn read_len(&self) -> u64 {
+        self.buffer_offset +
+        (self.mad.stream.buff_end as usize - self.mad.stream.buffer as usize) as u64
+    }
+}
+
+/// An iterator over the headers of a stream
+///
+/// Created by `Decoder::headers`.
+pub struct Headers<R>
+    where R: io::Read
+{
+    decoder: Decoder<R>,
+}
+
+impl<R> Headers<R> where R: io::Read {
+    /// Get the encoded bytes of the most recent header's frame, see
+    /// `Decoder::frame_bytes`
+    pub fn frame_bytes(&self) -> &[u8] {
+        self.decoder.frame_bytes()
+    }
+
+    /// Get the underlying decoder
+    pub fn into_decoder(self) -> Decoder<R> {
+        self.decoder
+    }
+}
+
+impl<R> Iterator for Headers<R> where R: io::Read {
+    type Item = Result<FrameHeader, SimplemadError>;
+    fn next(&mut self) -> Option<Result<FrameHeader, SimplemadError>> {
+        let decoder = &mut self.decoder;
+        match decoder.get_frame() {
+            Ok(frame) => {
+                let header = &decoder.mad.frame.header;
+                Some(Ok(FrameHeader {
+                    sample_rate: frame.sample_rate,
+                    bit_rate: frame.bit_rate,
+                    layer: frame.layer,
+                    mode: frame.mode,
+                    flags: header.flags,
+                    duration: frame.duration,
+                    position: frame.position,
+                    byte_offset: frame.byte_offset,
+                    emphasis: frame.emphasis,
+                    copyright: frame.copyright,
+                    original: frame.original,
+                    private: frame.private,
+                    padding: frame.padding,
+                    crc: frame.crc,
+                    main_data_begin: frame.main_data_begin,
+                    reservoir_complete: frame.reservoir_complete,
+                }))
+            }
+            Err(SimplemadError::EOF) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.decoder.size_hint()
+    }
+}
+
+impl<R> FusedIterator for Headers<R> where R: io::Read {}
+
+/// An iterator over the frames of a stream whose length is known
+///
+/// Created by `Decoder::exact_frames`. Recoverable errors are skipped;
+/// iteration stops early at the first other error, which is available from
+/// `error`.
+pub struct ExactFrames<R>
+    where R: io::Read
+{
+    decoder: Decoder<R>,
+    first: Option<Frame>,
+    remaining: usize,
+    error: Option<SimplemadError>,
+}
+
+impl<R> ExactFrames<R> where R: io::Read {
+    /// Get the error that ended iteration early, if any
+    pub fn error(&self) -> Option<&SimplemadError> {
+        self.error.as_ref()
+    }
+
+    /// Get the underlying decoder
+    pub fn into_decoder(self) -> Decoder<R> {
+        self.decoder
+    }
+}
+
+impl<R> Iterator for ExactFrames<R> where R: io::Read {
+    type Item = Frame;
+    fn next(&mut self) -> Option<Frame> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let frame = match self.first.take() {
+            Some(frame) => frame,
+            None => {
+                loop {
+                    match self.decoder.get_frame() {
+                        Ok(frame) => break frame,
+                        Err(ref e) if e.is_recoverable() => continue,
+                        Err(SimplemadError::EOF) => {
+                            self.remaining = 0;
+                            return None;
+                        }
+                        Err(e) => {
+                            self.error = Some(e);
+                            self.remaining = 0;
+                            return None;
+                        }
+                    }
+                }
+            }
+        };
+
+        self.remaining -= 1;
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<R> ExactSizeIterator for ExactFrames<R> where R: io::Read {}
+
+/// Once the decoder has returned `None`, either at the end of the stream or
+/// after a fatal error, it keeps returning `None`
+impl<R> FusedIterator for Decoder<R> where R: io::Read {}
+
+// libmad's decoding state, released when dropped
+struct MadState {
+    stream: MadStream,
+    // Created on first use, header scans never synthesize
+    synth: Option<Box<MadSynth>>,
+    // Boxed so that the invalid `layer` value written by `mad_frame_init`
+    // can't be picked up as an enum niche by `Result<Decoder<R>, _>`
+    frame: Box<MadFrame>,
+}
+
+impl MadState {
+    fn new() -> MadState {
+        let mut state = MadState {
+            stream: Default::default(),
+            synth: None,
+            frame: Box::default(),
+        };
+
+        unsafe {
+            mad_stream_init(&mut state.stream);
+            mad_frame_init(&mut state.frame);
+        }
+
+        state
+    }
+
+    fn synth_frame(&mut self) {
+        let synth = self.synth.get_or_insert_with(init_synth);
+        unsafe {
+            mad_synth_frame(synth, &mut self.frame);
+        }
+    }
+}
+
+fn init_synth() -> Box<MadSynth> {
+    let mut synth = Box::<MadSynth>::default();
+    unsafe {
+        mad_synth_init(&mut synth);
+    }
+    synth
+}
+
+// The parts of libmad's stream that decoding a header changes, to undo it
+struct StreamMark {
+    this_frame: *mut c_char,
+    next_frame: *mut c_char,
+    ptr: (*mut c_char, u16, u16),
+    skip_len: c_ulong,
+    sync: c_int,
+    free_rate: c_ulong,
+}
+
+impl StreamMark {
+    fn new(stream: &MadStream) -> StreamMark {
+        StreamMark {
+            this_frame: stream.this_frame,
+            next_frame: stream.next_frame,
+            ptr: (stream.ptr.byte, stream.ptr.cache, stream.ptr.left),
+            skip_len: stream.skip_len,
+            sync: stream.sync,
+            free_rate: stream.free_rate,
+        }
+    }
+
+    fn restore(self, stream: &mut MadStream) {
+        stream.this_frame = self.this_frame;
+        stream.next_frame = self.next_frame;
+        stream.ptr.byte = self.ptr.0;
+        stream.ptr.cache = self.ptr.1;
+        stream.ptr.left = self.ptr.2;
+        stream.skip_len = self.skip_len;
+        stream.sync = self.sync;
+        stream.free_rate = self.free_rate;
+        stream.error = MadError::None;
+    }
+}
+
+// The fields of libmad's frame header that decoding a header changes, to undo
+// it. The enum fields are left out: libmad can leave values in them that
+// their Rust types don't allow, so they must not be copied.
+struct HeaderMark {
+    mode_extension: c_int,
+    bit_rate: c_ulong,
+    sample_rate: c_uint,
+    crc_check: c_ushort,
+    crc_target: c_ushort,
+    flags: c_int,
+    private_bits: c_int,
+    duration: MadTimer,
+}
+
+impl HeaderMark {
+    fn new(header: &MadHeader) -> HeaderMark {
+        HeaderMark {
+            mode_extension: header.mode_extension,
+            bit_rate: header.bit_rate,
+            sample_rate: header.sample_rate,
+            crc_check: header.crc_check,
+            crc_target: header.crc_target,
+            flags: header.flags,
+            private_bits: header.private_bits,
+            duration: header.duration,
+        }
+    }
+
+    fn restore(self, header: &mut MadHeader) {
+        header.mode_extension = self.mode_extension;
+        header.bit_rate = self.bit_rate;
+        header.sample_rate = self.sample_rate;
+        header.crc_check = self.crc_check;
+        header.crc_target = self.crc_target;
+        header.flags = self.flags;
+        header.private_bits = self.private_bits;
+        header.duration = self.duration;
+    }
 }
 
-impl<R> Drop for Decoder<R> where R: io::Read {
+// The pointers in libmad's structs point into `Decoder::buffer` and into
+// memory libmad allocates for this state alone. Nothing else holds them and
+// libmad keeps no thread-local or global state, so the state can move between
+// threads. It isn't `Sync`, libmad's functions all take it mutably.
+unsafe impl Send for MadState {}
+
+impl Drop for MadState {
     fn drop(&mut self) {
         unsafe {
             mad_stream_finish(&mut self.stream);
@@ -335,19 +1890,261 @@ impl<R> Drop for Decoder<R> where R: io::Read {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 /// An error encountered during the decoding process
 pub enum SimplemadError {
     /// An `io::Error` generated by the `Reader`
     Read(io::Error),
-    /// A `MadError` generated by libmad
-    Mad(MadError),
+    /// An `io::Error` generated by the writer output goes to, as in `cut`
+    Write(io::Error),
+    /// An error generated by libmad
+    Mad {
+        /// The error reported by libmad
+        error: LibmadError,
+        /// Where the error occurred
+        context: ErrorContext,
+    },
+    /// A frame whose layer isn't accepted by `DecoderBuilder::layers`
+    RejectedLayer {
+        /// The layer of the rejected frame
+        found: Layer,
+        /// Where the frame starts
+        context: ErrorContext,
+    },
+    /// Decoding was abandoned after too many consecutive errors, see
+    /// `DecoderBuilder::max_consecutive_errors`
+    TooManyErrors {
+        /// The number of consecutive errors
+        count: usize,
+        /// Where the last error occurred
+        context: ErrorContext,
+    },
+    /// Decoding was abandoned after scanning too many bytes without finding
+    /// a frame, see `DecoderBuilder::max_resync_bytes`
+    SyncLimitExceeded {
+        /// The number of bytes scanned since the last frame
+        scanned: u64,
+        /// The configured limit
+        limit: u64,
+        /// Where the scan was abandoned
+        context: ErrorContext,
+    },
+    /// The sample rate, channel count or layer changed mid-stream, see
+    /// `DecoderBuilder::consistent_format`
+    FormatChanged {
+        /// The format before and after the change
+        change: FormatChange,
+        /// Where the frame with the new format starts
+        context: ErrorContext,
+    },
+    /// The input is in another audio format
+    InvalidFormat(DetectedFormat),
+    /// The `Reader` has no data available yet; call `get_frame` again
+    /// once it does
+    WouldBlock,
     /// The `Reader` has stopped producing data
     EOF,
 }
 
-impl From<MadError> for SimplemadError {
-    fn from(err: MadError) -> SimplemadError {
-        SimplemadError::Mad(err)
+impl SimplemadError {
+    /// Get the category of the error
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            SimplemadError::Read(_) |
+            SimplemadError::Write(_) => ErrorKind::Io,
+            SimplemadError::Mad { error: LibmadError::LostSync, .. } => ErrorKind::LostSync,
+            SimplemadError::Mad { error, .. } if error.is_recoverable() => ErrorKind::BadData,
+            SimplemadError::Mad { .. } => ErrorKind::Internal,
+            SimplemadError::RejectedLayer { .. } |
+            SimplemadError::FormatChanged { .. } => ErrorKind::UnsupportedFeature,
+            SimplemadError::TooManyErrors { .. } |
+            SimplemadError::SyncLimitExceeded { .. } => ErrorKind::Aborted,
+            SimplemadError::InvalidFormat(_) => ErrorKind::InvalidFormat,
+            SimplemadError::WouldBlock => ErrorKind::WouldBlock,
+            SimplemadError::EOF => ErrorKind::Eof,
+        }
+    }
+
+    /// Whether decoding can continue after this error
+    ///
+    /// `WouldBlock` isn't recoverable in this sense: nothing was skipped, and
+    /// decoding continues once the reader has data again.
+    pub fn is_recoverable(&self) -> bool {
+        match *self {
+            SimplemadError::Mad { error, .. } => error.is_recoverable(),
+            SimplemadError::RejectedLayer { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Get the position in the stream where the error occurred
+    ///
+    /// Only errors found in the stream carry a context; `Read`, `Write`,
+    /// `InvalidFormat`, `WouldBlock` and `EOF` return `None`.
+    pub fn context(&self) -> Option<ErrorContext> {
+        match *self {
+            SimplemadError::Mad { context, .. } |
+            SimplemadError::RejectedLayer { context, .. } |
+            SimplemadError::TooManyErrors { context, .. } |
+            SimplemadError::SyncLimitExceeded { context, .. } |
+            SimplemadError::FormatChanged { context, .. } => Some(context),
+            SimplemadError::Read(_) |
+            SimplemadError::Write(_) |
+            SimplemadError::InvalidFormat(_) |
+            SimplemadError::WouldBlock |
+            SimplemadError::EOF => None,
+        }
+    }
+}
+
+impl fmt::Display for SimplemadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SimplemadError::Read(ref e) => write!(f, "read error: {}", e),
+            SimplemadError::Write(ref e) => write!(f, "write error: {}", e),
+            SimplemadError::Mad { error, context } => {
+                write!(f, "{} at byte {}", error, context.byte_offset)
+            }
+            SimplemadError::RejectedLayer { found, context } => {
+                write!(f, "rejected {:?} frame at byte {}", found, context.byte_offset)
+            }
+            SimplemadError::TooManyErrors { count, context } => {
+                write!(f,
+                       "gave up after {} consecutive errors at byte {}",
+                       count,
+                       context.byte_offset)
+            }
+            SimplemadError::SyncLimitExceeded { scanned, limit, context } => {
+                write!(f,
+                       "gave up after scanning {} bytes (limit {}) without finding a frame \
+                        at byte {}",
+                       scanned,
+                       limit,
+                       context.byte_offset)
+            }
+            SimplemadError::FormatChanged { change, context } => {
+                let previous = change.previous.unwrap_or(change.current);
+                write!(f,
+                       "format changed from {} Hz, {} channels, {:?} to {} Hz, {} channels, \
+                        {:?} at byte {}",
+                       previous.sample_rate,
+                       previous.channels,
+                       previous.layer,
+                       change.current.sample_rate,
+                       change.current.channels,
+                       change.current.layer,
+                       context.byte_offset)
+            }
+            SimplemadError::InvalidFormat(format) => {
+                write!(f, "not MPEG audio, the input looks like {}", format)
+            }
+            SimplemadError::WouldBlock => f.write_str("no data available yet"),
+            SimplemadError::EOF => f.write_str("end of stream"),
+        }
+    }
+}
+
+impl error::Error for SimplemadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            SimplemadError::Read(ref e) |
+            SimplemadError::Write(ref e) => Some(e),
+            SimplemadError::Mad { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+// `io::Error` isn't comparable, so read and write errors are equal if their
+// kinds are
+impl PartialEq for SimplemadError {
+    fn eq(&self, other: &SimplemadError) -> bool {
+        use SimplemadError::*;
+
+        match (self, other) {
+            (Read(a), Read(b)) |
+            (Write(a), Write(b)) => a.kind() == b.kind(),
+            (Mad { error: a, context: ca }, Mad { error: b, context: cb }) => a == b && ca == cb,
+            (RejectedLayer { found: a, context: ca }, RejectedLayer { found: b, context: cb }) => {
+                a == b && ca == cb
+            }
+            (TooManyErrors { count: a, context: ca }, TooManyErrors { count: b, context: cb }) => {
+                a == b && ca == cb
+            }
+            (SyncLimitExceeded { scanned: a, limit: la, context: ca },
+             SyncLimitExceeded { scanned: b, limit: lb, context: cb }) => {
+                a == b && la == lb && ca == cb
+            }
+            (FormatChanged { change: a, context: ca },
+             FormatChanged { change: b, context: cb }) => {
+                a == b && ca == cb
+            }
+            (InvalidFormat(a), InvalidFormat(b)) => a == b,
+            (WouldBlock, WouldBlock) |
+            (EOF, EOF) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for SimplemadError {}
+
+/// Categories of `SimplemadError`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The `Reader` or a writer failed
+    Io,
+    /// No frame was found where one was expected
+    LostSync,
+    /// A frame was found but its contents are damaged
+    BadData,
+    /// A valid frame that the decoder was configured to reject
+    UnsupportedFeature,
+    /// Decoding was abandoned because a limit was reached
+    Aborted,
+    /// An error in libmad itself, such as running out of memory
+    Internal,
+    /// The input is in another audio format
+    InvalidFormat,
+    /// The `Reader` has no data available yet
+    WouldBlock,
+    /// The end of the stream was reached
+    Eof,
+}
+
+/// Counters of a decoder's progress, see `Decoder::stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecoderStats {
+    /// Number of frames decoded
+    pub frames_decoded: u64,
+    /// Number of recoverable errors encountered
+    pub recoverable_errors: u64,
+    /// Number of input bytes processed
+    pub bytes_consumed: u64,
+    /// Number of bytes skipped while searching for frames
+    pub bytes_skipped: u64,
+    /// Mean bit rate of the decoded frames
+    pub average_bit_rate: u32,
+}
+
+/// Where in the stream an error was encountered
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Byte offset in the input where a frame was expected
+    pub byte_offset: u64,
+    /// The position of the decoder when the error occurred
+    pub position: Duration,
+    /// The number of frames decoded before the error
+    pub frame_index: u64,
+}
+
+impl From<LibmadError> for SimplemadError {
+    fn from(err: LibmadError) -> SimplemadError {
+        SimplemadError::Mad {
+            error: err,
+            context: Default::default(),
+        }
     }
 }
 
@@ -357,13 +2154,51 @@ impl From<io::Error> for SimplemadError {
     }
 }
 
+// The most bytes a Layer III frame can use from the frames before it
+const MAX_MAIN_DATA_BEGIN: u32 = 511;
+
+// The most recycled frames whose sample buffers are kept for reuse
+const MAX_POOLED_FRAMES: usize = 4;
+
+// Copy `len` bits starting at `ptr` into bytes, most significant bit first
+unsafe fn read_bits(ptr: &MadBitPtr, len: c_uint) -> Vec<u8> {
+    if ptr.byte.is_null() || len == 0 {
+        return Vec::new();
+    }
+
+    let len = len as usize;
+    // `left` is the number of unread bits in the first byte, which may be 0
+    let skip = 8 - min(ptr.left as usize, 8);
+    let shift = skip % 8;
+    let first = (ptr.byte as *const u8).add(skip / 8);
+    let input = slice::from_raw_parts(first, (shift + len).div_ceil(8));
+    let mut bytes: Vec<u8> = (0..len.div_ceil(8))
+                                 .map(|index| {
+                                     let high = input[index] << shift;
+                                     let low = match (shift, input.get(index + 1)) {
+                                         (0, _) | (_, None) => 0,
+                                         (_, Some(&next)) => next >> (8 - shift),
+                                     };
+                                     high | low
+                                 })
+                                 .collect();
+    if !len.is_multiple_of(8) {
+        if let Some(last) = bytes.last_mut() {
+            *last &= 0xff << (8 - len % 8);
+        }
+    }
+    bytes
+}
+
+// `c_ulong` is only 32 bits wide on some targets
+#[allow(clippy::unnecessary_cast)]
 fn frame_duration(frame: &MadFrame) -> Duration {
     let duration = &frame.header.duration;
     Duration::new(duration.seconds as u64,
                   ((duration.fraction as u64) * 1_000_000_000 / 352800000) as u32)
 }
 
-#[derive(Clone, Copy, Default, Debug)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(C)]
 /// libmad's native fixed-point sample format
 ///
@@ -380,269 +2215,1681 @@ impl MadFixed32 {
         MadFixed32 { value: v }
     }
 
-    /// Get the raw fixed-point representation
-    pub fn to_raw(&self) -> i32 {
-        self.value
+    /// Get the raw fixed-point representation
+    pub fn to_raw(&self) -> i32 {
+        self.value
+    }
+
+    /// Convert to i16
+    pub fn to_i16(&self) -> i16 {
+        let frac_bits = 28;
+        let unity_value = 0x1000_0000;
+        let rounded_value = self.value + (1 << (frac_bits - 16));
+        let clipped_value = max(-unity_value, min(rounded_value, unity_value - 1));
+        let quantized_value = clipped_value >> (frac_bits + 1 - 16);
+
+        quantized_value as i16
+    }
+
+    /// Convert to i32
+    pub fn to_i32(&self) -> i32 {
+        // clip only
+        if self.value > i32::MAX / 8 {
+            i32::MAX
+        } else if self.value < i32::MIN / 8 {
+            i32::MIN
+        } else {
+            self.value * 8
+        }
+    }
+
+    /// Convert to f32
+    pub fn to_f32(&self) -> f32 {
+        // The big number is 2^28, as 28 is the fractional bit count)
+        ((self.value as f32) / 268435456.0).clamp(-1.0, 1.0)
+    }
+
+    /// Convert to f64
+    pub fn to_f64(&self) -> f64 {
+        // The big number is 2^28, as 28 is the fractional bit count)
+        ((self.value as f64) / 268435456.0).clamp(-1.0, 1.0)
+    }
+
+    /// Add, clamping the result to the range of the format
+    pub fn saturating_add(self, other: MadFixed32) -> MadFixed32 {
+        MadFixed32 { value: self.value.saturating_add(other.value) }
+    }
+
+    /// Subtract, clamping the result to the range of the format
+    pub fn saturating_sub(self, other: MadFixed32) -> MadFixed32 {
+        MadFixed32 { value: self.value.saturating_sub(other.value) }
+    }
+
+    /// Multiply, clamping the result to the range of the format
+    pub fn saturating_mul(self, other: MadFixed32) -> MadFixed32 {
+        let product = fixed_mul(self.value, other.value);
+        MadFixed32 { value: product.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32 }
+    }
+
+    /// Negate, clamping the result to the range of the format
+    pub fn saturating_neg(self) -> MadFixed32 {
+        MadFixed32 { value: self.value.saturating_neg() }
+    }
+
+    /// Convert a slice of 16-bit samples
+    pub fn from_i16_slice(samples: &[i16]) -> Vec<MadFixed32> {
+        samples.iter().map(|&sample| MadFixed32::from(sample)).collect()
+    }
+
+    /// Convert a slice of `f32` samples
+    pub fn from_f32_slice(samples: &[f32]) -> Vec<MadFixed32> {
+        samples.iter().map(|&sample| MadFixed32::from(sample)).collect()
+    }
+
+    /// Convert a slice of samples to 16 bits, see `to_i16`
+    pub fn to_i16_vec(samples: &[MadFixed32]) -> Vec<i16> {
+        samples.iter().map(MadFixed32::to_i16).collect()
+    }
+
+    /// Convert a slice of samples to `f32`, see `to_f32`
+    pub fn to_f32_vec(samples: &[MadFixed32]) -> Vec<f32> {
+        samples.iter().map(MadFixed32::to_f32).collect()
+    }
+}
+
+// Multiply two fixed-point values, rounding to the nearest like libmad's
+// 64-bit `mad_f_mul`
+fn fixed_mul(a: i32, b: i32) -> i64 {
+    (i64::from(a) * i64::from(b) + (1 << 27)) >> 28
+}
+
+// Arithmetic overflows like that of `i32`: it panics in debug builds and
+// wraps otherwise, the saturating methods clamp instead
+
+impl ops::Add for MadFixed32 {
+    type Output = MadFixed32;
+    fn add(self, other: MadFixed32) -> MadFixed32 {
+        MadFixed32 { value: self.value + other.value }
+    }
+}
+
+impl ops::Sub for MadFixed32 {
+    type Output = MadFixed32;
+    fn sub(self, other: MadFixed32) -> MadFixed32 {
+        MadFixed32 { value: self.value - other.value }
+    }
+}
+
+impl ops::Mul for MadFixed32 {
+    type Output = MadFixed32;
+    fn mul(self, other: MadFixed32) -> MadFixed32 {
+        let product = fixed_mul(self.value, other.value);
+        if cfg!(debug_assertions) {
+            let value = i32::try_from(product).expect("attempt to multiply with overflow");
+            MadFixed32 { value }
+        } else {
+            MadFixed32 { value: product as i32 }
+        }
+    }
+}
+
+impl ops::Neg for MadFixed32 {
+    type Output = MadFixed32;
+    fn neg(self) -> MadFixed32 {
+        MadFixed32 { value: -self.value }
+    }
+}
+
+impl ops::AddAssign for MadFixed32 {
+    fn add_assign(&mut self, other: MadFixed32) {
+        *self = *self + other;
+    }
+}
+
+impl ops::SubAssign for MadFixed32 {
+    fn sub_assign(&mut self, other: MadFixed32) {
+        *self = *self - other;
+    }
+}
+
+impl ops::MulAssign for MadFixed32 {
+    fn mul_assign(&mut self, other: MadFixed32) {
+        *self = *self * other;
+    }
+}
+
+impl From<i16> for MadFixed32 {
+    fn from(v: i16) -> MadFixed32 {
+        MadFixed32 { value: v as i32 * 8192 }
+    }
+}
+
+impl From<i32> for MadFixed32 {
+    fn from(v: i32) -> MadFixed32 {
+        MadFixed32 { value: v / 8 }
+    }
+}
+
+impl From<f32> for MadFixed32 {
+    fn from(v: f32) -> MadFixed32 {
+        MadFixed32 {
+            // The big number is 2^28, as
+            // 28 is the fractional bit count)
+            value: (v * 268435456.0) as i32,
+        }
+    }
+}
+
+impl From<f64> for MadFixed32 {
+    fn from(v: f64) -> MadFixed32 {
+        MadFixed32 {
+            // The big number is 2^28, as
+            // 28 is the fractional bit count)
+            value: (v * 268435456.0) as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+    use std::sync::{Arc, Mutex};
+    use std::fs::{self, File};
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn test_find_duration() {
+        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
+        let file = File::open(path).unwrap();
+        let bufreader = BufReader::new(file);
+        let decoder = Decoder::decode_headers(bufreader).unwrap();
+
+        let duration = decoder.filter_map(|r| {
+                                  match r {
+                                      Ok(f) => Some(f.duration),
+                                      Err(_) => None,
+                                  }
+                              })
+                              .fold(Duration::new(0, 0), |acc, dtn| acc + dtn);
+
+        assert_eq!(duration, Duration::new(5, 41632464));
+    }
+
+    #[test]
+    fn test_decode_headers() {
+        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
+        let file = File::open(path).unwrap();
+        let bufreader = BufReader::new(file);
+        let decoder = Decoder::decode_headers(bufreader).unwrap();
+        let mut frame_count = 0;
+        let mut error_count = 0;
+
+        for item in decoder {
+            match item {
+                Err(_) => {
+                    if frame_count > 0 {
+                        error_count += 1;
+                    }
+                }
+                Ok(f) => {
+                    frame_count += 1;
+                    assert_eq!(f.mode, Mode::Stereo);
+                    assert_eq!(f.layer, Layer::III);
+                    assert_eq!(f.bit_rate, 128000);
+                    assert_eq!(f.sample_rate, 44100);
+                    assert_eq!(f.samples.len(), 0);
+                }
+            }
+        }
+        assert_eq!(error_count, 0);
+        assert_eq!(frame_count, 193);
+    }
+
+    #[test]
+    fn test_bufreader() {
+        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
+        let file = File::open(path).unwrap();
+        let bufreader = BufReader::new(file);
+        let decoder = Decoder::decode(bufreader).unwrap();
+        let mut frame_count = 0;
+        let mut error_count = 0;
+
+        for item in decoder {
+            match item {
+                Err(_) => {
+                    if frame_count > 0 {
+                        error_count += 1;
+                    }
+                }
+                Ok(f) => {
+                    frame_count += 1;
+                    assert_eq!(f.sample_rate, 44100);
+                    assert_eq!(f.mode, Mode::Stereo);
+                    assert_eq!(f.layer, Layer::III);
+                    assert_eq!(f.bit_rate, 128000);
+                    assert_eq!(f.samples.len(), 2);
+                    assert_eq!(f.samples[0].len(), 1152);
+                }
+            }
+        }
+        assert_eq!(error_count, 0);
+        assert_eq!(frame_count, 193);
+    }
+
+    #[test]
+    fn test_decode_interval() {
+        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
+        let file = File::open(path).unwrap();
+        let decoder = Decoder::decode_interval(file,
+                                               Duration::from_secs(3),
+                                               Duration::from_secs(4)).unwrap();
+        let mut frame_count = 0;
+        let mut error_count = 0;
+
+        for item in decoder {
+            match item {
+                Err(_) => {
+                    if frame_count > 0 {
+                        error_count += 1;
+                    }
+                }
+                Ok(f) => {
+                    frame_count += 1;
+                    assert_eq!(f.sample_rate, 44100);
+                    assert_eq!(f.samples.len(), 2);
+                    assert_eq!(f.samples[0].len(), 1152);
+                }
+            }
+        }
+        assert_eq!(error_count, 0);
+        assert_eq!(frame_count, 39);
+    }
+
+    #[test]
+    fn test_decode_headers_interval() {
+        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
+        let decoder = Decoder::decode_headers_interval(File::open(path).unwrap(),
+                                                       Duration::from_secs(3),
+                                                       Duration::from_secs(4)).unwrap();
+        let frames: Vec<Frame> = decoder.filter_map(|r| r.ok()).collect();
+        assert_eq!(frames.len(), 39);
+        assert!(frames.iter().all(|f| f.samples.is_empty()));
+        assert!(frames[0].position >= Duration::from_secs(3));
+        assert!(frames[38].position < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_interval_beyond_eof() {
+        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
+        let file = File::open(path).unwrap();
+        let mut decoder = Decoder::decode_interval(file,
+                                                   Duration::from_secs(60),
+                                                   Duration::from_secs(65)).unwrap();
+
+        assert!(decoder.next().is_none());
+    }
+
+    #[test]
+    fn test_decode_empty_interval() {
+        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
+        let file = File::open(path).unwrap();
+        let decoder = Decoder::decode_interval(file,
+                                               Duration::from_secs(2),
+                                               Duration::from_secs(2)).unwrap();
+        let mut frame_count = 0;
+        let mut error_count = 0;
+
+        for item in decoder {
+            match item {
+                Err(_) => {
+                    if frame_count > 0 {
+                        error_count += 1;
+                    }
+                }
+                Ok(f) => {
+                    frame_count += 1;
+                    assert_eq!(f.sample_rate, 44100);
+                    assert_eq!(f.samples.len(), 2);
+                    assert_eq!(f.samples[0].len(), 1152);
+                }
+            }
+        }
+        assert_eq!(error_count, 0);
+        assert_eq!(frame_count, 0);
+    }
+
+    #[test]
+    fn test_decode_overlong_interval() {
+        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
+        let file = File::open(path).unwrap();
+        let decoder = Decoder::decode_interval(file,
+                                               Duration::from_secs(3),
+                                               Duration::from_secs(45)).unwrap();
+        let mut frame_count = 0;
+        let mut error_count = 0;
+
+        for item in decoder {
+            match item {
+                Err(_) => {
+                    if frame_count > 0 {
+                        error_count += 1;
+                    }
+                }
+                Ok(f) => {
+                    frame_count += 1;
+                    assert_eq!(f.sample_rate, 44100);
+                    assert_eq!(f.samples.len(), 2);
+                    assert_eq!(f.samples[0].len(), 1152);
+                }
+            }
+        }
+        assert_eq!(error_count, 0);
+        assert_eq!(frame_count, 78);
+    }
+
+    // Pseudo-random bytes that aren't MPEG audio
+    fn junk(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491u32;
+        (0..len).map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect()
+    }
+
+    fn corrupted_sample() -> Vec<u8> {
+        let mut data = Vec::new();
+        File::open("sample_mp3s/constant_stereo_128.mp3").unwrap()
+                                                          .read_to_end(&mut data)
+                                                          .unwrap();
+        let junk: Vec<u8> = (0..2000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let mut corrupted = data[..40_000].to_vec();
+        corrupted.extend(junk);
+        corrupted.extend(&data[40_000..]);
+        corrupted
+    }
+
+    #[test]
+    fn test_builder_interval() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let decoder = Decoder::builder(file)
+                          .interval(Duration::from_secs(3), Duration::from_secs(4))
+                          .build()
+                          .unwrap();
+        let frames: Vec<Frame> = decoder.filter_map(|r| r.ok()).collect();
+        assert_eq!(frames.len(), 39);
+        assert!(frames.iter().all(|f| f.samples.len() == 2));
+    }
+
+    #[test]
+    fn test_builder_small_buffer() {
+        let file = File::open("sample_mp3s/constant_stereo_320.mp3").unwrap();
+        let decoder = Decoder::builder(file).buffer_size(1).build().unwrap();
+        let frame_count = decoder.filter(|r| r.is_ok()).count();
+        assert_eq!(frame_count, 193);
+    }
+
+    #[test]
+    fn test_error_policy() {
+        let decoder = Decoder::decode(Cursor::new(corrupted_sample())).unwrap();
+        let error_count = decoder.filter(|r| r.is_err()).count();
+        assert!(error_count > 0);
+
+        let decoder = Decoder::builder(Cursor::new(corrupted_sample()))
+                          .error_policy(ErrorPolicy::SkipRecoverable)
+                          .build()
+                          .unwrap();
+        let mut frame_count = 0;
+        for item in decoder {
+            assert!(item.is_ok());
+            frame_count += 1;
+        }
+        assert!(frame_count > 180);
+    }
+
+    // Set the protection bit on every frame of a CBR MPEG-1 Layer III file,
+    // so that libmad checks a CRC word that isn't there
+    fn crc_damaged_sample() -> Vec<u8> {
+        let mut data = Vec::new();
+        File::open("sample_mp3s/constant_stereo_128.mp3").unwrap()
+                                                          .read_to_end(&mut data)
+                                                          .unwrap();
+        let mut offset = 0;
+        while offset + 4 < data.len() && data[offset] == 0xff {
+            data[offset + 1] &= 0xfe;
+            let padding = ((data[offset + 2] >> 1) & 1) as usize;
+            offset += 144 * 128_000 / 44_100 + padding;
+        }
+        data
+    }
+
+    #[test]
+    fn test_ignore_crc() {
+        let is_bad_crc = |r: &Result<Frame, SimplemadError>| {
+            matches!(*r, Err(SimplemadError::Mad { error: LibmadError::BadCRC, .. }))
+        };
+
+        let decoder = Decoder::decode(Cursor::new(crc_damaged_sample())).unwrap();
+        assert!(decoder.filter(&is_bad_crc).count() > 100);
+
+        let decoder = Decoder::builder(Cursor::new(crc_damaged_sample()))
+                          .ignore_crc(true)
+                          .build()
+                          .unwrap();
+        assert_eq!(decoder.filter(&is_bad_crc).count(), 0);
+
+        let decoder = Decoder::builder(Cursor::new(crc_damaged_sample()))
+                          .ignore_crc(true)
+                          .build()
+                          .unwrap();
+        // Most frames fail to decode with the side information misplaced
+        let frames: Vec<Frame> = decoder.filter_map(|r| r.ok()).collect();
+        assert!(!frames.is_empty());
+        assert!(frames.iter().all(|f| f.crc == CrcStatus::Invalid));
+
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let decoder = Decoder::decode(file).unwrap();
+        assert!(decoder.filter_map(|r| r.ok()).all(|f| f.crc == CrcStatus::Unprotected));
+
+        let decoder = Decoder::decode(Cursor::new(crc_damaged_sample())).unwrap();
+        assert!(decoder.headers()
+                       .filter_map(|r| r.ok())
+                       .all(|h| h.crc == CrcStatus::Unchecked));
+    }
+
+    #[test]
+    fn test_half_sample_rate() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let decoder = Decoder::builder(file).half_sample_rate(true).build().unwrap();
+        let mut frame_count = 0;
+        let mut duration = Duration::new(0, 0);
+
+        for item in decoder {
+            let f = item.unwrap();
+            frame_count += 1;
+            duration += f.duration;
+            assert_eq!(f.sample_rate, 22050);
+            assert_eq!(f.samples.len(), 2);
+            assert_eq!(f.samples[0].len(), 576);
+        }
+        assert_eq!(frame_count, 193);
+        assert_eq!(duration, Duration::new(5, 41632464));
+    }
+
+    #[test]
+    fn test_raw_mad_options() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let frame = Decoder::builder(file)
+                        .raw_mad_options(MAD_OPTION_HALFSAMPLERATE)
+                        .build()
+                        .unwrap()
+                        .find_map(|r| r.ok())
+                        .unwrap();
+        assert_eq!(frame.sample_rate, 22050);
+
+        let decode = |bits| {
+            let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+            Decoder::builder(file).raw_mad_options(bits).build().unwrap().find_map(|r| r.ok())
+        };
+        let stereo = decode(0).unwrap();
+        let left = decode(MAD_OPTION_LEFTCHANNEL).unwrap();
+        let right = decode(MAD_OPTION_RIGHTCHANNEL).unwrap();
+        assert_eq!(left.samples, stereo.samples[..1]);
+        assert_eq!(right.samples, stereo.samples[1..]);
+
+        let decoder = Decoder::builder(Cursor::new(crc_damaged_sample()))
+                          .raw_mad_options(MAD_OPTION_IGNORECRC)
+                          .half_sample_rate(true)
+                          .build()
+                          .unwrap();
+        let results: Vec<_> = decoder.collect();
+        assert!(!results.iter().any(|r| {
+            matches!(*r, Err(SimplemadError::Mad { error: LibmadError::BadCRC, .. }))
+        }));
+        assert!(results.iter().filter_map(|r| r.as_ref().ok()).all(|f| f.sample_rate == 22050));
+    }
+
+    #[test]
+    fn test_layer_filter() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let decoder = Decoder::builder(file)
+                          .layers(&[Layer::I, Layer::II])
+                          .build()
+                          .unwrap();
+        let mut rejected_count = 0;
+
+        for item in decoder {
+            match item {
+                Err(SimplemadError::RejectedLayer { found: Layer::III, .. }) => rejected_count += 1,
+                _ => panic!("unexpected result {:?}", item),
+            }
+        }
+        assert_eq!(rejected_count, 193);
+
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let decoder = Decoder::builder(file).layers(&[Layer::III]).build().unwrap();
+        assert_eq!(decoder.filter(|r| r.is_ok()).count(), 193);
+    }
+
+    #[test]
+    fn test_max_consecutive_errors() {
+        let decoder = Decoder::builder(Cursor::new(junk(200_000)))
+                          .error_policy(ErrorPolicy::SkipRecoverable)
+                          .max_consecutive_errors(10)
+                          .build()
+                          .unwrap();
+        let results: Vec<_> = decoder.collect();
+
+        match results.last() {
+            Some(&Err(SimplemadError::TooManyErrors { count: 10, .. })) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let decoder = Decoder::builder(Cursor::new(corrupted_sample()))
+                          .strictness(Strictness::Strict)
+                          .build()
+                          .unwrap();
+        let results: Vec<_> = decoder.collect();
+        assert!(results.len() < 100);
+        assert!(results.last().unwrap().is_err());
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+
+        // The ID3v1 tag at the end of this file isn't an error
+        let file = File::open("sample_mp3s/rodiomusic.mp3").unwrap();
+        let decoder = Decoder::builder(file).strictness(Strictness::Strict).build().unwrap();
+        assert_eq!(decoder.filter(|r| r.is_ok()).count(), 1236);
+    }
+
+    #[test]
+    fn test_max_resync_bytes() {
+        let mut data = junk(100_000);
+        let mut file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        file.read_to_end(&mut data).unwrap();
+        let decoder = Decoder::builder(Cursor::new(data))
+                          .max_resync_bytes(10_000)
+                          .build()
+                          .unwrap();
+        let results: Vec<_> = decoder.collect();
+        assert!(results.iter().all(|r| r.is_err()));
+        match results.last() {
+            Some(&Err(SimplemadError::SyncLimitExceeded { scanned, .. })) => assert!(scanned > 10_000),
+            _ => panic!("expected the sync limit to be exceeded"),
+        }
+
+        // Short runs of garbage are skipped as usual
+        let decoder = Decoder::builder(Cursor::new(corrupted_sample()))
+                          .max_resync_bytes(10_000)
+                          .build()
+                          .unwrap();
+        let frame_count = decoder.filter(|r| r.is_ok()).count();
+        assert!(frame_count > 180);
+    }
+
+    #[test]
+    fn test_error_observer() {
+        let contexts = Arc::new(Mutex::new(Vec::new()));
+        let observed = contexts.clone();
+        let mut decoder = Decoder::builder(Cursor::new(corrupted_sample()))
+                          .error_policy(ErrorPolicy::SkipRecoverable)
+                          .on_error(move |_, context| observed.lock().unwrap().push(context))
+                          .build()
+                          .unwrap();
+        assert!(decoder.all(|r| r.is_ok()));
+
+        // Skipped errors are still observed
+        let contexts = contexts.lock().unwrap();
+        assert!(!contexts.is_empty());
+        for context in contexts.iter() {
+            assert!(context.byte_offset >= 40_000);
+            assert!(context.frame_index > 0);
+        }
+    }
+
+    #[test]
+    fn test_frame_observer() {
+        let durations = Arc::new(Mutex::new(Vec::new()));
+        let observed = durations.clone();
+        let decoder = Decoder::builder(File::open("sample_mp3s/constant_stereo_128.mp3").unwrap())
+                          .on_frame(move |frame| observed.lock().unwrap().push(frame.duration))
+                          .build()
+                          .unwrap();
+        let samples = decoder.samples::<i16>().count();
+
+        // Frames are observed through adapters as well
+        let durations = durations.lock().unwrap();
+        assert_eq!(durations.len(), samples / 2 / 1152);
+        assert!(durations.iter().all(|&d| d > Duration::from_millis(26)));
+    }
+
+    #[test]
+    fn test_error_kind() {
+        let decoder = Decoder::decode(Cursor::new(corrupted_sample())).unwrap();
+        let errors: Vec<_> = decoder.filter_map(|r| r.err()).collect();
+        assert!(errors.iter().any(|e| e.kind() == ErrorKind::LostSync));
+        assert!(errors.iter().all(|e| e.is_recoverable()));
+        assert_eq!(SimplemadError::EOF.kind(), ErrorKind::Eof);
+        assert!(!SimplemadError::EOF.is_recoverable());
+    }
+
+    #[test]
+    fn test_error_context() {
+        let decoder = Decoder::decode(Cursor::new(corrupted_sample())).unwrap();
+        let error = decoder.filter_map(|r| r.err()).next().unwrap();
+        let context = error.context().unwrap();
+        assert!(context.byte_offset >= 40_000 && context.byte_offset < 42_000);
+        assert_eq!(context.frame_index, 96);
+        assert!(context.position > Duration::from_secs(2));
+        assert_eq!(SimplemadError::EOF.context(), None);
+    }
+
+    #[test]
+    fn test_skipped_bytes() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let decoder = Decoder::decode(file).unwrap();
+        assert!(decoder.filter_map(|r| r.ok()).skip(1).all(|f| f.skipped_bytes == 0));
+
+        let decoder = Decoder::decode(Cursor::new(corrupted_sample())).unwrap();
+        let skipped: Vec<u64> = decoder.filter_map(|r| r.ok())
+                                       .map(|f| f.skipped_bytes)
+                                       .filter(|&n| n > 0)
+                                       .collect();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0] >= 2_000);
+    }
+
+    #[test]
+    fn test_gap() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let decoder = Decoder::decode(file).unwrap();
+        assert!(decoder.filter_map(|r| r.ok()).all(|f| f.gap.is_none()));
+
+        // Three frames replaced with 1000 zero bytes, and the two frames after
+        // them failing to decode without their bit reservoir. Both count
+        // towards the gap.
+        let bytes = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let offsets: Vec<usize> = Decoder::decode(&bytes[..])
+                                      .unwrap()
+                                      .headers()
+                                      .filter_map(|r| r.ok())
+                                      .map(|header| header.byte_offset as usize)
+                                      .collect();
+        let mut damaged = bytes[..offsets[60]].to_vec();
+        damaged.extend_from_slice(&[0; 1000]);
+        damaged.extend_from_slice(&bytes[offsets[63]..]);
+
+        let frames: Vec<Frame> = Decoder::decode(&damaged[..])
+                                     .unwrap()
+                                     .filter_map(|r| r.ok())
+                                     .collect();
+        let gaps: Vec<usize> = (0..frames.len()).filter(|&i| frames[i].gap.is_some()).collect();
+        assert_eq!(gaps, [60]);
+        let skipped = 1000 + offsets[65] - offsets[63];
+        assert_eq!(frames[60].skipped_bytes, skipped as u64);
+        let frame_len = (offsets[66] - offsets[65]) as u128;
+        let nanos = frames[0].duration.as_nanos() * skipped as u128 / frame_len;
+        assert_eq!(frames[60].gap, Some(Duration::from_nanos(nanos as u64)));
+        assert_eq!(frames[60].position, frames[0].duration * 60);
+    }
+
+    #[test]
+    fn test_concealment() {
+        // Dropping frames behind a gap leaves the two frames after it
+        // without their bit reservoir
+        let bytes = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let offsets: Vec<usize> = Decoder::decode(&bytes[..])
+                                      .unwrap()
+                                      .headers()
+                                      .filter_map(|r| r.ok())
+                                      .map(|header| header.byte_offset as usize)
+                                      .collect();
+        let mut damaged = bytes[..offsets[60]].to_vec();
+        damaged.extend_from_slice(&[0; 1000]);
+        damaged.extend_from_slice(&bytes[offsets[63]..]);
+
+        let decode = |concealment| {
+            Decoder::builder(&damaged[..])
+                .error_policy(ErrorPolicy::SkipRecoverable)
+                .concealment(concealment)
+                .build()
+                .unwrap()
+                .collect::<Result<Vec<Frame>, _>>()
+                .unwrap()
+        };
+
+        let frames = decode(Concealment::Silence);
+        assert_eq!(frames.len(), offsets.len() - 3);
+        let concealed: Vec<usize> = (0..frames.len()).filter(|&i| frames[i].concealed).collect();
+        assert_eq!(concealed, [60, 61]);
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(frame.position, frames[0].duration * i as u32);
+            assert_eq!(frame.samples.len(), 2);
+            assert_eq!(frame.samples[0].len(), 1152);
+        }
+        assert!(frames[60].samples.iter().flatten().all(|&sample| sample == MadFixed32::new(0)));
+        assert_eq!(frames[60].byte_offset, offsets[60] as u64 + 1000);
+
+        let frames = decode(Concealment::Repeat);
+        assert!(frames[60].concealed && frames[61].concealed);
+        assert_eq!(frames[60].samples, frames[59].samples);
+        assert_eq!(frames[61].samples, frames[59].samples);
+        assert!(!frames[62].concealed);
+
+        // Without concealment the damaged frames are errors
+        let errors = Decoder::decode(&damaged[..]).unwrap().filter(|r| r.is_err()).count();
+        assert_eq!(errors, 3);
+
+        // Concealed frames follow options given as raw libmad bits
+        let frames = Decoder::builder(&damaged[..])
+                         .error_policy(ErrorPolicy::SkipRecoverable)
+                         .concealment(Concealment::Silence)
+                         .raw_mad_options(MAD_OPTION_HALFSAMPLERATE | MAD_OPTION_SINGLECHANNEL)
+                         .build()
+                         .unwrap()
+                         .collect::<Result<Vec<Frame>, _>>()
+                         .unwrap();
+        assert!(frames[60].concealed);
+        for frame in &frames {
+            assert_eq!(frame.sample_rate, 22050);
+            assert_eq!(frame.samples.len(), 1);
+            assert_eq!(frame.samples[0].len(), 576);
+        }
+    }
+
+    #[test]
+    fn test_error_eq() {
+        let context = ErrorContext::default();
+        assert_eq!(SimplemadError::from(LibmadError::LostSync),
+                   SimplemadError::Mad { error: LibmadError::LostSync, context });
+        assert_ne!(SimplemadError::Mad { error: LibmadError::LostSync, context },
+                   SimplemadError::Mad { error: LibmadError::BadCRC, context });
+        assert_eq!(SimplemadError::from(io::Error::new(io::ErrorKind::UnexpectedEof, "a")),
+                   SimplemadError::from(io::Error::new(io::ErrorKind::UnexpectedEof, "b")));
+        assert_ne!(SimplemadError::EOF, SimplemadError::TooManyErrors { count: 1, context });
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(LibmadError::LostSync.to_string(), "lost synchronization");
+        let context = ErrorContext { byte_offset: 417, ..Default::default() };
+        assert_eq!(SimplemadError::Mad { error: LibmadError::BadCRC, context }.to_string(),
+                   "CRC check failed at byte 417");
+    }
+
+    // Mixes short reads with `WouldBlock`, like a non-blocking socket
+    struct NonBlocking {
+        inner: Cursor<Vec<u8>>,
+        reads: usize,
+    }
+
+    impl Read for NonBlocking {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            if !self.reads.is_multiple_of(3) {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "no data"));
+            }
+            let len = min(buf.len(), 1_000);
+            self.inner.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_would_block() {
+        let mut data = Vec::new();
+        let mut file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        file.read_to_end(&mut data).unwrap();
+        let reader = NonBlocking {
+            inner: Cursor::new(data),
+            reads: 0,
+        };
+
+        let mut decoder = Decoder::decode(reader).unwrap();
+        let mut frame_count = 0;
+        let mut block_count = 0;
+        loop {
+            match decoder.get_frame() {
+                Ok(_) => frame_count += 1,
+                Err(SimplemadError::WouldBlock) => block_count += 1,
+                Err(SimplemadError::EOF) => break,
+                Err(e) => panic!("unexpected error {}", e),
+            }
+        }
+
+        assert!(block_count > 0);
+        assert_eq!(frame_count, 193);
+    }
+
+    struct Blocked;
+
+    impl Read for Blocked {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::WouldBlock, "no data"))
+        }
+    }
+
+    #[test]
+    fn test_would_block_adapters() {
+        // Adapters that skip recoverable errors hand `WouldBlock` back
+        // instead of retrying
+        assert!(!SimplemadError::WouldBlock.is_recoverable());
+        let waveform = Decoder::decode(Blocked).unwrap().waveform(10);
+        assert_eq!(waveform.unwrap_err(), SimplemadError::WouldBlock);
+        let extent = silence_extent(Blocked, MadFixed32::new(0), Duration::from_secs(1));
+        assert_eq!(extent.unwrap_err(), SimplemadError::WouldBlock);
+    }
+
+    // Returns at most a few bytes from each read, like a slow network reader
+    struct Trickle {
+        inner: Cursor<Vec<u8>>,
+        reads: usize,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            let len = min(buf.len(), self.reads % 7 + 1);
+            self.inner.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_small_reads() {
+        let data = fs::read("sample_mp3s/variable_stereo.mp3").unwrap();
+        let expected: Vec<Frame> = Decoder::decode(Cursor::new(data.clone()))
+                                       .unwrap()
+                                       .filter_map(|r| r.ok())
+                                       .collect();
+
+        for &buffer_size in &[MIN_BUFFER_SIZE, 32_768] {
+            let reader = Trickle { inner: Cursor::new(data.clone()), reads: 0 };
+            let frames: Vec<Frame> = Decoder::builder(reader)
+                                         .buffer_size(buffer_size)
+                                         .build()
+                                         .unwrap()
+                                         .filter_map(|r| r.ok())
+                                         .collect();
+            assert_eq!(frames.len(), expected.len());
+            for (frame, expected) in frames.iter().zip(&expected) {
+                assert_eq!(frame.byte_offset, expected.byte_offset);
+                assert_eq!(frame.samples[0][100].to_raw(), expected.samples[0][100].to_raw());
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_garbage_prefix() {
+        let mut data = vec![0u8; 16 << 20];
+        data.extend(fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap());
+
+        // Each refill used to nest a call, overflowing a small stack
+        let count = std::thread::Builder::new()
+                        .stack_size(1024 * 1024)
+                        .spawn(move || {
+                            Decoder::builder(Cursor::new(data))
+                                .buffer_size(MIN_BUFFER_SIZE)
+                                .error_policy(ErrorPolicy::SkipRecoverable)
+                                .build()
+                                .unwrap()
+                                .filter_map(|r| r.ok())
+                                .count()
+                        })
+                        .unwrap()
+                        .join()
+                        .unwrap();
+        assert_eq!(count, 193);
+    }
+
+    #[test]
+    fn test_peek_header() {
+        for path in &["sample_mp3s/constant_stereo_128.mp3", "sample_mp3s/rodiomusic.mp3"] {
+            let data = fs::read(path).unwrap();
+            let expected: Vec<Frame> = Decoder::decode(Cursor::new(data.clone()))
+                                           .unwrap()
+                                           .filter_map(|r| r.ok())
+                                           .collect();
+            let mut headers = Decoder::decode_headers(Cursor::new(data.clone()))
+                                  .unwrap()
+                                  .headers();
+
+            let reader = Trickle { inner: Cursor::new(data), reads: 0 };
+            let mut decoder = Decoder::builder(reader)
+                                  .buffer_size(MIN_BUFFER_SIZE)
+                                  .build()
+                                  .unwrap();
+            for expected in &expected {
+                let header = decoder.peek_header().unwrap();
+                assert_eq!(decoder.peek_header().unwrap(), header);
+                assert_eq!(header, headers.next().unwrap().unwrap());
+
+                let frame = loop {
+                    if let Ok(frame) = decoder.get_frame() {
+                        break frame;
+                    }
+                };
+                assert_eq!(frame.byte_offset, header.byte_offset);
+                assert_eq!(frame.position, header.position);
+                assert_eq!(frame.reservoir_complete, header.reservoir_complete);
+                assert_eq!(frame.samples[0][500].to_raw(), expected.samples[0][500].to_raw());
+            }
+            assert_eq!(decoder.peek_header(), Err(SimplemadError::EOF));
+            assert!(decoder.get_frame().is_err());
+        }
+    }
+
+    #[test]
+    fn test_nth() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let expected: Vec<Frame> = Decoder::decode(File::open(path).unwrap())
+                                       .unwrap()
+                                       .filter_map(|r| r.ok())
+                                       .collect();
+        let raw = |frame: &Frame| -> Vec<i32> {
+            frame.samples.iter().flatten().map(|s| s.to_raw()).collect()
+        };
+
+        for &n in &[0, 1, 2, 5, 50, 192] {
+            let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+            let frame = decoder.nth(n).unwrap().unwrap();
+            assert_eq!(frame.byte_offset, expected[n].byte_offset);
+            assert_eq!(frame.position, expected[n].position);
+            assert_eq!(raw(&frame), raw(&expected[n]), "frame {}", n);
+        }
+
+        // Every 50th frame
+        let decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let offsets: Vec<u64> = decoder.step_by(50).map(|r| r.unwrap().byte_offset).collect();
+        let every_50th: Vec<u64> = expected.iter().step_by(50).map(|f| f.byte_offset).collect();
+        assert_eq!(offsets, every_50th);
+
+        let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        assert!(decoder.nth(193).is_none());
+        assert!(decoder.next().is_none());
+
+        let mut headers = Decoder::decode_headers(File::open(path).unwrap()).unwrap();
+        assert_eq!(headers.nth(100).unwrap().unwrap().byte_offset, expected[100].byte_offset);
+
+        // Skipped frames have no samples to process or compare formats of
+        let seen = Arc::new(Mutex::new(0));
+        let count = seen.clone();
+        let mut decoder = Decoder::builder(File::open(path).unwrap())
+                              .downmix(Downmix::Average)
+                              .consistent_format(true)
+                              .on_frame(move |_| *count.lock().unwrap() += 1)
+                              .build()
+                              .unwrap();
+        let frame = decoder.nth(5).unwrap().unwrap();
+        assert_eq!(frame.byte_offset, expected[5].byte_offset);
+        assert_eq!(frame.samples.len(), 1);
+        assert_eq!(*seen.lock().unwrap(), 1);
+        assert_eq!(decoder.filter(|r| r.is_err()).count(), 0);
+    }
+
+    #[test]
+    fn test_fused() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut decoder = Decoder::decode(file).unwrap();
+        assert_eq!(decoder.by_ref().count(), 193);
+        assert!(decoder.next().is_none());
+        assert!(decoder.next().is_none());
+        assert_eq!(decoder.get_frame().unwrap_err(), SimplemadError::EOF);
+    }
+
+    #[test]
+    fn test_size_hint() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut decoder = Decoder::decode(file).unwrap();
+        assert_eq!(decoder.size_hint(), (0, None));
+        decoder.next();
+        assert_eq!(decoder.size_hint(), (192, None));
+        assert_eq!(decoder.by_ref().count(), 192);
+        assert_eq!(decoder.size_hint(), (0, Some(0)));
+
+        // Without a Xing header the input length is needed
+        let path = "sample_mp3s/rodiomusic.mp3";
+        let len = fs::metadata(path).unwrap().len();
+        let mut decoder = Decoder::builder(File::open(path).unwrap())
+                              .input_len(len)
+                              .build()
+                              .unwrap();
+        decoder.next();
+        let (estimate, _) = decoder.size_hint();
+        assert!(estimate > 1_200 && estimate < 1_250);
+    }
+
+    #[test]
+    fn test_progress() {
+        // Measured in frames from the Xing header
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        assert_eq!(decoder.progress(), None);
+        assert_eq!(decoder.estimated_total_duration(), None);
+        decoder.by_ref().take(97).count();
+        let progress = decoder.progress().unwrap();
+        assert!(progress > 0.45 && progress < 0.55, "{}", progress);
+        let duration = decoder.estimated_total_duration().unwrap();
+        assert_eq!(duration, probe::duration(File::open(path).unwrap()).unwrap());
+        decoder.by_ref().count();
+        assert_eq!(decoder.progress(), Some(1.0));
+
+        // Measured in bytes from the input length
+        let path = "sample_mp3s/rodiomusic.mp3";
+        let mut decoder = Decoder::builder(File::open(path).unwrap())
+                              .measure_input_len()
+                              .unwrap()
+                              .build()
+                              .unwrap();
+        assert_eq!(decoder.progress(), Some(0.0));
+        decoder.by_ref().take(600).count();
+        let progress = decoder.progress().unwrap();
+        assert!(progress > 0.45 && progress < 0.55, "{}", progress);
+        let seconds = decoder.estimated_total_duration().unwrap().as_secs_f64();
+        assert!(seconds > 31.0 && seconds < 33.0, "{}", seconds);
+
+        let decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        assert_eq!(decoder.progress(), None);
+    }
+
+    #[test]
+    fn test_exact_frames() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let frames = Decoder::decode(file).unwrap().exact_frames().unwrap();
+        assert_eq!(frames.len(), 193);
+        assert_eq!(frames.count(), 193);
+
+        let path = "sample_mp3s/rodiomusic.mp3";
+        let file = File::open(path).unwrap();
+        assert!(Decoder::decode(file).unwrap().exact_frames().is_none());
+
+        let mut decoder = Decoder::builder(File::open(path).unwrap())
+                              .measure_input_len()
+                              .unwrap()
+                              .build()
+                              .unwrap()
+                              .exact_frames()
+                              .unwrap();
+        assert_eq!(decoder.len(), 1236);
+        decoder.next();
+        assert_eq!(decoder.len(), 1235);
+        assert_eq!(decoder.count(), 1235);
+    }
+
+    #[test]
+    fn test_iterate_by_reference() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut decoder = Decoder::decode(file).unwrap();
+        let mut frame_count = 0;
+        for _ in &mut decoder {
+            frame_count += 1;
+            if frame_count == 10 {
+                break;
+            }
+        }
+
+        assert_eq!(decoder.total_len(), Some(193));
+        assert_eq!(decoder.count(), 183);
+    }
+
+    #[test]
+    fn test_into_inner() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut decoder = Decoder::decode(file).unwrap();
+        decoder.by_ref().take(10).count();
+        let (mut file, offset) = decoder.into_inner();
+
+        // Continue decoding where the first decoder stopped. The first frame
+        // refers to the bit reservoir of frames that weren't seen again.
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        let results: Vec<_> = Decoder::decode(file).unwrap().collect();
+        assert_eq!(results.len(), 183);
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 182);
+    }
+
+    #[test]
+    fn test_reset() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut decoder = Decoder::decode(file).unwrap();
+        let first = decoder.get_frame().unwrap();
+        decoder.by_ref().take(10).count();
+
+        decoder.reset().unwrap();
+        let replayed = decoder.get_frame().unwrap();
+        assert_eq!(replayed.position, Duration::new(0, 0));
+        assert_eq!(replayed.samples[0][100].to_raw(), first.samples[0][100].to_raw());
+        assert_eq!(decoder.count(), 192);
+
+        // Leading garbage isn't decoded again
+        let mut data = junk(5_000);
+        let mut file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        file.read_to_end(&mut data).unwrap();
+        let mut decoder = Decoder::decode(Cursor::new(data)).unwrap();
+        let errors = decoder.by_ref().filter(|r| r.is_err()).count();
+        assert!(errors > 0);
+        decoder.reset().unwrap();
+        assert!(decoder.all(|r| r.is_ok()));
+    }
+
+    #[test]
+    fn test_checkpoint_resume() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        decoder.by_ref().take(50).count();
+        let checkpoint = decoder.checkpoint();
+        assert_eq!(checkpoint.frame_index, 50);
+        assert!(checkpoint.warm_up_offset < checkpoint.byte_offset);
+        let expected = decoder.get_frame().unwrap();
+
+        let mut resumed = Decoder::resume(File::open(path).unwrap(),
+                                          Default::default(),
+                                          checkpoint)
+                              .unwrap();
+        let frame = resumed.get_frame().unwrap();
+        assert_eq!(frame.position, expected.position);
+        for (a, b) in frame.samples[0].iter().zip(expected.samples[0].iter()) {
+            assert_eq!(a.to_raw(), b.to_raw());
+        }
+        assert_eq!(resumed.count(), 193 - 51);
+    }
+
+    #[test]
+    fn test_headers() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let headers: Vec<FrameHeader> = Decoder::decode(file)
+                                            .unwrap()
+                                            .headers()
+                                            .filter_map(|r| r.ok())
+                                            .collect();
+        assert_eq!(headers.len(), 193);
+        assert_eq!(headers[0].byte_offset, 0);
+        assert_eq!(headers[0].layer, Layer::III);
+        assert_eq!(headers[1].byte_offset, 417);
+        assert_eq!(headers[1].position, headers[0].duration);
+        assert!(headers.iter().all(|h| h.sample_rate == 44100 && h.bit_rate == 128000));
+        assert!(headers.iter().any(|h| h.flags & MAD_FLAG_PADDING != 0));
+    }
+
+    #[test]
+    fn test_invalid_format() {
+        let mut wav = b"RIFF\x24\x08\x00\x00WAVEfmt \x10\x00\x00\x00\x01\x00".to_vec();
+        wav.extend(junk(10_000));
+        match Decoder::decode(Cursor::new(wav)) {
+            Err(e) => {
+                assert_eq!(e, SimplemadError::InvalidFormat(DetectedFormat::Wav));
+                assert_eq!(e.kind(), ErrorKind::InvalidFormat);
+            }
+            Ok(_) => panic!("WAV input was accepted"),
+        }
+    }
+
+    #[test]
+    fn test_estimated_duration() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let total_bytes = fs::metadata(path).unwrap().len();
+        let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        assert_eq!(decoder.estimated_duration(total_bytes), None);
+
+        decoder.next().unwrap().unwrap();
+        let estimate = decoder.estimated_duration(total_bytes).unwrap();
+        assert!(estimate > Duration::from_millis(4950) && estimate < Duration::from_millis(5150),
+                "{:?}",
+                estimate);
+    }
+
+    #[test]
+    fn test_stats() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        assert_eq!(decoder.stats(), DecoderStats::default());
+
+        let frames = decoder.by_ref().filter(|r| r.is_ok()).count() as u64;
+        let stats = decoder.stats();
+        assert_eq!(stats.frames_decoded, frames);
+        assert_eq!(stats.average_bit_rate, 128_000);
+        let len = fs::metadata(path).unwrap().len();
+        assert!(stats.bytes_consumed > len - 1_000 && stats.bytes_consumed <= len);
+
+        let mut decoder = Decoder::decode(Cursor::new(corrupted_sample())).unwrap();
+        let errors = decoder.by_ref().filter(|r| r.is_err()).count() as u64;
+        let stats = decoder.stats();
+        assert_eq!(stats.recoverable_errors, errors);
+        assert!(stats.bytes_skipped > 0);
+    }
+
+    #[test]
+    fn test_bytes_consumed() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut decoder = Decoder::decode(file).unwrap();
+        assert_eq!(decoder.bytes_consumed(), 0);
+
+        let mut frame_ends = Vec::new();
+        for _ in 0..3 {
+            decoder.get_frame().unwrap();
+            frame_ends.push(decoder.bytes_consumed());
+        }
+        // 128 kbps frames at 44.1 kHz are 417 or 418 bytes long
+        let frame_len = frame_ends[2] - frame_ends[1];
+        assert!(frame_len == 417 || frame_len == 418, "{}", frame_len);
+
+        let consumed = decoder.bytes_consumed();
+        let (mut file, offset) = decoder.into_inner();
+        assert_eq!(offset, consumed);
+        assert!(file.stream_position().unwrap() > consumed);
+    }
+
+    #[test]
+    fn test_frame_byte_offset() {
+        let data = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let decoder = Decoder::decode(Cursor::new(&data)).unwrap();
+        let offsets: Vec<u64> = decoder.filter_map(|r| r.ok()).map(|f| f.byte_offset).collect();
+
+        assert!(offsets.iter().all(|&offset| data[offset as usize] == 0xff));
+        assert!(offsets.windows(2).all(|w| w[1] - w[0] == 417 || w[1] - w[0] == 418));
+    }
+
+    #[test]
+    fn test_encoded_bytes() {
+        let data = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let frames: Vec<Frame> = Decoder::builder(Cursor::new(&data))
+                                     .keep_encoded(true)
+                                     .build()
+                                     .unwrap()
+                                     .filter_map(|r| r.ok())
+                                     .collect();
+        for frame in &frames {
+            let encoded = frame.encoded.as_ref().unwrap();
+            let offset = frame.byte_offset as usize;
+            assert!(encoded.len() == 417 || encoded.len() == 418);
+            assert_eq!(&data[offset..offset + encoded.len()], &encoded[..]);
+        }
+
+        let mut headers = Decoder::decode_headers(Cursor::new(&data)).unwrap().headers();
+        let header = headers.next().unwrap().unwrap();
+        assert_eq!(headers.frame_bytes(), frames[0].encoded.as_ref().unwrap().as_slice());
+        assert_eq!(header.byte_offset, frames[0].byte_offset);
+
+        let frame = Decoder::decode(Cursor::new(&data)).unwrap().find_map(|r| r.ok()).unwrap();
+        assert!(frame.encoded.is_none());
+    }
+
+    #[test]
+    fn test_ancillary_data() {
+        // The tag frame's payload is ancillary data to libmad
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let frame = Decoder::decode(file).unwrap().find_map(|r| r.ok()).unwrap();
+        assert!(frame.ancillary_data().starts_with(b"Info"));
+
+        let file = File::open("sample_mp3s/variable_stereo.mp3").unwrap();
+        let frame = Decoder::decode(file).unwrap().find_map(|r| r.ok()).unwrap();
+        assert!(frame.ancillary_data().starts_with(b"Xing"));
+
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let frame = Decoder::decode_headers(file).unwrap().find_map(|r| r.ok()).unwrap();
+        assert!(frame.ancillary_data().is_empty());
     }
 
-    /// Convert to i16
-    pub fn to_i16(&self) -> i16 {
-        let frac_bits = 28;
-        let unity_value = 0x1000_0000;
-        let rounded_value = self.value + (1 << (frac_bits - 16));
-        let clipped_value = max(-unity_value, min(rounded_value, unity_value - 1));
-        let quantized_value = clipped_value >> (frac_bits + 1 - 16);
+    #[test]
+    fn test_bit_reservoir() {
+        let data = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let frames: Vec<Frame> = Decoder::decode(Cursor::new(&data))
+                                     .unwrap()
+                                     .filter_map(|r| r.ok())
+                                     .collect();
+        assert!(frames.iter().all(|f| f.reservoir_complete));
+        assert!(frames.iter().any(|f| f.main_data_begin > 0));
 
-        quantized_value as i16
+        // Starting in the middle of the stream loses the data of the frames
+        // before the first one
+        let offset = frames.iter()
+                           .skip(1)
+                           .find(|f| f.main_data_begin > 0)
+                           .unwrap()
+                           .byte_offset as usize;
+        let mut decoder = Decoder::decode(Cursor::new(&data[offset..])).unwrap();
+        match decoder.get_frame() {
+            Err(SimplemadError::Mad { error: LibmadError::BadDataPtr, .. }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(decoder.filter_map(|r| r.ok()).all(|f| f.reservoir_complete));
+
+        let mut headers = Decoder::decode_headers(Cursor::new(&data[offset..]))
+                              .unwrap()
+                              .headers();
+        let header = headers.next().unwrap().unwrap();
+        assert!(header.main_data_begin > 0);
+        assert!(!header.reservoir_complete);
     }
 
-    /// Convert to i32
-    pub fn to_i32(&self) -> i32 {
-        // clip only
-        if self.value > i32::max_value() / 8 {
-            i32::max_value()
-        } else if self.value < i32::min_value() / 8 {
-            i32::min_value()
-        } else {
-            self.value * 8
+    #[test]
+    fn test_recycle() {
+        let path = "sample_mp3s/variable_stereo.mp3";
+        let raw = |frame: &Frame| -> Vec<Vec<i32>> {
+            frame.samples.iter().map(|c| c.iter().map(|s| s.to_raw()).collect()).collect()
+        };
+        let configs: [fn(DecoderBuilder<File>) -> DecoderBuilder<File>; 3] =
+            [|b| b,
+             |b| b.downmix(Downmix::Average).channels(&[Channel::Left, Channel::Right]),
+             |b| b.channels(&[Channel::Right])];
+
+        for config in &configs {
+            let expected: Vec<Frame> = config(Decoder::builder(File::open(path).unwrap()))
+                                           .build()
+                                           .unwrap()
+                                           .filter_map(|r| r.ok())
+                                           .collect();
+
+            let mut decoder = config(Decoder::builder(File::open(path).unwrap())).build().unwrap();
+            let mut count = 0;
+            let mut recycled = None;
+            while let Ok(frame) = decoder.get_frame() {
+                assert_eq!(raw(&frame), raw(&expected[count]));
+                // The next frame reuses the buffer
+                if let Some(pointer) = recycled {
+                    assert_eq!(frame.samples[0].as_ptr(), pointer);
+                }
+                recycled = Some(frame.samples[0].as_ptr());
+                decoder.recycle(frame);
+                count += 1;
+            }
+            assert_eq!(count, expected.len());
         }
     }
 
-    /// Convert to f32
-    pub fn to_f32(&self) -> f32 {
-        // The big number is 2^28, as 28 is the fractional bit count)
-        f32::max(-1.0, f32::min(1.0, (self.value as f32) / 268435456.0))
+    #[test]
+    fn test_lazy_synth() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let mut decoder = Decoder::decode_headers(File::open(path).unwrap()).unwrap();
+        assert_eq!(decoder.by_ref().count(), 193);
+        assert!(decoder.mad.synth.is_none());
+
+        let mut decoder = Decoder::builder(File::open(path).unwrap())
+                              .headers_only(true)
+                              .start_time(Duration::from_secs(2))
+                              .build()
+                              .unwrap();
+        assert!(decoder.get_frame().is_ok());
+        assert!(decoder.mad.synth.is_none());
+
+        let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        assert!(decoder.get_frame().is_ok());
+        assert!(decoder.mad.synth.is_some());
     }
 
-    /// Convert to f64
-    pub fn to_f64(&self) -> f64 {
-        // The big number is 2^28, as 28 is the fractional bit count)
-        f64::max(-1.0, f64::min(1.0, (self.value as f64) / 268435456.0))
+    #[test]
+    fn test_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Decoder<File>>();
+        assert_send::<Headers<File>>();
+
+        let path = "sample_mp3s/variable_stereo.mp3";
+        let expected: Vec<Frame> = Decoder::decode(File::open(path).unwrap())
+                                       .unwrap()
+                                       .filter_map(|r| r.ok())
+                                       .collect();
+
+        // Move a decoder with libmad's state mid-stream to another thread
+        let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let mut frames: Vec<Frame> = decoder.by_ref().take(10).filter_map(|r| r.ok()).collect();
+        let rest = std::thread::spawn(move || decoder.filter_map(|r| r.ok()).collect::<Vec<_>>());
+        frames.extend(rest.join().unwrap());
+
+        assert_eq!(frames.len(), expected.len());
+        let raw = |frame: &Frame| -> Vec<i32> {
+            frame.samples.iter().flatten().map(|s| s.to_raw()).collect()
+        };
+        assert!(frames.iter().zip(&expected).all(|(a, b)| raw(a) == raw(b)));
     }
-}
 
-impl From<i16> for MadFixed32 {
-    fn from(v: i16) -> MadFixed32 {
-        MadFixed32 { value: v as i32 * 8192 }
+    #[test]
+    #[cfg(feature = "sys")]
+    fn test_raw_state() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut decoder = Decoder::decode(file).unwrap();
+        let frame = decoder.get_frame().unwrap();
+
+        let stream = unsafe { decoder.raw_stream() };
+        assert_eq!(stream.error, MadError::None);
+        let header = unsafe { &decoder.raw_frame().header };
+        assert_eq!(header.sample_rate, frame.sample_rate);
+        let synth = unsafe { decoder.raw_synth() };
+        assert_eq!(synth.pcm.length as usize, frame.n_samples());
+        assert_eq!(synth.pcm.samples[1][..frame.n_samples()],
+                   frame.samples[1].iter().map(|s| s.to_raw()).collect::<Vec<_>>()[..]);
     }
-}
 
-impl From<i32> for MadFixed32 {
-    fn from(v: i32) -> MadFixed32 {
-        MadFixed32 { value: v / 8 }
+    #[test]
+    fn test_read_bits() {
+        let mut data = [0b1010_1100u8, 0b0101_1111, 0b1000_0000];
+        let mut ptr = MadBitPtr {
+            byte: data.as_mut_ptr() as *mut c_char,
+            cache: 0,
+            left: 8,
+        };
+        assert_eq!(unsafe { read_bits(&ptr, 0) }, Vec::<u8>::new());
+        assert_eq!(unsafe { read_bits(&ptr, 16) }, vec![0b1010_1100, 0b0101_1111]);
+        assert_eq!(unsafe { read_bits(&ptr, 5) }, vec![0b1010_1000]);
+
+        ptr.left = 3;
+        assert_eq!(unsafe { read_bits(&ptr, 13) }, vec![0b1000_1011, 0b1111_0000]);
+
+        ptr.left = 0;
+        assert_eq!(unsafe { read_bits(&ptr, 9) }, vec![0b0101_1111, 0b1000_0000]);
     }
-}
 
-impl From<f32> for MadFixed32 {
-    fn from(v: f32) -> MadFixed32 {
-        MadFixed32 {
-            // The big number is 2^28, as
-            // 28 is the fractional bit count)
-            value: (v * 268435456.0) as i32,
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
         }
-    }
-}
 
-impl From<f64> for MadFixed32 {
-    fn from(v: f64) -> MadFixed32 {
-        MadFixed32 {
-            // The big number is 2^28, as
-            // 28 is the fractional bit count)
-            value: (v * 268435456.0) as i32,
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
         }
     }
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    use simplemad_sys::*;
-    use std::io::BufReader;
-    use std::fs::File;
-    use std::path::Path;
-    use std::time::Duration;
 
     #[test]
-    fn test_find_duration() {
-        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
-        let bufreader = BufReader::new(file);
-        let decoder = Decoder::decode_headers(bufreader).unwrap();
+    fn test_tee() {
+        let data = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let copy = SharedBuffer::default();
+        let mut decoder = Decoder::builder(Cursor::new(&data))
+                              .buffer_size(MIN_BUFFER_SIZE)
+                              .tee(copy.clone())
+                              .build()
+                              .unwrap();
+        decoder.next();
+        assert_eq!(copy.0.lock().unwrap().len(), MIN_BUFFER_SIZE);
 
-        let duration = decoder.filter_map(|r| {
-                                  match r {
-                                      Ok(f) => Some(f.duration),
-                                      Err(_) => None,
-                                  }
-                              })
-                              .fold(Duration::new(0, 0), |acc, dtn| acc + dtn);
-
-        assert_eq!(duration, Duration::new(5, 41632464));
+        assert!(decoder.all(|r| r.is_ok()));
+        assert_eq!(*copy.0.lock().unwrap(), data);
     }
 
     #[test]
-    fn test_decode_headers() {
-        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
-        let bufreader = BufReader::new(file);
-        let decoder = Decoder::decode_headers(bufreader).unwrap();
-        let mut frame_count = 0;
-        let mut error_count = 0;
+    fn test_header_bits() {
+        let mut data = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let offsets: Vec<u64> = Decoder::decode(Cursor::new(&data))
+                                    .unwrap()
+                                    .filter_map(|r| r.ok())
+                                    .map(|f| f.byte_offset)
+                                    .collect();
+        let second = offsets[1] as usize;
+        // Set the private, copyright and original bits and 50/15 us emphasis
+        data[second + 2] |= 0x01;
+        data[second + 3] = data[second + 3] & 0xf0 | 0x0d;
 
-        for item in decoder {
-            match item {
-                Err(_) => {
-                    if frame_count > 0 {
-                        error_count += 1;
-                    }
-                }
-                Ok(f) => {
-                    frame_count += 1;
-                    assert_eq!(f.mode, MadMode::Stereo);
-                    assert_eq!(f.layer, MadLayer::LayerIII);
-                    assert_eq!(f.bit_rate, 128000);
-                    assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.samples.len(), 0);
-                }
-            }
-        }
-        assert_eq!(error_count, 0);
-        assert_eq!(frame_count, 193);
+        let frames: Vec<Frame> = Decoder::decode(Cursor::new(&data))
+                                     .unwrap()
+                                     .filter_map(|r| r.ok())
+                                     .take(3)
+                                     .collect();
+        assert!(frames[1].private && frames[1].copyright && frames[1].original);
+        assert_eq!(frames[1].emphasis, Emphasis::Fifty15Us);
+        assert!(!frames[2].private && !frames[2].copyright);
+        assert_eq!(frames[2].emphasis, Emphasis::None);
+        assert!(frames.iter().any(|f| f.padding) && frames.iter().any(|f| !f.padding));
     }
 
     #[test]
-    fn test_bufreader() {
-        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
-        let bufreader = BufReader::new(file);
-        let decoder = Decoder::decode(bufreader).unwrap();
-        let mut frame_count = 0;
-        let mut error_count = 0;
+    fn test_frame_sizes() {
+        let file = File::open("sample_mp3s/constant_single_channel_128.mp3").unwrap();
+        let frame = Decoder::decode(file).unwrap().find_map(|r| r.ok()).unwrap();
+        assert_eq!(frame.n_channels(), 1);
+        assert_eq!(frame.n_samples(), 1152);
+        assert_eq!(frame.duration_samples(), 1152);
+        assert_eq!(frame.channel(Channel::Left).unwrap().len(), 1152);
+        assert!(frame.channel(Channel::Right).is_none());
 
-        for item in decoder {
-            match item {
-                Err(_) => {
-                    if frame_count > 0 {
-                        error_count += 1;
-                    }
-                }
-                Ok(f) => {
-                    frame_count += 1;
-                    assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.mode, MadMode::Stereo);
-                    assert_eq!(f.layer, MadLayer::LayerIII);
-                    assert_eq!(f.bit_rate, 128000);
-                    assert_eq!(f.samples.len(), 2);
-                    assert_eq!(f.samples[0].len(), 1152);
-                }
-            }
-        }
-        assert_eq!(error_count, 0);
-        assert_eq!(frame_count, 193);
+        let file = File::open("sample_mp3s/constant_stereo_16.mp3").unwrap();
+        let frame = Decoder::decode_headers(file).unwrap().find_map(|r| r.ok()).unwrap();
+        assert_eq!(frame.n_channels(), 2);
+        assert_eq!(frame.n_samples(), 0);
+        assert_eq!(frame.duration_samples(), 576);
+        assert!(frame.channel(Channel::Left).is_none());
+
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let frame = Decoder::decode(file).unwrap().find_map(|r| r.ok()).unwrap();
+        assert_eq!(frame.channel(Channel::Right).unwrap().as_ptr(), frame.samples[1].as_ptr());
+
+        let left = frame.samples[0].as_ptr();
+        let samples = frame.into_samples();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].as_ptr(), left);
     }
 
     #[test]
-    fn test_decode_interval() {
-        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
-        let decoder = Decoder::decode_interval(file,
-                                               Duration::from_secs(3),
-                                               Duration::from_secs(4)).unwrap();
-        let mut frame_count = 0;
-        let mut error_count = 0;
+    fn test_frame_split_at() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let frame = Decoder::decode(file).unwrap().filter_map(|r| r.ok()).nth(2).unwrap();
+        let (position, duration) = (frame.position, frame.duration);
 
-        for item in decoder {
-            match item {
-                Err(_) => {
-                    if frame_count > 0 {
-                        error_count += 1;
-                    }
-                }
-                Ok(f) => {
-                    frame_count += 1;
-                    assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.samples.len(), 2);
-                    assert_eq!(f.samples[0].len(), 1152);
-                }
-            }
-        }
-        assert_eq!(error_count, 0);
-        assert_eq!(frame_count, 39);
+        let (head, tail) = frame.split_at(Duration::from_millis(10));
+        assert_eq!(head.n_samples(), 441);
+        assert_eq!(tail.n_samples(), 1152 - 441);
+        assert_eq!(tail.samples.len(), 2);
+        assert_eq!(head.position, position);
+        assert_eq!(head.duration + tail.duration, duration);
+        assert_eq!(tail.position, position + head.duration);
+        assert!(head.duration > Duration::from_micros(9_990) &&
+                head.duration < Duration::from_micros(10_010));
+
+        let (head, tail) = head.split_at(Duration::from_secs(1));
+        assert_eq!(head.n_samples(), 441);
+        assert_eq!(tail.n_samples(), 0);
+        assert_eq!(tail.duration, Duration::new(0, 0));
     }
 
     #[test]
-    fn test_interval_beyond_eof() {
-        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
-        let mut decoder = Decoder::decode_interval(file,
-                                                   Duration::from_secs(60),
-                                                   Duration::from_secs(65)).unwrap();
+    fn test_frame_levels() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut frame = Decoder::decode(file).unwrap().filter_map(|r| r.ok()).nth(100).unwrap();
+        let (peak, rms) = (frame.peak().to_f32(), frame.rms().to_f32());
+        assert!(peak > 0.1 && peak <= 1.0, "{}", peak);
+        assert!(rms > 0.0 && rms < peak, "{}", rms);
+        assert!(!frame.is_silent(MadFixed32::from(0.001)));
+        assert!(frame.is_silent(frame.peak()));
 
-        assert!(decoder.next().is_none());
+        frame.samples = vec![vec![MadFixed32::new(0); 1152]; 2];
+        frame.samples[1][7] = MadFixed32::new(-1000);
+        assert_eq!(frame.peak().to_raw(), 1000);
+        assert_eq!(frame.rms().to_raw(), 20);
+        assert!(frame.is_silent(MadFixed32::from(0.001)));
     }
 
     #[test]
-    fn test_decode_empty_interval() {
-        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
-        let decoder = Decoder::decode_interval(file,
-                                               Duration::from_secs(2),
-                                               Duration::from_secs(2)).unwrap();
-        let mut frame_count = 0;
-        let mut error_count = 0;
+    fn test_downmix() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let stereo = Decoder::decode(File::open(path).unwrap())
+                         .unwrap()
+                         .find_map(|r| r.ok())
+                         .unwrap();
 
-        for item in decoder {
-            match item {
-                Err(_) => {
-                    if frame_count > 0 {
-                        error_count += 1;
-                    }
-                }
-                Ok(f) => {
-                    frame_count += 1;
-                    assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.samples.len(), 2);
-                    assert_eq!(f.samples[0].len(), 1152);
-                }
+        for &downmix in &[Downmix::Average, Downmix::ConstantPower] {
+            let mono = Decoder::builder(File::open(path).unwrap())
+                           .downmix(downmix)
+                           .build()
+                           .unwrap()
+                           .find_map(|r| r.ok())
+                           .unwrap();
+            assert_eq!(mono.n_channels(), 1);
+            assert_eq!(mono.n_samples(), stereo.n_samples());
+            for i in 0..mono.n_samples() {
+                let (l, r) = (stereo.samples[0][i].to_raw(), stereo.samples[1][i].to_raw());
+                assert_eq!(mono.samples[0][i].to_raw(), downmix.mix(l, r));
             }
         }
-        assert_eq!(error_count, 0);
-        assert_eq!(frame_count, 0);
+
+        assert_eq!(Downmix::Average.mix(1000, 3000), 2000);
+        assert_eq!(Downmix::ConstantPower.mix(1 << 28, 1 << 28), 0x16a0_9e66);
     }
 
     #[test]
-    fn test_decode_overlong_interval() {
-        let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
-        let decoder = Decoder::decode_interval(file,
-                                               Duration::from_secs(3),
-                                               Duration::from_secs(45)).unwrap();
-        let mut frame_count = 0;
-        let mut error_count = 0;
+    fn test_channel_map() {
+        let first_frame = |path: &str, channels: &[Channel]| {
+            Decoder::builder(File::open(path).unwrap())
+                .channels(channels)
+                .build()
+                .unwrap()
+                .find_map(|r| r.ok())
+                .unwrap()
+        };
+        let raw = |samples: &[MadFixed32]| samples.iter().map(|s| s.to_raw()).collect::<Vec<_>>();
 
-        for item in decoder {
-            match item {
-                Err(_) => {
-                    if frame_count > 0 {
-                        error_count += 1;
-                    }
-                }
-                Ok(f) => {
-                    frame_count += 1;
-                    assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.samples.len(), 2);
-                    assert_eq!(f.samples[0].len(), 1152);
-                }
-            }
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let stereo = first_frame(path, &[Channel::Left, Channel::Right]);
+        let right = first_frame(path, &[Channel::Right]);
+        assert_eq!(right.n_channels(), 1);
+        assert_eq!(raw(&right.samples[0]), raw(&stereo.samples[1]));
+
+        let swapped = first_frame(path, &[Channel::Right, Channel::Left]);
+        assert_eq!(raw(&swapped.samples[0]), raw(&stereo.samples[1]));
+        assert_eq!(raw(&swapped.samples[1]), raw(&stereo.samples[0]));
+
+        let path = "sample_mp3s/constant_single_channel_128.mp3";
+        let doubled = first_frame(path, &[Channel::Left, Channel::Right]);
+        assert_eq!(doubled.n_channels(), 2);
+        assert_eq!(raw(&doubled.samples[0]), raw(&doubled.samples[1]));
+    }
+
+    #[test]
+    fn test_gain() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let frame_with_gain = |gain_db: f32| {
+            Decoder::builder(File::open(path).unwrap())
+                .gain_db(gain_db)
+                .build()
+                .unwrap()
+                .filter_map(|r| r.ok())
+                .nth(100)
+                .unwrap()
+        };
+
+        let unity = frame_with_gain(0.0);
+        let quieter = frame_with_gain(-6.0);
+        for (a, b) in unity.samples[0].iter().zip(&quieter.samples[0]) {
+            let expected = f64::from(a.to_raw()) * 0.501;
+            assert!((f64::from(b.to_raw()) - expected).abs() < 1e-3 * f64::from(1 << 28));
         }
-        assert_eq!(error_count, 0);
-        assert_eq!(frame_count, 77);
+
+        let decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let plain = decoder.filter_map(|r| r.ok()).nth(100).unwrap();
+        assert!(plain.samples[0]
+                     .iter()
+                     .zip(&unity.samples[0])
+                     .all(|(a, b)| a.to_raw() == b.to_raw()));
+
+        assert_eq!(builder::apply_gain(i32::MAX, 2 << 28), i32::MAX);
     }
 
     #[test]
     fn constant_stereo_128() {
         let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
+        let file = File::open(path).unwrap();
         let decoder = Decoder::decode(file).unwrap();
         let mut frame_count = 0;
         let mut error_count = 0;
@@ -657,8 +3904,8 @@ mod test {
                 Ok(f) => {
                     frame_count += 1;
                     assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.mode, MadMode::Stereo);
-                    assert_eq!(f.layer, MadLayer::LayerIII);
+                    assert_eq!(f.mode, Mode::Stereo);
+                    assert_eq!(f.layer, Layer::III);
                     assert_eq!(f.bit_rate, 128000);
                     assert_eq!(f.samples.len(), 2);
                     assert_eq!(f.samples[0].len(), 1152);
@@ -672,7 +3919,7 @@ mod test {
     #[test]
     fn constant_joint_stereo_128() {
         let path = Path::new("sample_mp3s/constant_joint_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
+        let file = File::open(path).unwrap();
         let decoder = Decoder::decode(file).unwrap();
         let mut frame_count = 0;
         let mut error_count = 0;
@@ -687,8 +3934,8 @@ mod test {
                 Ok(f) => {
                     frame_count += 1;
                     assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.mode, MadMode::JointStereo);
-                    assert_eq!(f.layer, MadLayer::LayerIII);
+                    assert_eq!(f.mode, Mode::JointStereo);
+                    assert_eq!(f.layer, Layer::III);
                     assert_eq!(f.bit_rate, 128000);
                     assert_eq!(f.samples.len(), 2);
                     assert_eq!(f.samples[0].len(), 1152);
@@ -702,7 +3949,7 @@ mod test {
     #[test]
     fn average_stereo_128() {
         let path = Path::new("sample_mp3s/average_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
+        let file = File::open(path).unwrap();
         let decoder = Decoder::decode(file).unwrap();
         let mut frame_count = 0;
         let mut error_count = 0;
@@ -717,8 +3964,8 @@ mod test {
                 Ok(f) => {
                     frame_count += 1;
                     assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.mode, MadMode::Stereo);
-                    assert_eq!(f.layer, MadLayer::LayerIII);
+                    assert_eq!(f.mode, Mode::Stereo);
+                    assert_eq!(f.layer, Layer::III);
                     assert_eq!(f.samples.len(), 2);
                     assert_eq!(f.samples[0].len(), 1152);
                 }
@@ -731,7 +3978,7 @@ mod test {
     #[test]
     fn constant_stereo_320() {
         let path = Path::new("sample_mp3s/constant_stereo_320.mp3");
-        let file = File::open(&path).unwrap();
+        let file = File::open(path).unwrap();
         let decoder = Decoder::decode(file).unwrap();
         let mut frame_count = 0;
         let mut error_count = 0;
@@ -746,8 +3993,8 @@ mod test {
                 Ok(f) => {
                     frame_count += 1;
                     assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.mode, MadMode::Stereo);
-                    assert_eq!(f.layer, MadLayer::LayerIII);
+                    assert_eq!(f.mode, Mode::Stereo);
+                    assert_eq!(f.layer, Layer::III);
                     assert_eq!(f.bit_rate, 320000);
                     assert_eq!(f.samples.len(), 2);
                     assert_eq!(f.samples[0].len(), 1152);
@@ -761,7 +4008,7 @@ mod test {
     #[test]
     fn variable_joint_stereo() {
         let path = Path::new("sample_mp3s/variable_joint_stereo.mp3");
-        let file = File::open(&path).unwrap();
+        let file = File::open(path).unwrap();
         let decoder = Decoder::decode(file).unwrap();
         let mut frame_count = 0;
         let mut error_count = 0;
@@ -776,8 +4023,8 @@ mod test {
                 Ok(f) => {
                     frame_count += 1;
                     assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.mode, MadMode::JointStereo);
-                    assert_eq!(f.layer, MadLayer::LayerIII);
+                    assert_eq!(f.mode, Mode::JointStereo);
+                    assert_eq!(f.layer, Layer::III);
                     assert_eq!(f.samples.len(), 2);
                     assert_eq!(f.samples[0].len(), 1152);
                 }
@@ -790,7 +4037,7 @@ mod test {
     #[test]
     fn variable_stereo() {
         let path = Path::new("sample_mp3s/variable_stereo.mp3");
-        let file = File::open(&path).unwrap();
+        let file = File::open(path).unwrap();
         let decoder = Decoder::decode(file).unwrap();
         let mut frame_count = 0;
         let mut error_count = 0;
@@ -817,7 +4064,7 @@ mod test {
     #[test]
     fn constant_stereo_16() {
         let path = Path::new("sample_mp3s/constant_stereo_16.mp3");
-        let file = File::open(&path).unwrap();
+        let file = File::open(path).unwrap();
         let decoder = Decoder::decode(file).unwrap();
         let mut frame_count = 0;
         let mut error_count = 0;
@@ -832,8 +4079,8 @@ mod test {
                 Ok(f) => {
                     frame_count += 1;
                     assert_eq!(f.sample_rate, 24000);
-                    assert_eq!(f.mode, MadMode::Stereo);
-                    assert_eq!(f.layer, MadLayer::LayerIII);
+                    assert_eq!(f.mode, Mode::Stereo);
+                    assert_eq!(f.layer, Layer::III);
                     assert_eq!(f.bit_rate, 16000);
                     assert_eq!(f.samples.len(), 2);
                     assert_eq!(f.samples[0].len(), 576);
@@ -847,7 +4094,7 @@ mod test {
     #[test]
     fn constant_single_channel_128() {
         let path = Path::new("sample_mp3s/constant_single_channel_128.mp3");
-        let file = File::open(&path).unwrap();
+        let file = File::open(path).unwrap();
         let decoder = Decoder::decode(file).unwrap();
         let mut frame_count = 0;
         let mut error_count = 0;
@@ -862,8 +4109,8 @@ mod test {
                 Ok(f) => {
                     frame_count += 1;
                     assert_eq!(f.sample_rate, 44100);
-                    assert_eq!(f.mode, MadMode::SingleChannel);
-                    assert_eq!(f.layer, MadLayer::LayerIII);
+                    assert_eq!(f.mode, Mode::SingleChannel);
+                    assert_eq!(f.layer, Layer::III);
                     assert_eq!(f.bit_rate, 128000);
                     assert_eq!(f.samples.len(), 1);
                     assert_eq!(f.samples[0].len(), 1152);
@@ -874,6 +4121,136 @@ mod test {
         assert_eq!(frame_count, 193);
     }
 
+    #[test]
+    fn test_samples_per_frame() {
+        assert_eq!(samples_per_frame(Layer::I, MpegVersion::Mpeg2), 384);
+        assert_eq!(samples_per_frame(Layer::II, MpegVersion::Mpeg25), 1152);
+        assert_eq!(samples_per_frame(Layer::III, MpegVersion::Mpeg1), 1152);
+        assert_eq!(samples_per_frame(Layer::III, MpegVersion::Mpeg2), 576);
+        assert_eq!(MpegVersion::from_sample_rate(11025), Some(MpegVersion::Mpeg25));
+        assert_eq!(MpegVersion::from_sample_rate(44000), None);
+
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let frame = Decoder::decode(file).unwrap().next().unwrap().unwrap();
+        assert_eq!(frame.samples_per_frame(), frame.n_samples());
+
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let header = Decoder::decode(file).unwrap().headers().nth(3).unwrap().unwrap();
+        assert_eq!(header.samples_per_frame(), LAYER_III_SAMPLES);
+
+        let file = File::open("sample_mp3s/constant_stereo_16.mp3").unwrap();
+        let mut headers = Decoder::decode(file).unwrap().headers();
+        let header = headers.next().unwrap().unwrap();
+        assert_eq!(header.samples_per_frame(), LAYER_III_LSF_SAMPLES);
+    }
+
+    #[test]
+    fn test_convert() {
+        let file = File::open("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let frame = Decoder::decode(file).unwrap().nth(20).unwrap().unwrap();
+
+        let converted = frame.convert::<i16>();
+        assert_eq!(converted.len(), 2);
+        assert_eq!(converted[1], MadFixed32::to_i16_vec(&frame.samples[1]));
+        assert_eq!(frame.convert::<MadFixed32>(), frame.samples);
+
+        let mut interleaved: Vec<f32> = vec![0.5];
+        frame.interleave(&mut interleaved);
+        assert_eq!(interleaved.len(), 1 + 2 * 1152);
+        assert_eq!(interleaved[2], frame.samples[1][0].to_f32());
+        assert_eq!(interleaved[3], frame.samples[0][1].to_f32());
+
+        let mut bytes = Vec::new();
+        assert_eq!(frame.write_pcm::<i16>(&mut bytes).unwrap(), 2 * 1152 * 2);
+        assert_eq!(bytes.len(), 2 * 1152 * 2);
+        assert_eq!(i16::from_le_bytes([bytes[2], bytes[3]]), converted[1][0]);
+        let mut bytes = Vec::new();
+        assert_eq!(frame.write_pcm::<f64>(&mut bytes).unwrap(), 2 * 1152 * 8);
+    }
+
+    #[test]
+    fn test_sample_format() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let frame = Decoder::decode(File::open(path).unwrap()).unwrap().next().unwrap().unwrap();
+        let mut expected = Vec::new();
+        frame.write_pcm::<i16>(&mut expected).unwrap();
+
+        let mut decoder = Decoder::builder(File::open(path).unwrap())
+                              .sample_format(SampleFormat::I16)
+                              .build()
+                              .unwrap();
+        let mut bytes = Vec::new();
+        assert_eq!(decoder.write_pcm(&mut bytes).unwrap(), expected.len() as u64);
+        assert_eq!(bytes, expected);
+        assert_eq!(bytes.len(), 2 * 1152 * SampleFormat::I16.bytes_per_sample());
+
+        let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let mut bytes = Vec::new();
+        assert_eq!(decoder.write_pcm(&mut bytes).unwrap(), 2 * 1152 * 4);
+        assert_eq!(&bytes[..4], &frame.samples[0][0].to_raw().to_le_bytes());
+        while decoder.write_pcm(&mut bytes).is_ok() {}
+        assert_eq!(decoder.write_pcm(&mut bytes), Err(SimplemadError::EOF));
+    }
+
+    #[test]
+    fn test_fixed_arithmetic() {
+        let half = MadFixed32::from(0.5);
+        let quarter = MadFixed32::from(0.25);
+        assert_eq!((half + quarter).to_f32(), 0.75);
+        assert_eq!((quarter - half).to_f32(), -0.25);
+        assert_eq!((half * quarter).to_f32(), 0.125);
+        assert_eq!((-half).to_f32(), -0.5);
+        assert_eq!((half * MadFixed32::from(-1.0)).to_raw(), (-half).to_raw());
+
+        // Products are rounded to the nearest
+        assert_eq!((MadFixed32::new(3) * half).to_raw(), 2);
+        assert_eq!((MadFixed32::new(-3) * half).to_raw(), -1);
+
+        let mut sample = half;
+        sample += quarter;
+        sample -= half;
+        sample *= half;
+        assert_eq!(sample.to_f32(), 0.125);
+
+        let max = MadFixed32::new(i32::MAX);
+        let min = MadFixed32::new(i32::MIN);
+        assert_eq!(max.saturating_add(half).to_raw(), i32::MAX);
+        assert_eq!(min.saturating_sub(half).to_raw(), i32::MIN);
+        assert_eq!(max.saturating_mul(MadFixed32::from(2.0)).to_raw(), i32::MAX);
+        assert_eq!(max.saturating_mul(MadFixed32::from(-2.0)).to_raw(), i32::MIN);
+        assert_eq!(min.saturating_neg().to_raw(), i32::MAX);
+        assert_eq!(half.saturating_mul(half).to_f32(), 0.25);
+    }
+
+    #[test]
+    fn test_fixed_slices() {
+        let pcm = [0, 1, -1, 1000, i16::MAX, i16::MIN];
+        let fixed = MadFixed32::from_i16_slice(&pcm);
+        assert_eq!(fixed[1].to_raw(), 8192);
+        assert_eq!(MadFixed32::to_i16_vec(&fixed), pcm);
+
+        let floats = [0.0, 0.5, -0.25, 1.0, -1.0];
+        let fixed = MadFixed32::from_f32_slice(&floats);
+        assert_eq!(fixed[1].to_raw(), 1 << 27);
+        assert_eq!(MadFixed32::to_f32_vec(&fixed), floats);
+        assert!(MadFixed32::from_i16_slice(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_fixed_ordering() {
+        use std::collections::HashSet;
+
+        let mut samples = MadFixed32::from_f32_slice(&[0.5, -0.75, 0.0, 0.25, -0.75]);
+        assert_eq!(samples[0], MadFixed32::from(0.5));
+        assert!(samples[1] < samples[2]);
+        assert_eq!(samples.iter().max(), Some(&MadFixed32::from(0.5)));
+
+        samples.sort();
+        assert_eq!(MadFixed32::to_f32_vec(&samples), [-0.75, -0.75, 0.0, 0.25, 0.5]);
+        let distinct: HashSet<MadFixed32> = samples.into_iter().collect();
+        assert_eq!(distinct.len(), 4);
+    }
+
     #[allow(unused_variables)]
     #[test]
     fn test_readme_md() {
@@ -881,8 +4258,8 @@ mod test {
         use std::path::Path;
 
         let path = Path::new("sample_mp3s/constant_stereo_128.mp3");
-        let file = File::open(&path).unwrap();
-        let file2 = File::open(&path).unwrap();
+        let file = File::open(path).unwrap();
+        let file2 = File::open(path).unwrap();
         let decoder = Decoder::decode(file).unwrap();
 
         for decoding_result in decoder {
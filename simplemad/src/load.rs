@@ -0,0 +1,185 @@
+//! Decoding a whole stream in one call
+
+use std::io;
+use std::time::Duration;
+use {BitrateMode, Decoder, DecoderStats, ErrorPolicy, Frame, Mode, MpegVersion, SimplemadError,
+     StreamInfo};
+#[cfg(feature = "resample")]
+use {Channel, Downmix};
+
+/// A fully decoded stream, see `Decoder::decode_all`
+#[derive(Clone, Debug)]
+pub struct DecodedStream {
+    /// The properties of the stream, as far as they were seen while decoding
+    pub info: StreamInfo,
+    /// Every decoded frame
+    pub frames: Vec<Frame>,
+    /// Counts of the frames decoded, errors skipped and bytes read
+    pub stats: DecoderStats,
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    /// Decode a whole stream into memory
+    ///
+    /// Recoverable errors are skipped and counted in `DecodedStream::stats`,
+    /// any other error is returned. Returns `SimplemadError::EOF` if the
+    /// stream has no frames.
+    pub fn decode_all(reader: R) -> Result<DecodedStream, SimplemadError> {
+        let mut decoder = Decoder::builder(reader)
+                              .error_policy(ErrorPolicy::SkipRecoverable)
+                              .build()?;
+
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut flags = 0;
+        loop {
+            match decoder.get_frame() {
+                Ok(frame) => {
+                    if frames.is_empty() {
+                        flags = decoder.mad.frame.header.flags;
+                    }
+                    frames.push(frame);
+                }
+                Err(SimplemadError::EOF) => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        let info = {
+            let first = match frames.first() {
+                Some(first) => first,
+                None => return Err(SimplemadError::EOF),
+            };
+            let variable = match decoder.xing {
+                Some(xing) => xing.vbr,
+                None => frames.iter().any(|frame| frame.bit_rate != first.bit_rate),
+            };
+            StreamInfo {
+                sample_rate: first.sample_rate,
+                mode: first.mode,
+                channels: if first.mode == Mode::SingleChannel { 1 } else { 2 },
+                layer: first.layer,
+                version: MpegVersion::from_flags(flags),
+                bit_rate: first.bit_rate,
+                bitrate_mode: if variable {
+                    BitrateMode::Variable
+                } else {
+                    BitrateMode::Constant
+                },
+                duration: Some(frames.iter().fold(Duration::new(0, 0), |sum, f| sum + f.duration)),
+                audio_bytes: Some(decoder.sync_offset - first.byte_offset),
+            }
+        };
+
+        Ok(DecodedStream {
+            info,
+            frames,
+            stats: decoder.stats(),
+        })
+    }
+}
+
+/// The output format of `load_pcm`
+#[cfg(feature = "resample")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Sample rate of the output
+    pub rate: u32,
+    /// Number of output channels, 1 or 2
+    pub channels: usize,
+}
+
+/// Decode a whole stream to interleaved `f32` samples at a fixed sample
+/// rate and channel count
+///
+/// Stereo is downmixed to mono by averaging, mono is copied to both
+/// channels of a stereo output. Recoverable errors are skipped.
+///
+/// # Panics
+///
+/// Panics if `options.channels` isn't 1 or 2, or `options.rate` is 0.
+#[cfg(feature = "resample")]
+pub fn load_pcm<R>(reader: R, options: LoadOptions) -> Result<Vec<f32>, SimplemadError>
+    where R: io::Read
+{
+    assert!(options.rate > 0, "the sample rate must not be 0");
+    let builder = Decoder::builder(reader).error_policy(ErrorPolicy::SkipRecoverable);
+    let builder = match options.channels {
+        1 => builder.downmix(Downmix::Average),
+        2 => builder.channels(&[Channel::Left, Channel::Right]),
+        channels => panic!("can't load {} channels", channels),
+    };
+    let mut stream = builder.build()?.resample(options.rate);
+
+    let mut samples = Vec::new();
+    let mut buffer = [0.0; 4096];
+    loop {
+        match stream.next_samples(&mut buffer)? {
+            0 => return Ok(samples),
+            count => samples.extend_from_slice(&buffer[..count]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use {probe, Layer};
+
+    #[test]
+    fn test_decode_all() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let decoded = Decoder::decode_all(File::open(path).unwrap()).unwrap();
+        assert_eq!(decoded.frames.len(), 193);
+        assert_eq!(decoded.stats.frames_decoded, 193);
+        assert_eq!(decoded.info.layer, Layer::III);
+        assert_eq!(decoded.info.channels, 2);
+        assert_eq!(decoded.info.bitrate_mode, BitrateMode::Constant);
+
+        let probed = probe(File::open(path).unwrap()).unwrap();
+        assert_eq!(decoded.info.sample_rate, probed.sample_rate);
+        assert_eq!(decoded.info.version, probed.version);
+        assert_eq!(decoded.info.duration, probed.duration);
+        let bit_rate = decoded.info.average_bitrate().unwrap();
+        assert!((127_000..=129_000).contains(&bit_rate), "{}", bit_rate);
+
+        let path = "sample_mp3s/variable_stereo.mp3";
+        let decoded = Decoder::decode_all(File::open(path).unwrap()).unwrap();
+        assert!(decoded.info.is_vbr());
+    }
+
+    #[test]
+    #[cfg(feature = "resample")]
+    fn test_load_pcm() {
+        let load = |path: &str, rate, channels| {
+            load_pcm(File::open(path).unwrap(), LoadOptions { rate, channels }).unwrap()
+        };
+        let stereo = "sample_mp3s/constant_stereo_128.mp3";
+        let mono = "sample_mp3s/constant_single_channel_128.mp3";
+        let frames = 193 * 1152;
+
+        assert_eq!(load(stereo, 44100, 2).len(), frames * 2);
+        assert_eq!(load(stereo, 44100, 1).len(), frames);
+        let resampled = load(stereo, 22050, 1).len() as i64;
+        assert!((resampled - frames as i64 / 2).abs() <= 2, "{}", resampled);
+
+        // Mono is copied to both channels
+        let samples = load(mono, 48000, 2);
+        assert!(samples.chunks(2).all(|pair| pair[0] == pair[1]));
+        assert!(samples.iter().any(|&sample| sample != 0.0));
+        assert!(samples.iter().all(|sample| (-1.0..=1.0).contains(sample)));
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(feature = "resample")]
+    fn test_load_pcm_channels() {
+        let _ = load_pcm(io::empty(), LoadOptions { rate: 44100, channels: 6 });
+    }
+
+    #[test]
+    fn test_decode_all_empty() {
+        let error = Decoder::decode_all(io::empty()).unwrap_err();
+        assert_eq!(error, SimplemadError::EOF);
+    }
+}
@@ -0,0 +1,255 @@
+//! Gapless looping of a region of a seekable stream
+
+use std::io;
+use std::mem;
+use std::time::Duration;
+use {DecodeCheckpoint, Decoder, Frame, SimplemadError};
+
+/// An iterator that repeats a region of a stream without gaps
+///
+/// Created by `LoopingDecoder::new`. Frames are cut to the sample at the
+/// loop points, and decoding restarts from a checkpoint taken before the
+/// loop start, so the first frame of every repetition decodes exactly as
+/// it did the first time. The region can be changed with `set_loop` and
+/// `clear_loop` while iterating.
+pub struct LoopingDecoder<R>
+    where R: io::Read + io::Seek
+{
+    decoder: Option<Decoder<R>>,
+    region: Option<(Duration, Option<Duration>)>,
+    checkpoint: Option<DecodeCheckpoint>,
+    // Whether the checkpoint was taken on the first pass over the region
+    checkpoint_done: bool,
+    // Whether the region starts before the current position
+    restart: bool,
+    yielded: bool,
+    loops: u64,
+}
+
+impl<R> LoopingDecoder<R> where R: io::Read + io::Seek {
+    /// Loop `decoder` from `loop_start` to `loop_end`, or to the end of the
+    /// stream if `loop_end` is `None`
+    pub fn new(decoder: Decoder<R>,
+               loop_start: Duration,
+               loop_end: Option<Duration>)
+               -> LoopingDecoder<R> {
+        let mut looping = LoopingDecoder {
+            decoder: Some(decoder),
+            region: None,
+            checkpoint: None,
+            checkpoint_done: false,
+            restart: false,
+            yielded: false,
+            loops: 0,
+        };
+        looping.set_loop(loop_start, loop_end);
+        looping
+    }
+
+    /// Loop from `loop_start` to `loop_end`, or to the end of the stream if
+    /// `loop_end` is `None`
+    ///
+    /// If the current position is past `loop_start`, the stream is decoded
+    /// again from its start to find the loop start.
+    pub fn set_loop(&mut self, loop_start: Duration, loop_end: Option<Duration>) {
+        self.region = Some((loop_start, loop_end));
+        self.checkpoint = None;
+        self.checkpoint_done = false;
+        self.restart = self.decoder.as_ref().is_some_and(|d| d.position > loop_start);
+    }
+
+    /// Stop looping and continue to the end of the stream
+    pub fn clear_loop(&mut self) {
+        self.region = None;
+        self.restart = false;
+    }
+
+    /// Get the number of times the decoder has jumped back to the loop start
+    pub fn loops(&self) -> u64 {
+        self.loops
+    }
+
+    /// Get the underlying decoder
+    ///
+    /// Returns `None` if rewinding the input failed.
+    pub fn into_decoder(self) -> Option<Decoder<R>> {
+        self.decoder
+    }
+
+    fn rewind(&mut self) -> Result<(), SimplemadError> {
+        let decoder = match self.decoder.as_mut() {
+            Some(decoder) => decoder,
+            None => return Err(SimplemadError::EOF),
+        };
+        let checkpoint = match self.checkpoint {
+            Some(checkpoint) => checkpoint,
+            // The stream ended before the loop start
+            None => return Err(SimplemadError::EOF),
+        };
+
+        decoder.restore(checkpoint)?;
+        self.checkpoint_done = true;
+        self.loops += 1;
+        Ok(())
+    }
+
+    // Jump back to the loop start at the end of the region
+    fn wrap(&mut self) -> Option<Result<Frame, SimplemadError>> {
+        // Stop instead of spinning on an empty loop region
+        if !mem::replace(&mut self.yielded, false) {
+            return Some(Err(SimplemadError::EOF));
+        }
+        match self.rewind() {
+            Ok(()) => None,
+            Err(e) => {
+                self.decoder = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R> Iterator for LoopingDecoder<R> where R: io::Read + io::Seek {
+    type Item = Result<Frame, SimplemadError>;
+    fn next(&mut self) -> Option<Result<Frame, SimplemadError>> {
+        loop {
+            let (loop_start, loop_end) = match self.region {
+                Some(region) => region,
+                None => return self.decoder.as_mut()?.next(),
+            };
+
+            let result = {
+                let decoder = self.decoder.as_mut()?;
+                if mem::replace(&mut self.restart, false) {
+                    if let Err(e) = decoder.reset() {
+                        return Some(Err(e));
+                    }
+                }
+                // The last checkpoint at or before the loop start is the
+                // one before the frame containing it. Later passes start
+                // from it and have less data to warm up with.
+                if !self.checkpoint_done && decoder.position <= loop_start {
+                    self.checkpoint = Some(decoder.checkpoint());
+                }
+                decoder.get_frame()
+            };
+
+            let mut frame = match result {
+                Ok(frame) => frame,
+                Err(SimplemadError::EOF) => {
+                    match self.wrap() {
+                        Some(Err(SimplemadError::EOF)) => return None,
+                        Some(result) => return Some(result),
+                        None => continue,
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            };
+
+            let frame_end = frame.position + frame.duration;
+            if frame_end <= loop_start {
+                continue;
+            }
+            if frame.position < loop_start {
+                let offset = loop_start - frame.position;
+                frame = frame.split_at(offset).1;
+            }
+
+            if let Some(loop_end) = loop_end {
+                if frame.position >= loop_end {
+                    match self.wrap() {
+                        Some(Err(SimplemadError::EOF)) => return None,
+                        Some(result) => return Some(result),
+                        None => continue,
+                    }
+                }
+                if frame_end > loop_end {
+                    let offset = loop_end - frame.position;
+                    frame = frame.split_at(offset).0;
+                }
+            }
+
+            self.yielded = true;
+            return Some(Ok(frame));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+
+    fn raw(frame: &Frame) -> Vec<i32> {
+        frame.samples[0].iter().map(|s| s.to_raw()).collect()
+    }
+
+    #[test]
+    fn test_looping_decoder() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let mut looping = LoopingDecoder::new(decoder,
+                                              Duration::from_secs(1),
+                                              Some(Duration::from_secs(2)));
+
+        let pass = |looping: &mut LoopingDecoder<File>| {
+            let mut frames = Vec::new();
+            let mut samples = 0;
+            while samples < 44_100 {
+                let frame = looping.next().unwrap().unwrap();
+                samples += frame.n_samples();
+                frames.push(frame);
+            }
+            assert_eq!(samples, 44_100);
+            frames
+        };
+
+        let first = pass(&mut looping);
+        assert_eq!(looping.loops(), 0);
+        let second = pass(&mut looping);
+        assert_eq!(looping.loops(), 1);
+        assert_eq!(first.len(), second.len());
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.position, b.position);
+            assert_eq!(raw(a), raw(b));
+        }
+
+        // Looping to the end of the stream
+        let decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let mut looping = LoopingDecoder::new(decoder, Duration::from_secs(4), None);
+        let count = looping.by_ref().take(100).filter(|r| r.is_ok()).count();
+        assert_eq!(count, 100);
+        assert!(looping.loops() > 1);
+
+        // The decoder carries on across loops instead of starting over
+        let stats = looping.into_decoder().unwrap().stats();
+        assert!(stats.frames_decoded >= 100);
+        assert_eq!(stats.recoverable_errors, 0);
+    }
+
+    #[test]
+    fn test_set_loop() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let mut looping = LoopingDecoder::new(decoder,
+                                              Duration::from_secs(1),
+                                              Some(Duration::from_secs(2)));
+        let first = looping.next().unwrap().unwrap();
+        assert!(first.position > Duration::from_micros(999_999) &&
+                first.position <= Duration::from_secs(1));
+
+        // The new region starts behind the current position
+        looping.set_loop(Duration::from_millis(500), Some(Duration::from_millis(600)));
+        let region: Vec<Frame> = looping.by_ref().take(20).map(|r| r.unwrap()).collect();
+        assert!(region.iter().all(|f| {
+            f.position > Duration::from_micros(499_999) && f.position < Duration::from_millis(600)
+        }));
+        assert!(looping.loops() > 0);
+        let loops = looping.loops();
+
+        looping.clear_loop();
+        let rest = looping.by_ref().filter_map(|r| r.ok()).count();
+        assert!(rest > 100);
+        assert_eq!(looping.loops(), loops);
+    }
+}
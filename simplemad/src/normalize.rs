@@ -0,0 +1,76 @@
+//! Two-pass normalization to a loudness or peak target
+
+use std::io;
+use analysis;
+use {Decoder, SimplemadError};
+
+/// The level a stream is normalized to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalizationTarget {
+    /// Integrated loudness in LUFS, e.g. -16.0
+    Loudness(f64),
+    /// Largest absolute sample value in dBFS, e.g. -1.0
+    Peak(f64),
+}
+
+/// Measure a stream and compute the gain in dB that brings it to `target`
+///
+/// The gain is 0 for streams too quiet to measure. A loudness target may
+/// call for a gain that clips the loudest samples.
+pub fn normalization_gain<R>(reader: R, target: NormalizationTarget) -> Result<f32, SimplemadError>
+    where R: io::Read
+{
+    let loudness = analysis::loudness(reader)?;
+    let (level, target) = match target {
+        NormalizationTarget::Loudness(lufs) => (loudness.integrated, lufs),
+        NormalizationTarget::Peak(dbfs) => (loudness.peak, dbfs),
+    };
+
+    if level.is_finite() {
+        Ok((target - level) as f32)
+    } else {
+        Ok(0.0)
+    }
+}
+
+/// Measure a seekable stream, then return a decoder for it that applies
+/// the gain reaching `target`
+///
+/// The analysis pass decodes the stream from the reader's current
+/// position, which is restored for the returned decoder. Use
+/// `normalization_gain` to combine the gain with other options.
+pub fn normalize<R>(reader: R, target: NormalizationTarget) -> Result<Decoder<R>, SimplemadError>
+    where R: io::Read + io::Seek
+{
+    let mut reader = reader;
+    let start = reader.stream_position()?;
+    let gain_db = normalization_gain(&mut reader, target)?;
+    reader.seek(io::SeekFrom::Start(start))?;
+    Decoder::builder(reader).gain_db(gain_db).build()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_normalize() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let before = analysis::loudness(File::open(path).unwrap()).unwrap();
+
+        let target = NormalizationTarget::Peak(-6.0);
+        let gain_db = normalization_gain(File::open(path).unwrap(), target).unwrap();
+        assert!((f64::from(gain_db) - (-6.0 - before.peak)).abs() < 1e-3);
+
+        let decoder = normalize(File::open(path).unwrap(), target).unwrap();
+        let peak = decoder.filter_map(|r| r.ok())
+                          .map(|frame| frame.peak().to_f64())
+                          .fold(0.0, f64::max);
+        assert!((20.0 * peak.log10() + 6.0).abs() < 0.01);
+
+        let target = NormalizationTarget::Loudness(-30.0);
+        let gain_db = normalization_gain(File::open(path).unwrap(), target).unwrap();
+        assert!((f64::from(gain_db) - (-30.0 - before.integrated)).abs() < 1e-3);
+    }
+}
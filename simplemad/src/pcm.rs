@@ -0,0 +1,442 @@
+//! Continuous sample output without frame boundaries
+
+use std::cmp::min;
+use std::io;
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::mem;
+use {Decoder, Frame, Sample, SimplemadError};
+
+/// A stream of interleaved `f32` samples
+///
+/// Created by `Decoder::pcm_stream`. Frames are buffered internally, so
+/// samples can be read in any amount. Recoverable errors are skipped.
+pub struct PcmStream<R>
+    where R: io::Read
+{
+    decoder: Decoder<R>,
+    pending: Vec<f32>,
+    read: usize,
+    channels: usize,
+    sample_rate: u32,
+    error: Option<SimplemadError>,
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    /// Read the decoded audio as a continuous stream of samples
+    pub fn pcm_stream(self) -> PcmStream<R> {
+        PcmStream {
+            decoder: self,
+            pending: Vec::new(),
+            read: 0,
+            channels: 0,
+            sample_rate: 0,
+            error: None,
+        }
+    }
+
+    /// Iterate over the decoded samples one by one, interleaved and
+    /// converted to `T`
+    pub fn samples<T: Sample>(self) -> Samples<R, T> {
+        Samples {
+            decoder: self,
+            frame: None,
+            index: 0,
+            error: None,
+            format: PhantomData,
+        }
+    }
+
+    /// Read the decoded audio in chunks of `frames_per_chunk` samples per
+    /// channel
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frames_per_chunk` is 0.
+    pub fn pcm_chunks(self, frames_per_chunk: usize) -> PcmChunks<R> {
+        assert!(frames_per_chunk > 0, "chunks must not be empty");
+        PcmChunks {
+            stream: self.pcm_stream(),
+            frames_per_chunk,
+            pad_last: false,
+            chunk: Vec::new(),
+            filled: 0,
+            done: false,
+        }
+    }
+}
+
+/// An iterator over equally sized buffers of interleaved `f32` samples
+///
+/// Created by `Decoder::pcm_chunks`. The last chunk is shorter than the
+/// others unless padding was requested with `pad_last`. So is the chunk
+/// before a change in the number of channels, and `pad_last` pads it too.
+pub struct PcmChunks<R>
+    where R: io::Read
+{
+    stream: PcmStream<R>,
+    frames_per_chunk: usize,
+    pad_last: bool,
+    chunk: Vec<f32>,
+    filled: usize,
+    done: bool,
+}
+
+/// An iterator over individual interleaved samples
+///
+/// Created by `Decoder::samples`. Recoverable errors are skipped;
+/// iteration stops at the first other error, which is available from
+/// `error`. After `WouldBlock`, calling `next` again continues once the
+/// reader has data, as with `PcmStream::next_samples`.
+pub struct Samples<R, T>
+    where R: io::Read
+{
+    decoder: Decoder<R>,
+    frame: Option<Frame>,
+    index: usize,
+    error: Option<SimplemadError>,
+    format: PhantomData<T>,
+}
+
+impl<R> PcmStream<R> where R: io::Read {
+    /// Fill `out` with interleaved samples
+    ///
+    /// Returns the number of samples written, which is less than the length
+    /// of `out` only at the end of the stream or if the reader would block.
+    /// Returns `Ok(0)` once the stream is exhausted.
+    pub fn next_samples(&mut self, out: &mut [f32]) -> Result<usize, SimplemadError> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        let mut written = 0;
+        while written < out.len() {
+            if self.read == self.pending.len() {
+                match self.fill() {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(error) => {
+                        if written == 0 {
+                            return Err(error);
+                        }
+                        // Report the error with the next call
+                        if error != SimplemadError::WouldBlock {
+                            self.error = Some(error);
+                        }
+                        break;
+                    }
+                }
+            }
+
+            let count = min(out.len() - written, self.pending.len() - self.read);
+            out[written..written + count]
+                .copy_from_slice(&self.pending[self.read..self.read + count]);
+            written += count;
+            self.read += count;
+        }
+
+        Ok(written)
+    }
+
+    /// Get the number of channels of the most recent frame
+    ///
+    /// This is 0 until the first frame has been decoded.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Get the sample rate of the most recent frame
+    ///
+    /// This is 0 until the first frame has been decoded.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Get the underlying decoder, discarding buffered samples
+    pub fn into_decoder(self) -> Decoder<R> {
+        self.decoder
+    }
+
+    // Decode the next frame into `pending`, returns false at the end
+    fn fill(&mut self) -> Result<bool, SimplemadError> {
+        loop {
+            match self.decoder.get_frame() {
+                Ok(frame) => {
+                    self.buffer_frame(&frame);
+                    self.decoder.recycle(frame);
+                    return Ok(true);
+                }
+                Err(SimplemadError::EOF) => return Ok(false),
+                Err(ref e) if e.is_recoverable() => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn buffer_frame(&mut self, frame: &Frame) {
+        self.pending.clear();
+        self.read = 0;
+        self.channels = frame.samples.len();
+        self.sample_rate = frame.sample_rate;
+        frame.interleave(&mut self.pending);
+    }
+}
+
+impl<R> PcmChunks<R> where R: io::Read {
+    /// Pad the last chunk with silence to the full size
+    pub fn pad_last(mut self, pad_last: bool) -> PcmChunks<R> {
+        self.pad_last = pad_last;
+        self
+    }
+
+    /// Get the underlying decoder, discarding buffered samples
+    pub fn into_decoder(self) -> Decoder<R> {
+        self.stream.into_decoder()
+    }
+}
+
+impl<R> Iterator for PcmChunks<R> where R: io::Read {
+    type Item = Result<Vec<f32>, SimplemadError>;
+    fn next(&mut self) -> Option<Result<Vec<f32>, SimplemadError>> {
+        if self.done {
+            return None;
+        }
+
+        while self.chunk.is_empty() || self.filled < self.chunk.len() {
+            let stream = &mut self.stream;
+            if stream.read == stream.pending.len() {
+                match stream.fill() {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        self.done = true;
+                        break;
+                    }
+                    // Keep the partial chunk for the next call
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            // Chunks hold whole samples of one channel count, a change ends
+            // the chunk early
+            let len = self.frames_per_chunk * stream.channels;
+            if self.chunk.is_empty() {
+                self.chunk = vec![0.0; len];
+            } else if self.chunk.len() != len {
+                break;
+            }
+
+            let count = min(len - self.filled, stream.pending.len() - stream.read);
+            self.chunk[self.filled..self.filled + count]
+                .copy_from_slice(&stream.pending[stream.read..stream.read + count]);
+            self.filled += count;
+            stream.read += count;
+        }
+
+        let mut chunk = mem::take(&mut self.chunk);
+        let filled = mem::replace(&mut self.filled, 0);
+        match filled {
+            0 => None,
+            _ if self.pad_last => Some(Ok(chunk)),
+            filled => {
+                chunk.truncate(filled);
+                Some(Ok(chunk))
+            }
+        }
+    }
+}
+
+impl<R> FusedIterator for PcmChunks<R> where R: io::Read {}
+
+impl<R, T> Samples<R, T> where R: io::Read {
+    /// Get the error that ended iteration early, if any
+    pub fn error(&self) -> Option<&SimplemadError> {
+        self.error.as_ref()
+    }
+
+    /// Get the underlying decoder, discarding the rest of the current frame
+    pub fn into_decoder(self) -> Decoder<R> {
+        self.decoder
+    }
+}
+
+impl<R, T> Iterator for Samples<R, T>
+    where R: io::Read,
+          T: Sample
+{
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        loop {
+            if let Some(ref frame) = self.frame {
+                let channels = frame.samples.len();
+                if self.index < channels * frame.n_samples() {
+                    let sample = frame.samples[self.index % channels][self.index / channels];
+                    self.index += 1;
+                    return Some(T::from_fixed(sample));
+                }
+            }
+
+            self.error = None;
+            match self.decoder.get_frame() {
+                Ok(frame) => {
+                    if let Some(done) = self.frame.replace(frame) {
+                        self.decoder.recycle(done);
+                    }
+                    self.index = 0;
+                }
+                Err(ref e) if e.is_recoverable() => continue,
+                Err(SimplemadError::EOF) => return None,
+                Err(e) => {
+                    self.error = Some(e);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::{self, File};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_next_samples() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let expected: Vec<f32> = Decoder::decode(File::open(path).unwrap())
+                                     .unwrap()
+                                     .filter_map(|r| r.ok())
+                                     .flat_map(|frame| {
+                                         (0..frame.n_samples())
+                                             .flat_map(|i| {
+                                                 vec![frame.samples[0][i].to_f32(),
+                                                      frame.samples[1][i].to_f32()]
+                                             })
+                                             .collect::<Vec<f32>>()
+                                     })
+                                     .collect();
+
+        let mut stream = Decoder::decode(File::open(path).unwrap()).unwrap().pcm_stream();
+        assert_eq!(stream.channels(), 0);
+        let mut samples = Vec::new();
+        let mut buffer = [0.0; 1000];
+        loop {
+            match stream.next_samples(&mut buffer).unwrap() {
+                0 => break,
+                count => samples.extend_from_slice(&buffer[..count]),
+            }
+        }
+
+        assert_eq!(stream.channels(), 2);
+        assert_eq!(stream.sample_rate(), 44100);
+        assert_eq!(samples, expected);
+    }
+
+    #[test]
+    fn test_pcm_chunks() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let chunks: Vec<Vec<f32>> = decoder.pcm_chunks(1000).map(|r| r.unwrap()).collect();
+        let samples: usize = chunks.iter().map(|c| c.len()).sum();
+        let (last, full) = chunks.split_last().unwrap();
+        assert!(full.iter().all(|c| c.len() == 2000));
+        assert!(last.len() < 2000 && last.len() % 2 == 0);
+
+        let decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let chunks: Vec<Vec<f32>> = decoder.pcm_chunks(1000)
+                                           .pad_last(true)
+                                           .map(|r| r.unwrap())
+                                           .collect();
+        assert!(chunks.iter().all(|c| c.len() == 2000));
+        assert!(chunks.last().unwrap()[samples % 2000..].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_pcm_chunks_channel_change() {
+        let mut data = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        data.extend(fs::read("sample_mp3s/constant_single_channel_128.mp3").unwrap());
+        let frames: Vec<Frame> = Decoder::decode(Cursor::new(data.clone()))
+                                     .unwrap()
+                                     .filter_map(|r| r.ok())
+                                     .collect();
+        let len = |channels| {
+            frames.iter()
+                  .filter(|frame| frame.n_channels() == channels)
+                  .map(|frame| channels * frame.n_samples())
+                  .sum::<usize>()
+        };
+        let (stereo_len, mono_len) = (len(2), len(1));
+        assert!(frames.iter()
+                      .skip_while(|frame| frame.n_channels() == 2)
+                      .all(|frame| frame.n_channels() == 1));
+
+        // The stereo part ends with a short chunk, the mono part starts a new
+        // one
+        let decoder = Decoder::decode(Cursor::new(data)).unwrap();
+        let chunks: Vec<Vec<f32>> = decoder.pcm_chunks(1000).map(|r| r.unwrap()).collect();
+        let split = stereo_len.div_ceil(2000);
+        assert!(chunks[..split - 1].iter().all(|c| c.len() == 2000));
+        assert_eq!(chunks[split - 1].len(), stereo_len - (split - 1) * 2000);
+        assert!(chunks[split..chunks.len() - 1].iter().all(|c| c.len() == 1000));
+        assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), stereo_len + mono_len);
+    }
+
+    #[test]
+    fn test_samples() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let mut stream = Decoder::decode(File::open(path).unwrap()).unwrap().pcm_stream();
+        let mut expected = vec![0.0; 10_000];
+        assert_eq!(stream.next_samples(&mut expected).unwrap(), 10_000);
+
+        let decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let samples: Vec<f32> = decoder.samples::<f32>().take(10_000).collect();
+        assert_eq!(samples, expected);
+
+        let decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+        let peak = decoder.samples::<i16>().map(|s| s.unsigned_abs()).max().unwrap();
+        assert!(peak > 1_000);
+    }
+
+    // Returns `WouldBlock` from every other read
+    struct NonBlocking {
+        inner: Cursor<Vec<u8>>,
+        reads: usize,
+    }
+
+    impl io::Read for NonBlocking {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            if self.reads.is_multiple_of(2) {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "no data"));
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_samples_would_block() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let expected: Vec<i16> = Decoder::decode(File::open(path).unwrap())
+                                     .unwrap()
+                                     .samples()
+                                     .collect();
+
+        let reader = NonBlocking {
+            inner: Cursor::new(fs::read(path).unwrap()),
+            reads: 0,
+        };
+        let mut samples = Decoder::decode(reader).unwrap().samples::<i16>();
+        let mut collected = Vec::new();
+        let mut blocks = 0;
+        loop {
+            match samples.next() {
+                Some(sample) => collected.push(sample),
+                None if samples.error() == Some(&SimplemadError::WouldBlock) => blocks += 1,
+                None => break,
+            }
+        }
+        assert!(blocks > 0);
+        assert!(samples.error().is_none());
+        assert_eq!(collected, expected);
+    }
+}
@@ -0,0 +1,313 @@
+//! Quick inspection of a stream's properties
+
+use std::io;
+use std::os::raw::c_int;
+use std::time::Duration;
+use simplemad_sys::*;
+use {Decoder, ErrorPolicy, Layer, Mode, SimplemadError};
+
+// Headers compared to tell constant and variable bit rates apart when the
+// stream has no Xing, Info or VBRI header
+const SCAN_FRAMES: usize = 16;
+
+/// Samples per channel in a Layer I frame
+pub const LAYER_I_SAMPLES: usize = 384;
+/// Samples per channel in a Layer II frame
+pub const LAYER_II_SAMPLES: usize = 1152;
+/// Samples per channel in an MPEG-1 Layer III frame
+pub const LAYER_III_SAMPLES: usize = 1152;
+/// Samples per channel in an MPEG-2 or MPEG-2.5 Layer III frame
+pub const LAYER_III_LSF_SAMPLES: usize = 576;
+
+/// The MPEG standard a stream conforms to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MpegVersion {
+    /// MPEG-1, 32 to 48 kHz
+    Mpeg1,
+    /// MPEG-2 low sampling frequencies, 16 to 24 kHz
+    Mpeg2,
+    /// The unofficial MPEG-2.5 extension, 8 to 12 kHz
+    Mpeg25,
+}
+
+impl MpegVersion {
+    /// Get the version from libmad's `MAD_FLAG_*` header bits
+    pub fn from_flags(flags: c_int) -> MpegVersion {
+        if flags & MAD_FLAG_MPEG_2_5_EXT != 0 {
+            MpegVersion::Mpeg25
+        } else if flags & MAD_FLAG_LSF_EXT != 0 {
+            MpegVersion::Mpeg2
+        } else {
+            MpegVersion::Mpeg1
+        }
+    }
+
+    /// Get the version that has `sample_rate` as one of its rates
+    pub fn from_sample_rate(sample_rate: u32) -> Option<MpegVersion> {
+        match sample_rate {
+            32000 | 44100 | 48000 => Some(MpegVersion::Mpeg1),
+            16000 | 22050 | 24000 => Some(MpegVersion::Mpeg2),
+            8000 | 11025 | 12000 => Some(MpegVersion::Mpeg25),
+            _ => None,
+        }
+    }
+}
+
+/// Get the number of samples per channel in a frame of `layer` and `version`
+pub fn samples_per_frame(layer: Layer, version: MpegVersion) -> usize {
+    match (layer, version) {
+        (Layer::I, _) => LAYER_I_SAMPLES,
+        (Layer::II, _) => LAYER_II_SAMPLES,
+        (Layer::III, MpegVersion::Mpeg1) => LAYER_III_SAMPLES,
+        (Layer::III, _) => LAYER_III_LSF_SAMPLES,
+    }
+}
+
+/// Whether every frame of a stream has the same bit rate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitrateMode {
+    /// Constant bit rate
+    Constant,
+    /// Variable bit rate
+    Variable,
+}
+
+/// The properties of a stream, see `probe`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// Number of samples per second
+    pub sample_rate: u32,
+    /// Single Channel, Dual Channel, Joint Stereo or Stereo
+    pub mode: Mode,
+    /// Number of channels
+    pub channels: u32,
+    /// Audio layer (I, II or III)
+    pub layer: Layer,
+    /// MPEG version
+    pub version: MpegVersion,
+    /// Bit rate of the first frame
+    pub bit_rate: u32,
+    /// Constant or variable bit rate
+    pub bitrate_mode: BitrateMode,
+    /// Estimated duration of the stream, if its length is known
+    pub duration: Option<Duration>,
+    /// Length of the audio data in bytes, if known
+    pub audio_bytes: Option<u64>,
+}
+
+impl StreamInfo {
+    /// Check whether the stream has a variable bit rate
+    pub fn is_vbr(&self) -> bool {
+        self.bitrate_mode == BitrateMode::Variable
+    }
+
+    /// Get the average bit rate, if the length of the stream is known
+    pub fn average_bitrate(&self) -> Option<u32> {
+        let nanos = self.duration?.as_nanos();
+        if nanos == 0 {
+            return None;
+        }
+        Some((u128::from(self.audio_bytes?) * 8 * 1_000_000_000 / nanos) as u32)
+    }
+}
+
+/// Read the properties of a stream from its first frames
+///
+/// Uses the Xing, Info or VBRI header if there is one, otherwise compares
+/// the bit rates of the first few frames. The duration of a constant bit
+/// rate stream is estimated from the length of the input, a variable bit
+/// rate stream is scanned to the end.
+pub fn probe<R>(reader: R) -> Result<StreamInfo, SimplemadError>
+    where R: io::Read + io::Seek
+{
+    let mut headers = Decoder::builder(reader)
+                          .measure_input_len()?
+                          .error_policy(ErrorPolicy::SkipRecoverable)
+                          .build()?
+                          .headers();
+
+    let first = match headers.next() {
+        Some(result) => result?,
+        None => return Err(SimplemadError::EOF),
+    };
+
+    let decoder = &headers.decoder;
+    let mut duration = decoder.total_len().map(|frames| first.duration * frames as u32);
+    let xing = decoder.xing;
+    let audio_bytes = match xing.and_then(|xing| xing.bytes) {
+        Some(bytes) => Some(u64::from(bytes)),
+        None => decoder.options.input_len.map(|len| len.saturating_sub(first.byte_offset)),
+    };
+
+    let bitrate_mode = match xing {
+        Some(xing) if xing.vbr => BitrateMode::Variable,
+        Some(_) => BitrateMode::Constant,
+        None => {
+            let mut scanned = first.duration;
+            let mut variable = false;
+            for header in headers.by_ref().take(SCAN_FRAMES).filter_map(|r| r.ok()) {
+                scanned += header.duration;
+                variable = variable || header.bit_rate != first.bit_rate;
+            }
+            if variable {
+                for header in headers.filter_map(|r| r.ok()) {
+                    scanned += header.duration;
+                }
+                duration = Some(scanned);
+                BitrateMode::Variable
+            } else {
+                BitrateMode::Constant
+            }
+        }
+    };
+
+    Ok(StreamInfo {
+        sample_rate: first.sample_rate,
+        mode: first.mode,
+        channels: if first.mode == Mode::SingleChannel { 1 } else { 2 },
+        layer: first.layer,
+        version: MpegVersion::from_flags(first.flags),
+        bit_rate: first.bit_rate,
+        bitrate_mode,
+        duration,
+        audio_bytes,
+    })
+}
+
+/// Get the duration of a stream
+///
+/// Uses the frame count of a Xing, Info or VBRI header if there is one,
+/// otherwise adds up the durations of all frame headers.
+pub fn duration<R>(reader: R) -> Result<Duration, SimplemadError>
+    where R: io::Read
+{
+    let mut headers = Decoder::builder(reader)
+                          .error_policy(ErrorPolicy::SkipRecoverable)
+                          .build()?
+                          .headers();
+
+    let first = match headers.next() {
+        Some(result) => result?,
+        None => return Err(SimplemadError::EOF),
+    };
+
+    if let Some(frames) = headers.decoder.xing.and_then(|xing| xing.frames) {
+        return Ok(first.duration * frames);
+    }
+
+    let mut total = first.duration;
+    for header in headers {
+        total += header?.duration;
+    }
+    Ok(total)
+}
+
+/// Get the number of samples per channel in a stream
+///
+/// Uses the frame count of a Xing, Info or VBRI header if there is one,
+/// otherwise counts the samples of all frame headers. The encoder delay and
+/// padding recorded in a LAME tag are not counted.
+pub fn total_samples<R>(reader: R) -> Result<u64, SimplemadError>
+    where R: io::Read
+{
+    let mut headers = Decoder::builder(reader)
+                          .error_policy(ErrorPolicy::SkipRecoverable)
+                          .build()?
+                          .headers();
+
+    let first = match headers.next() {
+        Some(result) => result?,
+        None => return Err(SimplemadError::EOF),
+    };
+
+    let xing = headers.decoder.xing;
+    let total = match xing.and_then(|xing| xing.frames) {
+        Some(frames) => u64::from(frames) * first.samples_per_frame() as u64,
+        None => {
+            let mut total = first.samples_per_frame() as u64;
+            for header in headers {
+                total += header?.samples_per_frame() as u64;
+            }
+            total
+        }
+    };
+
+    let trimmed = xing.map_or(0, |xing| u64::from(xing.delay + xing.padding));
+    Ok(total.saturating_sub(trimmed))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+
+    fn probe_file(path: &str) -> StreamInfo {
+        probe(File::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_probe() {
+        let info = probe_file("sample_mp3s/constant_stereo_128.mp3");
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.layer, Layer::III);
+        assert_eq!(info.version, MpegVersion::Mpeg1);
+        assert_eq!(info.bitrate_mode, BitrateMode::Constant);
+        assert_eq!(info.duration.unwrap().as_millis(), 5041);
+
+        let info = probe_file("sample_mp3s/variable_stereo.mp3");
+        assert_eq!(info.bitrate_mode, BitrateMode::Variable);
+        assert!(info.is_vbr());
+
+        let info = probe_file("sample_mp3s/constant_single_channel_128.mp3");
+        assert_eq!(info.channels, 1);
+
+        let info = probe_file("sample_mp3s/constant_stereo_16.mp3");
+        assert_eq!(info.version, MpegVersion::Mpeg2);
+        assert_eq!(info.sample_rate, 24000);
+
+        // No Xing header, the duration is estimated from the file size
+        let info = probe_file("sample_mp3s/rodiomusic.mp3");
+        assert_eq!(info.bitrate_mode, BitrateMode::Constant);
+        assert_eq!(info.duration.unwrap().as_secs(), 32);
+    }
+
+    #[test]
+    fn test_duration() {
+        let xing = duration(File::open("sample_mp3s/constant_stereo_128.mp3").unwrap()).unwrap();
+        assert_eq!(xing.as_millis(), 5041);
+
+        let scan = duration(File::open("sample_mp3s/rodiomusic.mp3").unwrap()).unwrap();
+        let headers = Decoder::decode(File::open("sample_mp3s/rodiomusic.mp3").unwrap())
+                          .unwrap()
+                          .headers();
+        let total = headers.filter_map(|r| r.ok())
+                           .fold(Duration::new(0, 0), |acc, h| acc + h.duration);
+        assert_eq!(scan, total);
+    }
+
+    #[test]
+    fn test_total_samples() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        assert_eq!(total_samples(File::open(path).unwrap()).unwrap(), 193 * 1152 - 576 - 704);
+
+        let path = "sample_mp3s/constant_stereo_16.mp3";
+        let frames = Decoder::decode(File::open(path).unwrap())
+                         .unwrap()
+                         .filter(|r| r.is_ok())
+                         .count() as u64;
+        assert_eq!(total_samples(File::open(path).unwrap()).unwrap(), frames * 576);
+    }
+
+    #[test]
+    fn test_average_bitrate() {
+        let info = probe_file("sample_mp3s/constant_stereo_128.mp3");
+        assert!(!info.is_vbr());
+        let average = info.average_bitrate().unwrap();
+        assert!(average > 127_000 && average < 129_500, "{}", average);
+
+        let info = probe_file("sample_mp3s/variable_stereo.mp3");
+        let average = info.average_bitrate().unwrap();
+        assert!(average > 225_000 && average < 235_000, "{}", average);
+    }
+}
@@ -0,0 +1,150 @@
+//! Per-frame processing applied by the decoder
+
+use std::mem;
+use std::time::Duration;
+use builder::{self, Downmix};
+use Frame;
+
+/// A processing step applied to every decoded frame
+///
+/// Add processors with `DecoderBuilder::with_processor`. They run in the
+/// order they were added, before the frame is returned or observed.
+pub trait Processor: Send {
+    /// Modify `frame` in place
+    fn process(&mut self, frame: &mut Frame);
+}
+
+impl<F> Processor for F where F: FnMut(&mut Frame) + Send {
+    fn process(&mut self, frame: &mut Frame) {
+        self(frame)
+    }
+}
+
+/// A processor that amplifies or attenuates the samples
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gain {
+    factor: i64,
+}
+
+impl Gain {
+    /// Create a processor applying `gain_db` decibels
+    pub fn new(gain_db: f32) -> Gain {
+        Gain { factor: builder::db_to_factor(gain_db) }
+    }
+}
+
+impl Processor for Gain {
+    fn process(&mut self, frame: &mut Frame) {
+        for sample in frame.samples.iter_mut().flat_map(|channel| channel.iter_mut()) {
+            sample.value = builder::apply_gain(sample.value, self.factor);
+        }
+    }
+}
+
+impl Processor for Downmix {
+    fn process(&mut self, frame: &mut Frame) {
+        if frame.samples.len() != 2 {
+            return;
+        }
+        let right = frame.samples.pop().unwrap();
+        for (left, right) in frame.samples[0].iter_mut().zip(right) {
+            left.value = self.mix(left.value, right.value);
+        }
+    }
+}
+
+/// A processor that removes the samples outside of an interval
+///
+/// Unlike `DecoderBuilder::interval`, which works with whole frames, the
+/// interval is cut to the sample. Frames entirely outside of it are left
+/// without samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Trim {
+    /// The position of the first sample to keep
+    pub start: Duration,
+    /// The position after the last sample to keep, `None` to keep the rest
+    pub end: Option<Duration>,
+}
+
+impl Trim {
+    /// Create a processor keeping the samples from `start` to `end`
+    pub fn new(start: Duration, end: Option<Duration>) -> Trim {
+        Trim { start, end }
+    }
+}
+
+impl Processor for Trim {
+    fn process(&mut self, frame: &mut Frame) {
+        let samples = mem::take(&mut frame.samples);
+        let mut trimmed = frame.clone();
+        trimmed.samples = samples;
+
+        if let Some(end) = self.end {
+            let offset = end.checked_sub(trimmed.position).unwrap_or_default();
+            trimmed = trimmed.split_at(offset).0;
+        }
+        if let Some(offset) = self.start.checked_sub(trimmed.position) {
+            trimmed = trimmed.split_at(offset).1;
+        }
+        *frame = trimmed;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use Decoder;
+
+    const PATH: &str = "sample_mp3s/constant_stereo_128.mp3";
+
+    #[test]
+    fn test_builtin_processors() {
+        let with_options = Decoder::builder(File::open(PATH).unwrap())
+                               .gain_db(-6.0)
+                               .downmix(Downmix::Average)
+                               .build()
+                               .unwrap()
+                               .find_map(|r| r.ok())
+                               .unwrap();
+        let with_processors = Decoder::builder(File::open(PATH).unwrap())
+                                  .with_processor(Downmix::Average)
+                                  .with_processor(Gain::new(-6.0))
+                                  .build()
+                                  .unwrap()
+                                  .find_map(|r| r.ok())
+                                  .unwrap();
+        assert_eq!(with_processors.n_channels(), 1);
+        assert_eq!(with_processors.samples[0].len(), with_options.samples[0].len());
+        for (a, b) in with_processors.samples[0].iter().zip(&with_options.samples[0]) {
+            assert_eq!(a.to_raw(), b.to_raw());
+        }
+    }
+
+    #[test]
+    fn test_trim() {
+        let frames: Vec<Frame> = Decoder::builder(File::open(PATH).unwrap())
+                                     .with_processor(Trim::new(Duration::from_secs(1),
+                                                               Some(Duration::from_secs(2))))
+                                     .build()
+                                     .unwrap()
+                                     .filter_map(|r| r.ok())
+                                     .filter(|f| f.n_samples() > 0)
+                                     .collect();
+        let samples: usize = frames.iter().map(|f| f.n_samples()).sum();
+        assert!((44_099..=44_101).contains(&samples));
+        assert!(frames[0].position >= Duration::from_millis(999));
+        assert!(frames[0].position <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_closure_processor() {
+        let frame = Decoder::builder(File::open(PATH).unwrap())
+                        .with_processor(|frame: &mut Frame| frame.samples.truncate(1))
+                        .build()
+                        .unwrap()
+                        .find_map(|r| r.ok())
+                        .unwrap();
+        assert_eq!(frame.n_channels(), 1);
+    }
+}
@@ -0,0 +1,370 @@
+//! Sample rate conversion with a windowed-sinc filter
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::io;
+use {Decoder, Frame, SimplemadError};
+
+// Zero crossings of the sinc function on each side of a tap
+const HALF_TAPS: usize = 16;
+// Table entries per input sample
+const TABLE_RESOLUTION: usize = 512;
+// Cutoff relative to the lower Nyquist frequency, leaving room for the
+// filter's transition band
+const CUTOFF: f64 = 0.95;
+
+/// A streaming sample rate converter for interleaved `f32` samples
+///
+/// Uses a Blackman-windowed sinc filter, which low-pass filters the signal
+/// when converting to a lower rate.
+#[derive(Clone, Debug)]
+pub struct Resampler {
+    from: u32,
+    to: u32,
+    channels: usize,
+    // Input samples per output sample
+    step: f64,
+    table: Vec<f32>,
+    // Input not yet consumed, per channel, starting at input index `base`
+    history: Vec<VecDeque<f32>>,
+    base: u64,
+    // Input index of the next output sample
+    time: f64,
+    input_len: u64,
+    output_len: u64,
+}
+
+impl Resampler {
+    /// Convert `channels` interleaved channels from `from` Hz to `to` Hz
+    ///
+    /// # Panics
+    ///
+    /// Panics if either rate or `channels` is 0.
+    pub fn new(from: u32, to: u32, channels: usize) -> Resampler {
+        assert!(from > 0 && to > 0 && channels > 0);
+        let cutoff = CUTOFF * (f64::from(to) / f64::from(from)).min(1.0);
+        let table = (0..HALF_TAPS * TABLE_RESOLUTION + 2)
+                        .map(|i| kernel(i as f64 / TABLE_RESOLUTION as f64, cutoff) as f32)
+                        .collect();
+        let mut history = vec![VecDeque::new(); channels];
+        // Silence before the start
+        for channel in &mut history {
+            channel.extend(std::iter::repeat_n(0.0, HALF_TAPS));
+        }
+
+        Resampler {
+            from,
+            to,
+            channels,
+            step: f64::from(from) / f64::from(to),
+            table,
+            history,
+            base: 0,
+            time: HALF_TAPS as f64,
+            input_len: 0,
+            output_len: 0,
+        }
+    }
+
+    /// Get the input sample rate
+    pub fn from_rate(&self) -> u32 {
+        self.from
+    }
+
+    /// Get the output sample rate
+    pub fn to_rate(&self) -> u32 {
+        self.to
+    }
+
+    /// Get the number of channels
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Convert `input` and append the result to `output`
+    ///
+    /// Output lags behind the input by a few samples, `flush` returns the
+    /// rest at the end of the stream.
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        for frame in input.chunks(self.channels) {
+            for (channel, &sample) in self.history.iter_mut().zip(frame) {
+                channel.push_back(sample);
+            }
+        }
+        self.input_len += (input.len() / self.channels) as u64;
+        self.produce(output, None);
+    }
+
+    /// Append the remaining output at the end of the stream to `output`
+    ///
+    /// The total output is the input length scaled by the rate ratio. The
+    /// resampler can be reused for a new stream afterwards.
+    pub fn flush(&mut self, output: &mut Vec<f32>) {
+        let expected = (u128::from(self.input_len) * u128::from(self.to))
+                           .div_ceil(u128::from(self.from));
+        for channel in &mut self.history {
+            channel.extend(std::iter::repeat_n(0.0, 2 * HALF_TAPS));
+        }
+        self.produce(output, Some(expected as u64));
+        *self = Resampler::new(self.from, self.to, self.channels);
+    }
+
+    fn produce(&mut self, output: &mut Vec<f32>, limit: Option<u64>) {
+        let available = self.base + self.history[0].len() as u64;
+        while limit.is_none_or(|limit| self.output_len < limit) {
+            let center = self.time.floor() as u64;
+            if center + HALF_TAPS as u64 >= available {
+                break;
+            }
+
+            let first = center + 1 - HALF_TAPS as u64;
+            for channel in &self.history {
+                let mut sum = 0.0;
+                for tap in 0..2 * HALF_TAPS {
+                    let index = first + tap as u64;
+                    let distance = (self.time - index as f64).abs();
+                    sum += channel[(index - self.base) as usize] * self.weight(distance);
+                }
+                output.push(sum);
+            }
+
+            self.output_len += 1;
+            self.time = HALF_TAPS as f64 + self.output_len as f64 * self.step;
+        }
+
+        // Drop input that no later output sample needs
+        let keep_from = (self.time.floor() as u64 + 1).saturating_sub(HALF_TAPS as u64);
+        let drop = keep_from.saturating_sub(self.base).min(self.history[0].len() as u64);
+        for channel in &mut self.history {
+            channel.drain(..drop as usize);
+        }
+        self.base += drop;
+    }
+
+    // The filter kernel at `distance` input samples, interpolated from the
+    // table
+    fn weight(&self, distance: f64) -> f32 {
+        let position = distance * TABLE_RESOLUTION as f64;
+        let index = position as usize;
+        if index + 1 >= self.table.len() {
+            return 0.0;
+        }
+        let fraction = (position - index as f64) as f32;
+        self.table[index] + (self.table[index + 1] - self.table[index]) * fraction
+    }
+}
+
+// Windowed sinc at `distance` input samples from the center
+fn kernel(distance: f64, cutoff: f64) -> f64 {
+    if distance >= HALF_TAPS as f64 {
+        return 0.0;
+    }
+    let x = PI * cutoff * distance;
+    let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+    let w = PI * distance / HALF_TAPS as f64;
+    let window = 0.42 + 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+    cutoff * sinc * window
+}
+
+/// A stream of interleaved `f32` samples at a fixed sample rate
+///
+/// Created by `Decoder::resample`. Recoverable errors are skipped. When
+/// the input's sample rate changes, e.g. between chained streams, the
+/// resampler is reconfigured and the output rate stays the same.
+pub struct ResampledStream<R>
+    where R: io::Read
+{
+    decoder: Decoder<R>,
+    rate: u32,
+    resampler: Option<Resampler>,
+    output: VecDeque<f32>,
+    scratch: Vec<f32>,
+    rate_changes: u64,
+    done: bool,
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    /// Read the decoded audio converted to `rate` Hz
+    pub fn resample(self, rate: u32) -> ResampledStream<R> {
+        ResampledStream {
+            decoder: self,
+            rate,
+            resampler: None,
+            output: VecDeque::new(),
+            scratch: Vec::new(),
+            rate_changes: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R> ResampledStream<R> where R: io::Read {
+    /// Fill `out` with interleaved samples
+    ///
+    /// Behaves like `PcmStream::next_samples`, returning `Ok(0)` once the
+    /// stream is exhausted.
+    pub fn next_samples(&mut self, out: &mut [f32]) -> Result<usize, SimplemadError> {
+        while self.output.len() < out.len() && !self.done {
+            match self.decoder.get_frame() {
+                Ok(frame) => self.push_frame(&frame),
+                Err(SimplemadError::EOF) => {
+                    self.done = true;
+                    if let Some(ref mut resampler) = self.resampler {
+                        self.scratch.clear();
+                        resampler.flush(&mut self.scratch);
+                        self.output.extend(&self.scratch);
+                    }
+                }
+                Err(SimplemadError::WouldBlock) if !self.output.is_empty() => break,
+                Err(ref e) if e.is_recoverable() => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let count = self.output.len().min(out.len());
+        for (sample, resampled) in out.iter_mut().zip(self.output.drain(..count)) {
+            *sample = resampled;
+        }
+        Ok(count)
+    }
+
+    /// Get the output sample rate
+    pub fn sample_rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Get the number of channels
+    ///
+    /// This is 0 until the first frame has been decoded.
+    pub fn channels(&self) -> usize {
+        self.resampler.as_ref().map_or(0, |resampler| resampler.channels())
+    }
+
+    /// Get the number of times the input format changed and the resampler
+    /// was reconfigured
+    pub fn rate_changes(&self) -> u64 {
+        self.rate_changes
+    }
+
+    /// Get the underlying decoder, discarding buffered samples
+    pub fn into_decoder(self) -> Decoder<R> {
+        self.decoder
+    }
+
+    fn push_frame(&mut self, frame: &Frame) {
+        let channels = frame.samples.len();
+        if channels == 0 {
+            return;
+        }
+        let changed = self.resampler.as_ref().is_some_and(|resampler| {
+            resampler.from_rate() != frame.sample_rate || resampler.channels() != channels
+        });
+        if changed {
+            // Finish the previous part at its own rate
+            if let Some(ref mut resampler) = self.resampler {
+                self.scratch.clear();
+                resampler.flush(&mut self.scratch);
+                self.output.extend(&self.scratch);
+            }
+            self.resampler = None;
+            self.rate_changes += 1;
+        }
+
+        let rate = self.rate;
+        let resampler = self.resampler
+                            .get_or_insert_with(|| Resampler::new(frame.sample_rate, rate, channels));
+
+        let mut interleaved = Vec::new();
+        frame.interleave(&mut interleaved);
+        self.scratch.clear();
+        resampler.process(&interleaved, &mut self.scratch);
+        self.output.extend(&self.scratch);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::{self, File};
+    use std::io::Cursor;
+
+    fn sine(rate: u32, frequency: f64, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (2.0 * PI * frequency * i as f64 / f64::from(rate)).sin() as f32 * 0.5)
+            .collect()
+    }
+
+    #[test]
+    fn test_resampler() {
+        for &(from, to) in &[(44_100, 48_000), (48_000, 22_050), (32_000, 32_000)] {
+            let input = sine(from, 1_000.0, from as usize);
+            let mut resampler = Resampler::new(from, to, 1);
+            let mut output = Vec::new();
+            for chunk in input.chunks(1_000) {
+                resampler.process(chunk, &mut output);
+            }
+            resampler.flush(&mut output);
+
+            assert_eq!(output.len(), to as usize);
+            let expected = sine(to, 1_000.0, to as usize);
+            // Away from the edges the output matches the ideal signal
+            let margin = 100;
+            for (a, b) in output[margin..output.len() - margin]
+                              .iter()
+                              .zip(&expected[margin..]) {
+                assert!((a - b).abs() < 0.01, "{} Hz to {} Hz: {} != {}", from, to, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_resampled_stream() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let frames = Decoder::decode(File::open(path).unwrap())
+                         .unwrap()
+                         .filter_map(|r| r.ok())
+                         .map(|f| f.n_samples())
+                         .sum::<usize>();
+
+        let mut stream = Decoder::decode(File::open(path).unwrap()).unwrap().resample(48_000);
+        let mut buffer = [0.0; 1_000];
+        let mut total = 0;
+        loop {
+            match stream.next_samples(&mut buffer).unwrap() {
+                0 => break,
+                count => total += count,
+            }
+        }
+
+        assert_eq!(stream.channels(), 2);
+        assert_eq!(stream.sample_rate(), 48_000);
+        let expected = (frames * 48_000).div_ceil(44_100);
+        assert_eq!(total, expected * 2);
+    }
+
+    #[test]
+    fn test_rate_change() {
+        let mut data = fs::read("sample_mp3s/constant_stereo_128.mp3").unwrap();
+        data.extend(fs::read("sample_mp3s/constant_stereo_16.mp3").unwrap());
+
+        let mut lengths = [0; 2];
+        let decoder = Decoder::decode(Cursor::new(&data)).unwrap();
+        for frame in decoder.filter_map(|r| r.ok()) {
+            lengths[(frame.sample_rate == 24_000) as usize] += frame.n_samples();
+        }
+
+        let mut stream = Decoder::decode(Cursor::new(&data)).unwrap().resample(48_000);
+        let mut buffer = [0.0; 1_000];
+        let mut total = 0;
+        loop {
+            match stream.next_samples(&mut buffer).unwrap() {
+                0 => break,
+                count => total += count,
+            }
+        }
+
+        assert_eq!(stream.rate_changes(), 1);
+        let expected = (lengths[0] * 48_000).div_ceil(44_100) + lengths[1] * 2;
+        assert_eq!(total, expected * 2);
+    }
+}
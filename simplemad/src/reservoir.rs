@@ -0,0 +1,104 @@
+//! Tracking of the Layer III bit reservoir, the audio data frames borrow
+//! from the frames before them
+
+use MAX_MAIN_DATA_BEGIN;
+
+/// The audio data available to the next frame
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Reservoir {
+    // Bytes of audio data in the frames seen since the last gap, up to
+    // `MAX_MAIN_DATA_BEGIN`
+    available: u32,
+    // Offset of the end of the last frame
+    end_offset: Option<u64>,
+}
+
+impl Reservoir {
+    /// Account for the frame at `offset` and check whether the data it
+    /// borrows has been seen
+    ///
+    /// Returns the frame's `main_data_begin` and whether the reservoir had
+    /// that much data.
+    pub fn push(&mut self, offset: u64, frame: &[u8]) -> (u32, bool) {
+        if self.end_offset != Some(offset) {
+            self.available = 0;
+        }
+        self.end_offset = Some(offset + frame.len() as u64);
+
+        match side_info(frame) {
+            Some((main_data_begin, data_len)) => {
+                let complete = main_data_begin <= self.available;
+                self.available = (self.available + data_len).min(MAX_MAIN_DATA_BEGIN);
+                (main_data_begin, complete)
+            }
+            None => {
+                self.available = 0;
+                (0, true)
+            }
+        }
+    }
+}
+
+/// Read `main_data_begin` and the length of the audio data from the bytes of
+/// a Layer III frame
+pub(crate) fn side_info(frame: &[u8]) -> Option<(u32, u32)> {
+    if frame.len() < 4 || (frame[1] >> 1) & 0x3 != 0x1 {
+        return None;
+    }
+
+    let mpeg1 = (frame[1] >> 3) & 0x3 == 0x3;
+    let mono = frame[3] >> 6 == 0x3;
+    let side_info_len = match (mpeg1, mono) {
+        (true, false) => 32,
+        (true, true) | (false, false) => 17,
+        (false, true) => 9,
+    };
+    // A CRC word follows the header if the protection bit is clear
+    let start = if frame[1] & 0x1 == 0 { 6 } else { 4 };
+
+    let bits = frame.get(start..start + 2)?;
+    let bits = u32::from(bits[0]) << 8 | u32::from(bits[1]);
+    let main_data_begin = if mpeg1 { bits >> 7 } else { bits >> 8 };
+    let data_len = frame.len().saturating_sub(start + side_info_len) as u32;
+    Some((main_data_begin, data_len))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // An MPEG-1 Layer III stereo frame without CRC
+    fn frame(main_data_begin: u32, len: usize) -> Vec<u8> {
+        let mut frame = vec![0xff, 0xfb, 0x90, 0x00];
+        frame.push((main_data_begin >> 1) as u8);
+        frame.push(((main_data_begin & 0x1) << 7) as u8);
+        frame.resize(len, 0);
+        frame
+    }
+
+    #[test]
+    fn test_side_info() {
+        assert_eq!(side_info(&frame(300, 417)), Some((300, 417 - 36)));
+        assert_eq!(side_info(&frame(0, 10)), Some((0, 0)));
+
+        let mut protected = frame(0, 417);
+        protected[1] &= !0x1;
+        protected[6] = 0x80;
+        assert_eq!(side_info(&protected), Some((256, 417 - 38)));
+
+        // Layer II
+        assert_eq!(side_info(&[0xff, 0xfd, 0x90, 0x00, 0xff, 0xff]), None);
+    }
+
+    #[test]
+    fn test_reservoir() {
+        let mut reservoir = Reservoir::default();
+        assert_eq!(reservoir.push(0, &frame(0, 100)), (0, true));
+        assert_eq!(reservoir.push(100, &frame(64, 100)), (64, true));
+        assert_eq!(reservoir.push(200, &frame(129, 100)), (129, false));
+
+        // A gap empties the reservoir
+        assert_eq!(reservoir.push(400, &frame(10, 100)), (10, false));
+        assert_eq!(reservoir.push(500, &frame(64, 100)), (64, true));
+    }
+}
@@ -0,0 +1,95 @@
+//! Conversion of decoded samples to other formats
+
+use std::io;
+use MadFixed32;
+
+/// The sample formats `Decoder::write_pcm` can write, see
+/// `DecoderBuilder::sample_format`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SampleFormat {
+    /// libmad's fixed-point format, as in `Frame::samples`
+    #[default]
+    Fixed,
+    /// 16-bit signed integers
+    I16,
+    /// 32-bit signed integers
+    I32,
+    /// 32-bit floats
+    F32,
+    /// 64-bit floats
+    F64,
+}
+
+impl SampleFormat {
+    /// The size of a sample in bytes
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            SampleFormat::I16 => 2,
+            SampleFormat::Fixed | SampleFormat::I32 | SampleFormat::F32 => 4,
+            SampleFormat::F64 => 8,
+        }
+    }
+}
+
+/// A format that decoded samples can be converted to
+///
+/// Used by `Frame::convert`, `Frame::interleave`, `Frame::write_pcm` and
+/// `Decoder::samples`, so implementing it is all a new format needs.
+/// `SampleFormat` names the formats implemented here.
+pub trait Sample: Copy {
+    /// Convert a sample from libmad's fixed-point format
+    fn from_fixed(sample: MadFixed32) -> Self;
+
+    /// Write the sample in little-endian byte order
+    fn write_le(self, writer: &mut dyn io::Write) -> io::Result<()>;
+}
+
+impl Sample for MadFixed32 {
+    fn from_fixed(sample: MadFixed32) -> MadFixed32 {
+        sample
+    }
+
+    fn write_le(self, writer: &mut dyn io::Write) -> io::Result<()> {
+        writer.write_all(&self.to_raw().to_le_bytes())
+    }
+}
+
+impl Sample for i16 {
+    fn from_fixed(sample: MadFixed32) -> i16 {
+        sample.to_i16()
+    }
+
+    fn write_le(self, writer: &mut dyn io::Write) -> io::Result<()> {
+        writer.write_all(&self.to_le_bytes())
+    }
+}
+
+impl Sample for i32 {
+    fn from_fixed(sample: MadFixed32) -> i32 {
+        sample.to_i32()
+    }
+
+    fn write_le(self, writer: &mut dyn io::Write) -> io::Result<()> {
+        writer.write_all(&self.to_le_bytes())
+    }
+}
+
+impl Sample for f32 {
+    fn from_fixed(sample: MadFixed32) -> f32 {
+        sample.to_f32()
+    }
+
+    fn write_le(self, writer: &mut dyn io::Write) -> io::Result<()> {
+        writer.write_all(&self.to_le_bytes())
+    }
+}
+
+impl Sample for f64 {
+    fn from_fixed(sample: MadFixed32) -> f64 {
+        sample.to_f64()
+    }
+
+    fn write_le(self, writer: &mut dyn io::Write) -> io::Result<()> {
+        writer.write_all(&self.to_le_bytes())
+    }
+}
@@ -0,0 +1,222 @@
+//! Detection and trimming of leading and trailing silence
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+use {Decoder, Frame, MadFixed32, SimplemadError};
+
+/// The amount of silence at the ends of a stream, see `silence_extent`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SilenceExtent {
+    /// Duration of the silence before the first audible sample
+    pub leading: Duration,
+    /// Duration of the silence after the last audible sample
+    pub trailing: Duration,
+    /// Duration of the whole stream
+    pub total: Duration,
+}
+
+/// An iterator over frames with leading and trailing silence removed
+///
+/// Created by `Decoder::trim_silence`. Samples no louder than the
+/// threshold count as silence. Silence at either end is only removed if
+/// it lasts at least the minimum duration, and the frames at its edges are
+/// cut to the sample. Silent frames are held back until it is known
+/// whether audio follows, so errors may be returned ahead of them.
+pub struct TrimSilence<R>
+    where R: io::Read
+{
+    decoder: Decoder<R>,
+    threshold: MadFixed32,
+    min_duration: Duration,
+    // The current run of silence and its duration
+    silence: Vec<Frame>,
+    silence_duration: Duration,
+    ready: VecDeque<Frame>,
+    audible: bool,
+    leading: Duration,
+    trailing: Duration,
+    total: Duration,
+    done: bool,
+}
+
+impl<R> Decoder<R> where R: io::Read {
+    /// Remove silence of at least `min_duration` from the start and end of
+    /// the stream
+    pub fn trim_silence(self, threshold: MadFixed32, min_duration: Duration) -> TrimSilence<R> {
+        TrimSilence {
+            decoder: self,
+            threshold,
+            min_duration,
+            silence: Vec::new(),
+            silence_duration: Duration::new(0, 0),
+            ready: VecDeque::new(),
+            audible: false,
+            leading: Duration::new(0, 0),
+            trailing: Duration::new(0, 0),
+            total: Duration::new(0, 0),
+            done: false,
+        }
+    }
+}
+
+/// Measure the silence at the start and end of a stream
+///
+/// Decodes the whole stream, silence counts if it is no louder than
+/// `threshold` and lasts at least `min_duration`.
+pub fn silence_extent<R>(reader: R,
+                         threshold: MadFixed32,
+                         min_duration: Duration)
+                         -> Result<SilenceExtent, SimplemadError>
+    where R: io::Read
+{
+    let mut frames = Decoder::decode(reader)?.trim_silence(threshold, min_duration);
+    for result in &mut frames {
+        match result {
+            Ok(_) => {}
+            Err(ref e) if e.is_recoverable() => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(frames.extent())
+}
+
+impl<R> TrimSilence<R> where R: io::Read {
+    /// Get the silence removed so far
+    ///
+    /// The trailing silence is only known once iteration has finished.
+    pub fn extent(&self) -> SilenceExtent {
+        SilenceExtent {
+            leading: self.leading,
+            trailing: self.trailing,
+            total: self.total,
+        }
+    }
+
+    /// Get the underlying decoder, discarding buffered frames
+    pub fn into_decoder(self) -> Decoder<R> {
+        self.decoder
+    }
+
+    // Split a frame into silence, audio and silence, and queue the parts
+    fn push(&mut self, frame: Frame) {
+        self.total += frame.duration;
+        let threshold = self.threshold.value.unsigned_abs();
+        let audible: Vec<usize> = (0..frame.n_samples())
+                                      .filter(|&i| {
+                                          frame.samples
+                                               .iter()
+                                               .any(|c| c[i].value.unsigned_abs() > threshold)
+                                      })
+                                      .collect();
+        let (first, last) = match (audible.first(), audible.last()) {
+            (Some(&first), Some(&last)) => (first, last + 1),
+            _ => {
+                self.push_silence(frame);
+                return;
+            }
+        };
+
+        let rate = frame.sample_rate;
+        let (head, rest) = frame.split_at(sample_offset(first, rate));
+        let (audio, tail) = rest.split_at(sample_offset(last - first, rate));
+        self.push_silence(head);
+        self.end_silence(false);
+        self.audible = true;
+        self.ready.push_back(audio);
+        self.push_silence(tail);
+    }
+
+    fn push_silence(&mut self, frame: Frame) {
+        if frame.n_samples() > 0 {
+            self.silence_duration += frame.duration;
+            self.silence.push(frame);
+        }
+    }
+
+    // Drop the current run of silence if it is at an end of the stream and
+    // long enough, otherwise release it
+    fn end_silence(&mut self, at_end: bool) {
+        let duration = self.silence_duration;
+        self.silence_duration = Duration::new(0, 0);
+        if duration >= self.min_duration && (at_end || !self.audible) {
+            self.silence.clear();
+            if self.audible {
+                self.trailing = duration;
+            } else {
+                self.leading = duration;
+            }
+        } else {
+            self.ready.extend(self.silence.drain(..));
+        }
+    }
+}
+
+impl<R> Iterator for TrimSilence<R> where R: io::Read {
+    type Item = Result<Frame, SimplemadError>;
+    fn next(&mut self) -> Option<Result<Frame, SimplemadError>> {
+        loop {
+            if let Some(frame) = self.ready.pop_front() {
+                return Some(Ok(frame));
+            }
+            if self.done {
+                return None;
+            }
+
+            match self.decoder.get_frame() {
+                Ok(frame) => self.push(frame),
+                Err(SimplemadError::EOF) => {
+                    self.done = true;
+                    self.end_silence(true);
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+// The offset of a sample, rounded up so that `Frame::split_at` rounding
+// down lands on it
+fn sample_offset(index: usize, sample_rate: u32) -> Duration {
+    let nanos = (index as u128 * 1_000_000_000).div_ceil(u128::from(sample_rate));
+    Duration::from_nanos(nanos as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+
+    fn trimmed_samples(frames: &[Frame]) -> usize {
+        frames.iter().map(|f| f.n_samples()).sum()
+    }
+
+    #[test]
+    fn test_trim_silence() {
+        let path = "sample_mp3s/constant_stereo_128.mp3";
+        let all: Vec<Frame> = Decoder::decode(File::open(path).unwrap())
+                                  .unwrap()
+                                  .filter_map(|r| r.ok())
+                                  .collect();
+        let quiet = MadFixed32::from(0.01f32);
+
+        let mut frames = Decoder::decode(File::open(path).unwrap())
+                             .unwrap()
+                             .trim_silence(quiet, Duration::new(0, 0));
+        let trimmed: Vec<Frame> = frames.by_ref().filter_map(|r| r.ok()).collect();
+        let extent = frames.extent();
+        assert!(extent.leading > Duration::new(0, 0));
+        assert!(trimmed_samples(&trimmed) < trimmed_samples(&all));
+        assert!(trimmed[0].samples
+                          .iter()
+                          .any(|c| c[0].to_raw().unsigned_abs() > quiet.to_raw() as u32));
+        let total = all.iter().fold(Duration::new(0, 0), |acc, f| acc + f.duration);
+        assert_eq!(extent.total, total);
+
+        // Silence shorter than the minimum is kept
+        let extent = silence_extent(File::open(path).unwrap(), quiet, Duration::from_secs(3600))
+                         .unwrap();
+        assert_eq!(extent.leading, Duration::new(0, 0));
+        assert_eq!(extent.trailing, Duration::new(0, 0));
+    }
+}
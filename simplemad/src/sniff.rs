@@ -0,0 +1,270 @@
+//! Recognizing MPEG audio without decoding it
+
+use std::fmt;
+
+// Consecutive frames needed to be confident that a stream is MPEG audio
+const LIKELY_FRAMES: usize = 4;
+
+/// How likely some data is to be MPEG audio, see `is_mpeg_audio`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// No valid frame header was found
+    Unlikely,
+    /// A valid frame header was found, but it isn't followed by others
+    Possible,
+    /// A sequence of consistent frame headers was found
+    Likely,
+}
+
+// The fields of a frame header that are needed to find the next frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct RawHeader {
+    // 1, 2 or 25 for MPEG-2.5
+    pub version: u8,
+    pub layer: u8,
+    pub bit_rate: u32,
+    pub sample_rate: u32,
+    pub padding: bool,
+}
+
+const BIT_RATES_V1: [[u32; 14]; 3] =
+    [[32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448],
+     [32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384],
+     [32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320]];
+
+const BIT_RATES_V2: [[u32; 14]; 2] =
+    [[32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256],
+     [8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160]];
+
+impl RawHeader {
+    pub fn parse(bytes: &[u8]) -> Option<RawHeader> {
+        if bytes.len() < 4 || bytes[0] != 0xff || bytes[1] & 0xe0 != 0xe0 {
+            return None;
+        }
+
+        let version = match (bytes[1] >> 3) & 0x3 {
+            0 => 25,
+            2 => 2,
+            3 => 1,
+            _ => return None,
+        };
+        let layer = match (bytes[1] >> 1) & 0x3 {
+            0 => return None,
+            bits => 4 - bits,
+        };
+
+        // Free format streams have no bit rate in their headers
+        let bit_rate_index = (bytes[2] >> 4) as usize;
+        if bit_rate_index == 0 || bit_rate_index == 0xf {
+            return None;
+        }
+        let bit_rate = match (version, layer) {
+            (1, layer) => BIT_RATES_V1[layer as usize - 1][bit_rate_index - 1],
+            (_, 1) => BIT_RATES_V2[0][bit_rate_index - 1],
+            _ => BIT_RATES_V2[1][bit_rate_index - 1],
+        } * 1000;
+
+        let sample_rate = match (bytes[2] >> 2) & 0x3 {
+            0 => 44_100,
+            1 => 48_000,
+            2 => 32_000,
+            _ => return None,
+        } / match version {
+            1 => 1,
+            2 => 2,
+            _ => 4,
+        };
+
+        Some(RawHeader {
+            version,
+            layer,
+            bit_rate,
+            sample_rate,
+            padding: (bytes[2] >> 1) & 0x1 == 1,
+        })
+    }
+
+    /// The length of the frame in bytes, including the header
+    pub fn frame_len(&self) -> usize {
+        let padding = self.padding as u32;
+        let len = match (self.layer, self.version) {
+            (1, _) => (12 * self.bit_rate / self.sample_rate + padding) * 4,
+            (3, 2) | (3, 25) => 72 * self.bit_rate / self.sample_rate + padding,
+            _ => 144 * self.bit_rate / self.sample_rate + padding,
+        };
+        len as usize
+    }
+
+    // Whether `other` can belong to the same stream
+    fn is_consistent(&self, other: &RawHeader) -> bool {
+        self.version == other.version && self.layer == other.layer &&
+        self.sample_rate == other.sample_rate
+    }
+}
+
+/// The length of an ID3v2 tag at the start of `bytes`, or 0
+pub(crate) fn id3v2_len(bytes: &[u8]) -> usize {
+    if bytes.len() < 10 || !bytes.starts_with(b"ID3") {
+        return 0;
+    }
+
+    // The size is stored as four 7-bit bytes
+    let size = bytes[6..10].iter().fold(0, |size, &b| size << 7 | (b & 0x7f) as usize);
+    let footer = if bytes[5] & 0x10 != 0 { 10 } else { 0 };
+    10 + size + footer
+}
+
+/// A format that isn't MPEG audio, recognized by its magic number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DetectedFormat {
+    /// A RIFF WAVE file that doesn't contain MPEG audio
+    Wav,
+    /// FLAC
+    Flac,
+    /// An Ogg container, usually Vorbis or Opus
+    Ogg,
+    /// AAC in ADTS frames
+    Aac,
+    /// An MP4 or M4A container
+    Mp4,
+}
+
+impl fmt::Display for DetectedFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            DetectedFormat::Wav => "WAV",
+            DetectedFormat::Flac => "FLAC",
+            DetectedFormat::Ogg => "Ogg",
+            DetectedFormat::Aac => "AAC",
+            DetectedFormat::Mp4 => "MP4",
+        })
+    }
+}
+
+// WAVE format tag of MPEG Layer III data
+const WAVE_FORMAT_MPEGLAYER3: u16 = 0x0055;
+
+/// Recognize the start of a file in another common audio format
+pub(crate) fn detect_format(bytes: &[u8]) -> Option<DetectedFormat> {
+    let bytes = bytes.get(id3v2_len(bytes)..)?;
+
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WAVE") {
+        // MP3 in a WAVE container is still decodable
+        let mp3 = bytes.get(12..16) == Some(b"fmt ") && bytes.len() >= 22 &&
+                  u16::from(bytes[20]) | u16::from(bytes[21]) << 8 == WAVE_FORMAT_MPEGLAYER3;
+        if mp3 {
+            None
+        } else {
+            Some(DetectedFormat::Wav)
+        }
+    } else if bytes.starts_with(b"fLaC") {
+        Some(DetectedFormat::Flac)
+    } else if bytes.starts_with(b"OggS") {
+        Some(DetectedFormat::Ogg)
+    } else if bytes.get(4..8) == Some(b"ftyp") {
+        Some(DetectedFormat::Mp4)
+    } else if bytes.len() >= 2 && bytes[0] == 0xff && bytes[1] & 0xf6 == 0xf0 {
+        // ADTS shares the sync word but uses the reserved layer value
+        Some(DetectedFormat::Aac)
+    } else {
+        None
+    }
+}
+
+/// Check whether `bytes` look like the start of an MPEG audio stream
+///
+/// Skips an ID3v2 tag, then looks for a sequence of consistent frame
+/// headers. A few kilobytes of data are enough; nothing is decoded.
+pub fn is_mpeg_audio(bytes: &[u8]) -> Confidence {
+    let start = id3v2_len(bytes);
+    let mut confidence = Confidence::Unlikely;
+
+    for offset in start..bytes.len() {
+        let first = match RawHeader::parse(&bytes[offset..]) {
+            Some(header) => header,
+            None => continue,
+        };
+
+        let mut frames = 1;
+        let mut next = offset + first.frame_len();
+        while let Some(header) = bytes.get(next..).and_then(RawHeader::parse) {
+            if !first.is_consistent(&header) {
+                break;
+            }
+            frames += 1;
+            next += header.frame_len();
+        }
+
+        // A sequence that runs to the end of the data is as good as it gets
+        if frames >= LIKELY_FRAMES || (frames > 1 && next + 4 > bytes.len()) {
+            return Confidence::Likely;
+        }
+        confidence = Confidence::Possible;
+    }
+
+    confidence
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use std::io::Read;
+
+    fn first_bytes(path: &str, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0; len];
+        File::open(path).unwrap().read_exact(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_raw_header() {
+        let header = RawHeader::parse(&[0xff, 0xfb, 0x90, 0x04]).unwrap();
+        assert_eq!(header.version, 1);
+        assert_eq!(header.layer, 3);
+        assert_eq!(header.bit_rate, 128_000);
+        assert_eq!(header.sample_rate, 44_100);
+        assert_eq!(header.frame_len(), 417);
+
+        let header = RawHeader::parse(&first_bytes("sample_mp3s/constant_stereo_16.mp3", 4));
+        assert_eq!(header.unwrap().sample_rate, 24_000);
+
+        assert_eq!(RawHeader::parse(b"RIFF"), None);
+    }
+
+    #[test]
+    fn test_is_mpeg_audio() {
+        for path in &["sample_mp3s/constant_stereo_128.mp3",
+                      "sample_mp3s/variable_stereo.mp3",
+                      "sample_mp3s/constant_stereo_16.mp3",
+                      "sample_mp3s/rodiomusic.mp3"] {
+            assert_eq!(is_mpeg_audio(&first_bytes(path, 4_096)), Confidence::Likely);
+        }
+
+        let mut tagged = b"ID3\x04\x00\x00\x00\x00\x01\x00".to_vec();
+        tagged.extend(vec![0xff; 128]);
+        tagged.extend(first_bytes("sample_mp3s/constant_stereo_128.mp3", 4_096));
+        assert_eq!(is_mpeg_audio(&tagged), Confidence::Likely);
+
+        let single = first_bytes("sample_mp3s/constant_stereo_128.mp3", 100);
+        assert_eq!(is_mpeg_audio(&single), Confidence::Possible);
+
+        assert_eq!(is_mpeg_audio(b"RIFF\x24\x08\x00\x00WAVEfmt "), Confidence::Unlikely);
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format(b"RIFF\x24\x08\x00\x00WAVEfmt \x10\x00\x00\x00\x01\x00"),
+                   Some(DetectedFormat::Wav));
+        assert_eq!(detect_format(b"RIFF\x24\x08\x00\x00WAVEfmt \x1e\x00\x00\x00\x55\x00"),
+                   None);
+        assert_eq!(detect_format(b"fLaC\x00\x00\x00\x22"), Some(DetectedFormat::Flac));
+        assert_eq!(detect_format(b"ID3\x04\x00\x00\x00\x00\x00\x02\x00\x00fLaC"),
+                   Some(DetectedFormat::Flac));
+        assert_eq!(detect_format(b"OggS\x00\x02"), Some(DetectedFormat::Ogg));
+        assert_eq!(detect_format(b"\x00\x00\x00\x20ftypM4A "), Some(DetectedFormat::Mp4));
+        assert_eq!(detect_format(&[0xff, 0xf1, 0x50, 0x80]), Some(DetectedFormat::Aac));
+        assert_eq!(detect_format(&[0xff, 0xfb, 0x90, 0x04]), None);
+    }
+}
@@ -0,0 +1,156 @@
+//! Decoding the end of a seekable stream without reading all of it
+
+use std::cmp::max;
+use std::collections::VecDeque;
+use std::io;
+use {frame_duration, Decoder, Frame, SimplemadError};
+
+impl<R> Decoder<R> where R: io::Read + io::Seek {
+    /// Decode the last `n` frames of the stream
+    ///
+    /// Jumps to an estimate of where the last frames start, based on the
+    /// Xing header or the length of the first frame, and jumps further back
+    /// if there weren't enough frames after that point. The frames before the
+    /// last ones are used to fill the bit reservoir, so the samples are the
+    /// same as those of a full decode.
+    ///
+    /// The positions of the frames are estimated from the Xing header or the
+    /// average frame length near the end. The start and end times are
+    /// ignored, and the decoder is left at the end of the stream, `reset`
+    /// rewinds it.
+    pub fn last_frames(&mut self, n: usize) -> Result<Vec<Frame>, SimplemadError> {
+        // The first frame tells where the audio starts and how long frames are
+        while self.frame_index == 0 {
+            match self.get_frame() {
+                Ok(frame) => self.recycle(frame),
+                Err(ref error) if error.is_recoverable() => {}
+                Err(error) => return Err(error),
+            }
+        }
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+
+        let audio_start = self.audio_offset.unwrap_or(0);
+        let frame_len = match self.xing.and_then(|xing| Some((xing.bytes?, xing.frames?))) {
+            Some((bytes, frames)) if frames > 0 => u64::from(bytes / frames),
+            _ => self.frame_bytes().len() as u64,
+        };
+        let warm_up = self.warm_up_len() as usize;
+        let end = {
+            let here = self.reader.stream_position()?;
+            let len = self.reader.seek(io::SeekFrom::End(0))?;
+            self.reader.seek(io::SeekFrom::Start(here))?;
+            self.read_len() + (len - here)
+        };
+
+        let mut window = (n + warm_up + 1) as u64 * max(frame_len, 1);
+        loop {
+            let offset = max(end.saturating_sub(window), audio_start);
+            self.jump_to(offset)?;
+            let offsets: Vec<u64> = self.scan_frames()?
+                                        .frames
+                                        .iter()
+                                        .map(|frame| frame.byte_offset)
+                                        .collect();
+            let from_start = offset == audio_start;
+            if offsets.len() < n + warm_up && !from_start {
+                window *= 2;
+                continue;
+            }
+
+            // Estimate the index of the first frame found
+            let count = offsets.len() as u64;
+            let base = match self.xing.and_then(|xing| xing.frames) {
+                // Only the end is known from the Xing header's frame count
+                Some(frames) if !from_start => u64::from(frames).saturating_sub(count),
+                _ if from_start || count == 0 => 0,
+                _ => {
+                    let span = max(self.sync_offset - offsets[0], 1);
+                    ((offsets[0] - audio_start) * count + span / 2) / span
+                }
+            };
+            let duration = frame_duration(&self.mad.frame);
+
+            let first = offsets.len().saturating_sub(n);
+            let warm_up_start = first.saturating_sub(warm_up);
+            let jump = offsets.get(warm_up_start).cloned().unwrap_or(end);
+            self.jump_to(jump)?;
+            self.frame_index = base + first as u64;
+            self.position = duration * (base as u32 + warm_up_start as u32);
+
+            let interval = (self.options.start_time, self.options.end_time);
+            self.options.start_time = Some(duration * (base as u32 + first as u32));
+            self.options.end_time = None;
+            let frames = self.decode_to_end(n);
+            self.options.start_time = interval.0;
+            self.options.end_time = interval.1;
+            return frames;
+        }
+    }
+
+    // Decode the remaining frames, keeping the last `n`
+    fn decode_to_end(&mut self, n: usize) -> Result<Vec<Frame>, SimplemadError> {
+        let mut frames = VecDeque::with_capacity(n + 1);
+        loop {
+            match self.get_frame() {
+                Ok(frame) => {
+                    frames.push_back(frame);
+                    if frames.len() > n {
+                        let frame = frames.pop_front().unwrap();
+                        self.recycle(frame);
+                    }
+                }
+                Err(SimplemadError::EOF) => return Ok(frames.into_iter().collect()),
+                Err(ref error) if error.is_recoverable() => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use {Decoder, Frame};
+
+    fn raw(frame: &Frame) -> Vec<Vec<i32>> {
+        frame.samples
+             .iter()
+             .map(|channel| channel.iter().map(|sample| sample.to_raw()).collect())
+             .collect()
+    }
+
+    #[test]
+    fn test_last_frames() {
+        for path in &["sample_mp3s/constant_stereo_128.mp3",
+                      "sample_mp3s/variable_stereo.mp3",
+                      "sample_mp3s/constant_single_channel_128.mp3"] {
+            let all: Vec<Frame> = Decoder::decode(File::open(path).unwrap())
+                                      .unwrap()
+                                      .filter_map(|r| r.ok())
+                                      .collect();
+            for &n in &[1, 5, 40] {
+                let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+                let tail = decoder.last_frames(n).unwrap();
+                assert_eq!(tail.len(), n, "{}", path);
+                for (frame, expected) in tail.iter().zip(&all[all.len() - n..]) {
+                    assert_eq!(frame.byte_offset, expected.byte_offset, "{}", path);
+                    assert_eq!(raw(frame), raw(expected), "{} {}", path, frame.byte_offset);
+                }
+                assert_eq!(tail.last().unwrap().position, all.last().unwrap().position, "{}", path);
+                assert!(decoder.next().is_none());
+            }
+
+            // Asking for more frames than there are returns all of them
+            let mut decoder = Decoder::decode(File::open(path).unwrap()).unwrap();
+            let tail = decoder.last_frames(all.len() + 10).unwrap();
+            assert_eq!(tail.len(), all.len());
+            assert_eq!(tail[0].position, all[0].position);
+            assert_eq!(raw(&tail[0]), raw(&all[0]));
+
+            decoder.reset().unwrap();
+            assert_eq!(decoder.filter_map(|r| r.ok()).count(), all.len());
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! The layer, mode, emphasis and error codes of libmad, as this crate's own
+//! types
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use simplemad_sys::{MadEmphasis, MadError, MadLayer, MadMode};
+
+/// The audio layer of a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Layer {
+    /// Layer I
+    I = 1,
+    /// Layer II
+    II = 2,
+    /// Layer III, i.e. MP3
+    III = 3,
+}
+
+impl From<MadLayer> for Layer {
+    fn from(layer: MadLayer) -> Layer {
+        match layer {
+            MadLayer::LayerI => Layer::I,
+            MadLayer::LayerII => Layer::II,
+            MadLayer::LayerIII => Layer::III,
+        }
+    }
+}
+
+impl From<Layer> for MadLayer {
+    fn from(layer: Layer) -> MadLayer {
+        match layer {
+            Layer::I => MadLayer::LayerI,
+            Layer::II => MadLayer::LayerII,
+            Layer::III => MadLayer::LayerIII,
+        }
+    }
+}
+
+/// The channel mode of a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// One channel
+    SingleChannel = 0,
+    /// Two independent channels, e.g. two languages
+    DualChannel = 1,
+    /// Stereo using intensity or middle/side coding
+    JointStereo = 2,
+    /// Stereo with the channels coded separately
+    Stereo = 3,
+}
+
+impl From<MadMode> for Mode {
+    fn from(mode: MadMode) -> Mode {
+        match mode {
+            MadMode::SingleChannel => Mode::SingleChannel,
+            MadMode::DualChannel => Mode::DualChannel,
+            MadMode::JointStereo => Mode::JointStereo,
+            MadMode::Stereo => Mode::Stereo,
+        }
+    }
+}
+
+impl From<Mode> for MadMode {
+    fn from(mode: Mode) -> MadMode {
+        match mode {
+            Mode::SingleChannel => MadMode::SingleChannel,
+            Mode::DualChannel => MadMode::DualChannel,
+            Mode::JointStereo => MadMode::JointStereo,
+            Mode::Stereo => MadMode::Stereo,
+        }
+    }
+}
+
+/// The de-emphasis to apply to the decoded audio of a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Emphasis {
+    /// No emphasis
+    None = 0,
+    /// 50/15 µs emphasis
+    Fifty15Us = 1,
+    /// CCITT J.17 emphasis
+    CcittJ17 = 3,
+    /// The reserved value
+    Reserved = 2,
+}
+
+impl From<MadEmphasis> for Emphasis {
+    fn from(emphasis: MadEmphasis) -> Emphasis {
+        match emphasis {
+            MadEmphasis::None => Emphasis::None,
+            MadEmphasis::Fifty15Us => Emphasis::Fifty15Us,
+            MadEmphasis::CcittJ17 => Emphasis::CcittJ17,
+            MadEmphasis::Reserved => Emphasis::Reserved,
+        }
+    }
+}
+
+impl From<Emphasis> for MadEmphasis {
+    fn from(emphasis: Emphasis) -> MadEmphasis {
+        match emphasis {
+            Emphasis::None => MadEmphasis::None,
+            Emphasis::Fifty15Us => MadEmphasis::Fifty15Us,
+            Emphasis::CcittJ17 => MadEmphasis::CcittJ17,
+            Emphasis::Reserved => MadEmphasis::Reserved,
+        }
+    }
+}
+
+/// An error reported by libmad
+///
+/// The values are libmad's `MAD_ERROR_*` codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LibmadError {
+    /// Input buffer too small, or the end of the stream
+    BufLen = 0x0001,
+    /// Invalid (null) buffer pointer
+    BufPtr = 0x0002,
+    /// Not enough memory
+    NoMem = 0x0031,
+    /// Lost synchronization
+    LostSync = 0x0101,
+    /// Reserved header layer value
+    BadLayer = 0x0102,
+    /// Forbidden bitrate value
+    BadBitRate = 0x0103,
+    /// Reserved sample frequency value
+    BadSampleRate = 0x0104,
+    /// Reserved emphasis value
+    BadEmphasis = 0x0105,
+    /// CRC check failed
+    BadCRC = 0x0201,
+    /// Forbidden bit allocation value
+    BadBitAlloc = 0x0211,
+    /// Bad scalefactor index
+    BadScaleFactor = 0x0221,
+    /// Bad bitrate/mode combination
+    BadMode = 0x0222,
+    /// Bad frame length
+    BadFrameLen = 0x0231,
+    /// Bad big_values count
+    BadBigValues = 0x0232,
+    /// Reserved block_type
+    BadBlockType = 0x0233,
+    /// Bad scalefactor selection info
+    BadScFSI = 0x0234,
+    /// Bad main_data_begin pointer
+    BadDataPtr = 0x0235,
+    /// Bad audio data length
+    BadPart3Len = 0x0236,
+    /// Bad Huffman table select
+    BadHuffTable = 0x0237,
+    /// Huffman data overrun
+    BadHuffData = 0x0238,
+    /// Incompatible block_type for joint stereo
+    BadStereo = 0x0239,
+}
+
+impl LibmadError {
+    /// Whether libmad can continue decoding after this error
+    pub fn is_recoverable(self) -> bool {
+        MadError::from(self).is_recoverable()
+    }
+
+    /// A description of the error, as returned by `mad_stream_errorstr`
+    pub fn as_str(self) -> &'static str {
+        MadError::from(self).as_str()
+    }
+}
+
+impl fmt::Display for LibmadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl error::Error for LibmadError {}
+
+/// Fails for `MadError::None`
+impl TryFrom<MadError> for LibmadError {
+    type Error = MadError;
+
+    fn try_from(error: MadError) -> Result<LibmadError, MadError> {
+        Ok(match error {
+            MadError::None => return Err(error),
+            MadError::BufLen => LibmadError::BufLen,
+            MadError::BufPtr => LibmadError::BufPtr,
+            MadError::NoMem => LibmadError::NoMem,
+            MadError::LostSync => LibmadError::LostSync,
+            MadError::BadLayer => LibmadError::BadLayer,
+            MadError::BadBitRate => LibmadError::BadBitRate,
+            MadError::BadSampleRate => LibmadError::BadSampleRate,
+            MadError::BadEmphasis => LibmadError::BadEmphasis,
+            MadError::BadCRC => LibmadError::BadCRC,
+            MadError::BadBitAlloc => LibmadError::BadBitAlloc,
+            MadError::BadScaleFactor => LibmadError::BadScaleFactor,
+            MadError::BadMode => LibmadError::BadMode,
+            MadError::BadFrameLen => LibmadError::BadFrameLen,
+            MadError::BadBigValues => LibmadError::BadBigValues,
+            MadError::BadBlockType => LibmadError::BadBlockType,
+            MadError::BadScFSI => LibmadError::BadScFSI,
+            MadError::BadDataPtr => LibmadError::BadDataPtr,
+            MadError::BadPart3Len => LibmadError::BadPart3Len,
+            MadError::BadHuffTable => LibmadError::BadHuffTable,
+            MadError::BadHuffData => LibmadError::BadHuffData,
+            MadError::BadStereo => LibmadError::BadStereo,
+        })
+    }
+}
+
+impl From<LibmadError> for MadError {
+    fn from(error: LibmadError) -> MadError {
+        match error {
+            LibmadError::BufLen => MadError::BufLen,
+            LibmadError::BufPtr => MadError::BufPtr,
+            LibmadError::NoMem => MadError::NoMem,
+            LibmadError::LostSync => MadError::LostSync,
+            LibmadError::BadLayer => MadError::BadLayer,
+            LibmadError::BadBitRate => MadError::BadBitRate,
+            LibmadError::BadSampleRate => MadError::BadSampleRate,
+            LibmadError::BadEmphasis => MadError::BadEmphasis,
+            LibmadError::BadCRC => MadError::BadCRC,
+            LibmadError::BadBitAlloc => MadError::BadBitAlloc,
+            LibmadError::BadScaleFactor => MadError::BadScaleFactor,
+            LibmadError::BadMode => MadError::BadMode,
+            LibmadError::BadFrameLen => MadError::BadFrameLen,
+            LibmadError::BadBigValues => MadError::BadBigValues,
+            LibmadError::BadBlockType => MadError::BadBlockType,
+            LibmadError::BadScFSI => MadError::BadScFSI,
+            LibmadError::BadDataPtr => MadError::BadDataPtr,
+            LibmadError::BadPart3Len => MadError::BadPart3Len,
+            LibmadError::BadHuffTable => MadError::BadHuffTable,
+            LibmadError::BadHuffData => MadError::BadHuffData,
+            LibmadError::BadStereo => MadError::BadStereo,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        for &layer in &[Layer::I, Layer::II, Layer::III] {
+            assert_eq!(MadLayer::from(layer) as u32, layer as u32);
+            assert_eq!(Layer::from(MadLayer::from(layer)), layer);
+        }
+        for &mode in &[Mode::SingleChannel, Mode::DualChannel, Mode::JointStereo, Mode::Stereo] {
+            assert_eq!(MadMode::from(mode) as u32, mode as u32);
+            assert_eq!(Mode::from(MadMode::from(mode)), mode);
+        }
+
+        assert_eq!(LibmadError::try_from(MadError::None), Err(MadError::None));
+        let error = LibmadError::try_from(MadError::BadDataPtr).unwrap();
+        assert_eq!(error, LibmadError::BadDataPtr);
+        assert_eq!(MadError::from(error) as u32, error as u32);
+        assert_eq!(error.to_string(), "bad main_data_begin pointer");
+        assert!(error.is_recoverable());
+        assert!(!LibmadError::BufLen.is_recoverable());
+    }
+}
@@ -0,0 +1,72 @@
+//! The version and build configuration of the linked libmad
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+use simplemad_sys::{mad_build, mad_version};
+
+/// How the linked libmad was compiled
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LibmadBuild {
+    /// The fixed-point implementation, e.g. `FPM_64BIT` or `FPM_DEFAULT`
+    pub fixed_point: Option<&'static str>,
+    /// All flags libmad reports, e.g. `FPM_64BIT`, `ASO_ZEROCHECK` or
+    /// `NDEBUG`
+    pub flags: Vec<&'static str>,
+}
+
+impl LibmadBuild {
+    /// Check whether libmad was compiled with `flag`
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+}
+
+/// Get the version of the linked libmad, e.g. `0.15.1 (beta)`
+pub fn libmad_version() -> &'static str {
+    let version = unsafe { static_str(ptr::addr_of!(mad_version)) };
+    version.trim_start_matches("MPEG Audio Decoder ")
+}
+
+/// Get the configuration the linked libmad was compiled with
+///
+/// This tells which fixed-point implementation and optimizations decoded
+/// the audio, which can explain small differences between platforms.
+pub fn libmad_build() -> LibmadBuild {
+    parse_build(unsafe { static_str(ptr::addr_of!(mad_build)) })
+}
+
+fn parse_build(build: &'static str) -> LibmadBuild {
+    let flags: Vec<&'static str> = build.split_whitespace().collect();
+    LibmadBuild {
+        fixed_point: flags.iter().find(|flag| flag.starts_with("FPM_")).cloned(),
+        flags,
+    }
+}
+
+// The strings are compiled into libmad
+unsafe fn static_str(string: *const c_char) -> &'static str {
+    CStr::from_ptr(string).to_str().unwrap_or("")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_libmad_version() {
+        assert!(libmad_version().starts_with("0.15"));
+        assert!(libmad_build().fixed_point.is_some());
+    }
+
+    #[test]
+    fn test_parse_build() {
+        let build = parse_build("NDEBUG FPM_64BIT ASO_ZEROCHECK ");
+        assert_eq!(build.fixed_point, Some("FPM_64BIT"));
+        assert_eq!(build.flags, vec!["NDEBUG", "FPM_64BIT", "ASO_ZEROCHECK"]);
+        assert!(build.has_flag("ASO_ZEROCHECK"));
+        assert!(!build.has_flag("OPT_SPEED"));
+
+        assert_eq!(parse_build(""), LibmadBuild { fixed_point: None, flags: Vec::new() });
+    }
+}
@@ -0,0 +1,184 @@
+//! Parsing of the Xing, Info and VBRI headers that encoders write in the
+//! first frame
+
+/// The totals stored in a Xing, Info or VBRI header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct XingHeader {
+    /// Whether the header marks a variable bit rate stream
+    pub vbr: bool,
+    /// Number of frames, including the frame holding the header
+    pub frames: Option<u32>,
+    /// Number of bytes of audio data
+    pub bytes: Option<u32>,
+    /// Samples of encoder delay at the start, 0 if unknown
+    pub delay: u32,
+    /// Samples of padding at the end, 0 if unknown
+    pub padding: u32,
+    /// The encoder version at the start of a LAME tag, e.g. `LAME3.99r`
+    pub encoder: Option<[u8; ENCODER_LEN]>,
+}
+
+const FRAMES_FLAG: u32 = 0x1;
+const BYTES_FLAG: u32 = 0x2;
+const TOC_FLAG: u32 = 0x4;
+const QUALITY_FLAG: u32 = 0x8;
+
+// Encoders that write a LAME tag after the Xing header
+const LAME_TAGS: [&[u8]; 3] = [b"LAME", b"Lavc", b"Lavf"];
+// Offset of the delay and padding within the LAME tag
+const LAME_DELAY_OFFSET: usize = 21;
+// Length of the encoder version the LAME tag starts with
+pub(crate) const ENCODER_LEN: usize = 9;
+
+// VBRI headers always follow the first 32 bytes after the frame header
+const VBRI_OFFSET: usize = 4 + 32;
+
+/// Find a Xing, Info or VBRI header in the bytes of a Layer III frame
+pub(crate) fn parse(frame: &[u8]) -> Option<XingHeader> {
+    if frame.len() < 4 || (frame[1] >> 1) & 0x3 != 0x1 {
+        return None;
+    }
+
+    if let Some(mut fields) = frame.get(VBRI_OFFSET..) {
+        if fields.starts_with(b"VBRI") {
+            // Skip the tag and version
+            fields = fields.get(6..)?;
+            let delay = read_u16(&mut fields)?;
+            // Skip the quality
+            fields = fields.get(2..)?;
+            let bytes = read_u32(&mut fields)?;
+            let frames = read_u32(&mut fields)?;
+            return Some(XingHeader {
+                vbr: true,
+                frames: Some(frames),
+                bytes: Some(bytes),
+                delay: u32::from(delay),
+                padding: 0,
+                encoder: None,
+            });
+        }
+    }
+
+    // The header follows the side information, whose size depends on the
+    // MPEG version and the number of channels
+    let mpeg1 = (frame[1] >> 3) & 0x3 == 0x3;
+    let mono = frame[3] >> 6 == 0x3;
+    let side_info_len = match (mpeg1, mono) {
+        (true, false) => 32,
+        (true, true) | (false, false) => 17,
+        (false, true) => 9,
+    };
+
+    let mut fields = frame.get(4 + side_info_len..)?;
+    let vbr = fields.starts_with(b"Xing");
+    if !vbr && !fields.starts_with(b"Info") {
+        return None;
+    }
+    fields = &fields[4..];
+
+    let flags = read_u32(&mut fields)?;
+    let frames = if flags & FRAMES_FLAG != 0 {
+        Some(read_u32(&mut fields)?)
+    } else {
+        None
+    };
+    let bytes = if flags & BYTES_FLAG != 0 {
+        Some(read_u32(&mut fields)?)
+    } else {
+        None
+    };
+
+    let mut skip = 0;
+    if flags & TOC_FLAG != 0 {
+        skip += 100;
+    }
+    if flags & QUALITY_FLAG != 0 {
+        skip += 4;
+    }
+
+    // The delay and padding are two 12 bit values
+    let (delay, padding, encoder) = match fields.get(skip..) {
+        Some(lame) if LAME_TAGS.iter().any(|tag| lame.starts_with(tag)) => {
+            let mut encoder = [0; ENCODER_LEN];
+            let encoder = lame.get(..ENCODER_LEN).map(|version| {
+                encoder.copy_from_slice(version);
+                encoder
+            });
+            match lame.get(LAME_DELAY_OFFSET..LAME_DELAY_OFFSET + 3) {
+                Some(value) => {
+                    (u32::from(value[0]) << 4 | u32::from(value[1]) >> 4,
+                     u32::from(value[1] & 0xf) << 8 | u32::from(value[2]),
+                     encoder)
+                }
+                None => (0, 0, encoder),
+            }
+        }
+        _ => (0, 0, None),
+    };
+
+    Some(XingHeader {
+        vbr,
+        frames,
+        bytes,
+        delay,
+        padding,
+        encoder,
+    })
+}
+
+fn read_u16(bytes: &mut &[u8]) -> Option<u16> {
+    let value = bytes.get(..2)?;
+    let value = u16::from(value[0]) << 8 | u16::from(value[1]);
+    *bytes = &bytes[2..];
+    Some(value)
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Option<u32> {
+    let value = bytes.get(..4)?;
+    let value = u32::from(value[0]) << 24 | u32::from(value[1]) << 16 |
+                u32::from(value[2]) << 8 | u32::from(value[3]);
+    *bytes = &bytes[4..];
+    Some(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use std::io::Read;
+
+    fn first_bytes(path: &str) -> Vec<u8> {
+        let mut bytes = vec![0; 1_000];
+        File::open(path).unwrap().read_exact(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_parse_xing() {
+        let header = parse(&first_bytes("sample_mp3s/variable_stereo.mp3")).unwrap();
+        assert!(header.vbr);
+        assert_eq!(header.frames, Some(193));
+        assert_eq!(header.bytes, Some(145_541));
+
+        let header = parse(&first_bytes("sample_mp3s/constant_single_channel_128.mp3")).unwrap();
+        assert!(!header.vbr);
+        assert_eq!(header.frames, Some(193));
+        assert_eq!((header.delay, header.padding), (576, 1456));
+        assert_eq!(&header.encoder.unwrap(), b"LAME3.99r");
+
+        assert_eq!(parse(&first_bytes("sample_mp3s/rodiomusic.mp3")), None);
+    }
+
+    #[test]
+    fn test_parse_vbri() {
+        let mut frame = vec![0xff, 0xfb, 0x90, 0x04];
+        frame.extend(vec![0; 32]);
+        frame.extend(b"VBRI\x00\x01\x04\x51\x00\x4b");
+        frame.extend(&[0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc1]);
+        let header = parse(&frame).unwrap();
+        assert!(header.vbr);
+        assert_eq!(header.bytes, Some(65_536));
+        assert_eq!(header.frames, Some(193));
+        assert_eq!(header.delay, 1105);
+    }
+}
@@ -0,0 +1,167 @@
+#ifndef SIMPLEMAD_H
+#define SIMPLEMAD_H
+
+/* Generated by cbindgen, do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * A frame was decoded
+ */
+#define SIMPLEMAD_OK 0
+
+/**
+ * The end of the stream was reached
+ */
+#define SIMPLEMAD_EOF 1
+
+/**
+ * libmad reported an error; see `simplemad_last_error`
+ */
+#define SIMPLEMAD_ERR_DECODE -1
+
+/**
+ * The underlying file could not be read
+ */
+#define SIMPLEMAD_ERR_IO -2
+
+/**
+ * A null pointer was passed
+ */
+#define SIMPLEMAD_ERR_ARGS -3
+
+/**
+ * Decoding was abandoned after too many errors or a mid-stream format
+ * change; the stream wasn't read to its end
+ */
+#define SIMPLEMAD_ERR_ABORTED -4
+
+/**
+ * An opaque decoder handle
+ */
+typedef struct SimplemadDecoder SimplemadDecoder;
+
+/**
+ * A decoded frame
+ *
+ * The sample pointers refer to memory owned by the decoder handle and
+ * stay valid until the next call to `simplemad_next_frame` or
+ * `simplemad_close`.
+ */
+typedef struct SimplemadFrame {
+  /**
+   * Number of samples per second
+   */
+  uint32_t sample_rate;
+  /**
+   * Stream bit rate
+   */
+  uint32_t bit_rate;
+  /**
+   * Audio layer (1, 2 or 3)
+   */
+  uint32_t layer;
+  /**
+   * 0: single channel, 1: dual channel, 2: joint stereo, 3: stereo
+   */
+  uint32_t mode;
+  /**
+   * Number of channels in `samples`
+   */
+  uint32_t channels;
+  /**
+   * Number of samples per channel
+   */
+  uint32_t length;
+  /**
+   * The position at the start of the frame, in microseconds
+   */
+  uint64_t position_us;
+  /**
+   * The duration of the frame, in microseconds
+   */
+  uint64_t duration_us;
+  /**
+   * Samples in libmad's fixed-point format, one pointer per channel
+   */
+  const int32_t *samples[2];
+} SimplemadFrame;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Open the file at `path` for decoding
+ *
+ * Returns null if the file can't be opened.
+ *
+ * # Safety
+ *
+ * `path` must be null or a valid, NUL-terminated string.
+ */
+SimplemadDecoder *simplemad_open(const char *path);
+
+/**
+ * Open the file at `path` for decoding frame headers only
+ *
+ * Frames returned by this decoder have no samples.
+ *
+ * # Safety
+ *
+ * `path` must be null or a valid, NUL-terminated string.
+ */
+SimplemadDecoder *simplemad_open_headers(const char *path);
+
+/**
+ * Open the file at `path` for decoding from `start_ms` to `end_ms`
+ *
+ * # Safety
+ *
+ * `path` must be null or a valid, NUL-terminated string.
+ */
+SimplemadDecoder *simplemad_open_interval(const char *path, uint64_t start_ms, uint64_t end_ms);
+
+/**
+ * Decode the next frame into `frame`
+ *
+ * Returns `SIMPLEMAD_OK` on success and `SIMPLEMAD_EOF` at the end of the
+ * stream. `SIMPLEMAD_ERR_DECODE` is usually recoverable, so callers should
+ * keep calling until they see `SIMPLEMAD_EOF`. After `SIMPLEMAD_ERR_ABORTED`
+ * the decoder only returns `SIMPLEMAD_EOF`.
+ *
+ * # Safety
+ *
+ * `decoder` must be null or a handle returned by one of the `simplemad_open`
+ * functions, and `frame` must be null or point to writable memory.
+ */
+int simplemad_next_frame(SimplemadDecoder *decoder, SimplemadFrame *frame);
+
+/**
+ * Get the libmad error code behind the last `SIMPLEMAD_ERR_DECODE`
+ *
+ * # Safety
+ *
+ * `decoder` must be null or a handle returned by one of the `simplemad_open`
+ * functions.
+ */
+int simplemad_last_error(const SimplemadDecoder *decoder);
+
+/**
+ * Close a decoder and free its resources
+ *
+ * # Safety
+ *
+ * `decoder` must be null or a handle returned by one of the `simplemad_open`
+ * functions that hasn't been closed yet.
+ */
+void simplemad_close(SimplemadDecoder *decoder);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SIMPLEMAD_H */
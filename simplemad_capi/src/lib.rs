@@ -0,0 +1,313 @@
+//! C interface to simplemad
+//!
+//! The matching header is `include/simplemad.h`. It is generated with
+//! `cbindgen --config cbindgen.toml --output include/simplemad.h`.
+
+extern crate simplemad;
+
+use simplemad::{Decoder, DecoderOptions, Frame, LibmadError, SimplemadError};
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::BufReader;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::time::Duration;
+
+/// A frame was decoded
+pub const SIMPLEMAD_OK: c_int = 0;
+/// The end of the stream was reached
+pub const SIMPLEMAD_EOF: c_int = 1;
+/// libmad reported an error; see `simplemad_last_error`
+pub const SIMPLEMAD_ERR_DECODE: c_int = -1;
+/// The underlying file could not be read
+pub const SIMPLEMAD_ERR_IO: c_int = -2;
+/// A null pointer was passed
+pub const SIMPLEMAD_ERR_ARGS: c_int = -3;
+/// Decoding was abandoned after too many errors or a mid-stream format
+/// change; the stream wasn't read to its end
+pub const SIMPLEMAD_ERR_ABORTED: c_int = -4;
+
+/// An opaque decoder handle
+pub struct SimplemadDecoder {
+    decoder: Decoder<BufReader<File>>,
+    frame: Option<Frame>,
+    last_error: Option<LibmadError>,
+}
+
+/// A decoded frame
+///
+/// The sample pointers refer to memory owned by the decoder handle and
+/// stay valid until the next call to `simplemad_next_frame` or
+/// `simplemad_close`.
+#[repr(C)]
+pub struct SimplemadFrame {
+    /// Number of samples per second
+    pub sample_rate: u32,
+    /// Stream bit rate
+    pub bit_rate: u32,
+    /// Audio layer (1, 2 or 3)
+    pub layer: u32,
+    /// 0: single channel, 1: dual channel, 2: joint stereo, 3: stereo
+    pub mode: u32,
+    /// Number of channels in `samples`
+    pub channels: u32,
+    /// Number of samples per channel
+    pub length: u32,
+    /// The position at the start of the frame, in microseconds
+    pub position_us: u64,
+    /// The duration of the frame, in microseconds
+    pub duration_us: u64,
+    /// Samples in libmad's fixed-point format, one pointer per channel
+    pub samples: [*const i32; 2],
+}
+
+fn open(path: *const c_char,
+        start_time: Option<Duration>,
+        end_time: Option<Duration>,
+        headers_only: bool)
+        -> *mut SimplemadDecoder {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+
+    let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(p) => p,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let file = match File::open(path) {
+        Ok(f) => BufReader::new(f),
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let options = DecoderOptions {
+        start_time,
+        end_time,
+        headers_only,
+        ..Default::default()
+    };
+
+    match Decoder::builder(file).options(options).build() {
+        Ok(decoder) => {
+            Box::into_raw(Box::new(SimplemadDecoder {
+                decoder,
+                frame: None,
+                last_error: None,
+            }))
+        }
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+fn duration_us(d: Duration) -> u64 {
+    d.as_secs() * 1_000_000 + u64::from(d.subsec_micros())
+}
+
+/// Open the file at `path` for decoding
+///
+/// Returns null if the file can't be opened.
+///
+/// # Safety
+///
+/// `path` must be null or a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn simplemad_open(path: *const c_char) -> *mut SimplemadDecoder {
+    open(path, None, None, false)
+}
+
+/// Open the file at `path` for decoding frame headers only
+///
+/// Frames returned by this decoder have no samples.
+///
+/// # Safety
+///
+/// `path` must be null or a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn simplemad_open_headers(path: *const c_char) -> *mut SimplemadDecoder {
+    open(path, None, None, true)
+}
+
+/// Open the file at `path` for decoding from `start_ms` to `end_ms`
+///
+/// # Safety
+///
+/// `path` must be null or a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn simplemad_open_interval(path: *const c_char,
+                                                 start_ms: u64,
+                                                 end_ms: u64)
+                                                 -> *mut SimplemadDecoder {
+    open(path,
+         Some(Duration::from_millis(start_ms)),
+         Some(Duration::from_millis(end_ms)),
+         false)
+}
+
+/// Decode the next frame into `frame`
+///
+/// Returns `SIMPLEMAD_OK` on success and `SIMPLEMAD_EOF` at the end of the
+/// stream. `SIMPLEMAD_ERR_DECODE` is usually recoverable, so callers should
+/// keep calling until they see `SIMPLEMAD_EOF`. After `SIMPLEMAD_ERR_ABORTED`
+/// the decoder only returns `SIMPLEMAD_EOF`.
+///
+/// # Safety
+///
+/// `decoder` must be null or a handle returned by one of the `simplemad_open`
+/// functions, and `frame` must be null or point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn simplemad_next_frame(decoder: *mut SimplemadDecoder,
+                                              frame: *mut SimplemadFrame)
+                                              -> c_int {
+    if decoder.is_null() || frame.is_null() {
+        return SIMPLEMAD_ERR_ARGS;
+    }
+
+    let handle = &mut *decoder;
+
+    match handle.decoder.get_frame() {
+        Ok(f) => {
+            let mut samples = [ptr::null(); 2];
+            for (ptr, ch) in samples.iter_mut().zip(f.samples.iter()) {
+                *ptr = ch.as_ptr() as *const i32;
+            }
+
+            *frame = SimplemadFrame {
+                sample_rate: f.sample_rate,
+                bit_rate: f.bit_rate,
+                layer: f.layer as u32,
+                mode: f.mode as u32,
+                channels: f.samples.len() as u32,
+                length: f.samples.first().map_or(0, |ch| ch.len()) as u32,
+                position_us: duration_us(f.position),
+                duration_us: duration_us(f.duration),
+                samples,
+            };
+            handle.frame = Some(f);
+            SIMPLEMAD_OK
+        }
+        Err(error) => error_status(handle, error),
+    }
+}
+
+// Map an error to a status code, keeping libmad's error for
+// `simplemad_last_error`
+fn error_status(handle: &mut SimplemadDecoder, error: SimplemadError) -> c_int {
+    match error {
+        SimplemadError::EOF => SIMPLEMAD_EOF,
+        SimplemadError::Mad { error, .. } => {
+            handle.last_error = Some(error);
+            SIMPLEMAD_ERR_DECODE
+        }
+        SimplemadError::Read(_) |
+        SimplemadError::Write(_) |
+        SimplemadError::WouldBlock => SIMPLEMAD_ERR_IO,
+        SimplemadError::TooManyErrors { .. } |
+        SimplemadError::SyncLimitExceeded { .. } |
+        SimplemadError::FormatChanged { .. } => SIMPLEMAD_ERR_ABORTED,
+        SimplemadError::InvalidFormat(_) => {
+            handle.last_error = Some(LibmadError::LostSync);
+            SIMPLEMAD_ERR_DECODE
+        }
+        SimplemadError::RejectedLayer { .. } => {
+            handle.last_error = Some(LibmadError::BadLayer);
+            SIMPLEMAD_ERR_DECODE
+        }
+        _ => SIMPLEMAD_ERR_DECODE,
+    }
+}
+
+/// Get the libmad error code behind the last `SIMPLEMAD_ERR_DECODE`
+///
+/// # Safety
+///
+/// `decoder` must be null or a handle returned by one of the `simplemad_open`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn simplemad_last_error(decoder: *const SimplemadDecoder) -> c_int {
+    if decoder.is_null() {
+        return SIMPLEMAD_ERR_ARGS;
+    }
+
+    (*decoder).last_error.map_or(0, |error| error as c_int)
+}
+
+/// Close a decoder and free its resources
+///
+/// # Safety
+///
+/// `decoder` must be null or a handle returned by one of the `simplemad_open`
+/// functions that hasn't been closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn simplemad_close(decoder: *mut SimplemadDecoder) {
+    if !decoder.is_null() {
+        drop(Box::from_raw(decoder));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use simplemad::ErrorContext;
+    use std::ffi::CString;
+    use std::mem;
+
+    #[test]
+    fn test_capi_decode() {
+        let path = CString::new("../simplemad/sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let mut frame_count = 0;
+
+        unsafe {
+            let decoder = simplemad_open(path.as_ptr());
+            assert!(!decoder.is_null());
+            let mut frame: SimplemadFrame = mem::zeroed();
+
+            loop {
+                match simplemad_next_frame(decoder, &mut frame) {
+                    SIMPLEMAD_OK => {
+                        frame_count += 1;
+                        assert_eq!(frame.sample_rate, 44100);
+                        assert_eq!(frame.layer, 3);
+                        assert_eq!(frame.channels, 2);
+                        assert_eq!(frame.length, 1152);
+                        assert!(!frame.samples[1].is_null());
+                    }
+                    SIMPLEMAD_EOF => break,
+                    SIMPLEMAD_ERR_DECODE => assert!(simplemad_last_error(decoder) > 0),
+                    status => panic!("unexpected status {}", status),
+                }
+            }
+
+            simplemad_close(decoder);
+        }
+
+        assert_eq!(frame_count, 193);
+    }
+
+    #[test]
+    fn test_capi_null_arguments() {
+        unsafe {
+            assert!(simplemad_open(ptr::null()).is_null());
+            assert_eq!(simplemad_next_frame(ptr::null_mut(), ptr::null_mut()),
+                       SIMPLEMAD_ERR_ARGS);
+            simplemad_close(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_capi_aborted() {
+        let path = CString::new("../simplemad/sample_mp3s/constant_stereo_128.mp3").unwrap();
+        let context = ErrorContext {
+            byte_offset: 0,
+            position: Duration::new(0, 0),
+            frame_index: 0,
+        };
+
+        unsafe {
+            let decoder = simplemad_open(path.as_ptr());
+            let error = SimplemadError::TooManyErrors { count: 10, context };
+            assert_eq!(error_status(&mut *decoder, error), SIMPLEMAD_ERR_ABORTED);
+            assert_eq!(error_status(&mut *decoder, SimplemadError::EOF), SIMPLEMAD_EOF);
+            simplemad_close(decoder);
+        }
+    }
+}
@@ -1,16 +1,18 @@
+// `gcc` has been renamed to `cc`; keep using it until the dependency is bumped
+#![allow(deprecated)]
+
 extern crate gcc;
 extern crate pkg_config;
 
 use std::env;
 
 fn main() {
-    match pkg_config::find_library("mad") {
-        Ok(_) => return,
-        Err(..) => {}
-    };
+    if pkg_config::find_library("mad").is_ok() {
+        return;
+    }
 
     // attempt to compile libmad manually
-    let mut gcc = gcc::Config::new();
+    let mut gcc = gcc::Build::new();
     gcc.include("libmad-src");
     gcc.define("HAVE_CONFIG_H", None);
 
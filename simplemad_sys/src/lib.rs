@@ -3,30 +3,31 @@
 extern crate libc;
 
 use libc::{c_void, c_char, c_int, c_uint, c_ushort, c_long, uint16_t};
-use std::fmt::{self, Result, Debug};
+use std::fmt;
 use std::ptr;
 
 pub use libc::c_ulong;
 
-extern {
+extern "C" {
     pub fn mad_decoder_init(decoder: *mut MadDecoder,
                             message: *mut c_void,
-                            input_cb: extern fn(message: *mut c_void,
-                                                stream: &MadStream) -> MadFlow,
-                            header_cb: extern fn(message: *mut c_void,
-                                                 header: &MadHeader) -> MadFlow,
-                            filter_cb: extern fn(),
-                            output_cb: extern fn(message: *mut c_void,
-                                                 header: &MadHeader,
-                                                 pcm: &MadPcm) -> MadFlow,
-                            error_cb: extern fn(message: *mut c_void,
-                                                stream: &MadStream,
-                                                frame: *const c_void) -> MadFlow,
-                            message_cb: extern fn());
+                            input_cb: extern "C" fn(message: *mut c_void,
+                                                    stream: &MadStream) -> MadFlow,
+                            header_cb: extern "C" fn(message: *mut c_void,
+                                                     header: &MadHeader) -> MadFlow,
+                            filter_cb: extern "C" fn(),
+                            output_cb: extern "C" fn(message: *mut c_void,
+                                                     header: &MadHeader,
+                                                     pcm: &MadPcm) -> MadFlow,
+                            error_cb: extern "C" fn(message: *mut c_void,
+                                                    stream: &MadStream,
+                                                    frame: *const c_void) -> MadFlow,
+                            message_cb: extern "C" fn());
 
     pub fn mad_decoder_run(decoder: &mut MadDecoder, mode: MadDecoderMode) -> c_int;
     pub fn mad_decoder_finish(decoder: &mut MadDecoder) -> c_int;
-    pub fn mad_stream_buffer(stream: &MadStream, buf_start: *const u8, buf_len: c_ulong);
+    pub fn mad_stream_buffer(stream: &mut MadStream, buf_start: *const u8, buf_len: c_ulong);
+    pub fn mad_stream_errorstr(stream: &MadStream) -> *const c_char;
 
     pub fn mad_header_init(header: &mut MadHeader);
     pub fn mad_stream_init(stream: &mut MadStream);
@@ -39,6 +40,12 @@ extern {
     pub fn mad_header_decode(header: &mut MadHeader, stream: &mut MadStream);
     pub fn mad_frame_decode(frame: &mut MadFrame, stream: &mut MadStream);
     pub fn mad_synth_frame(synth: &mut MadSynth, frame: &mut MadFrame);
+
+    // The first characters of NUL-terminated strings, use their address
+    pub static mad_version: c_char;
+    pub static mad_copyright: c_char;
+    pub static mad_author: c_char;
+    pub static mad_build: c_char;
 }
 
 /// libmad callbacks return MadFlow values, which are used to control the decoding process
@@ -59,10 +66,11 @@ pub enum MadFlow {
 }
 
 /// Errors generated by libmad
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(C)]
 pub enum MadError {
     /// no error
+    #[default]
     None = 0x0000,
 
     /// input buffer too small (or eof)
@@ -129,12 +137,49 @@ pub enum MadError {
     BadStereo = 0x0239,
 }
 
-impl Default for MadError {
-    fn default() -> MadError {
-        MadError::None
+impl MadError {
+    /// Whether libmad can continue decoding after this error (`MAD_RECOVERABLE`)
+    pub fn is_recoverable(self) -> bool {
+        self as u32 & 0xff00 != 0
+    }
+
+    /// A description of the error, as returned by `mad_stream_errorstr`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MadError::None => "no error",
+            MadError::BufLen => "input buffer too small (or EOF)",
+            MadError::BufPtr => "invalid (null) buffer pointer",
+            MadError::NoMem => "not enough memory",
+            MadError::LostSync => "lost synchronization",
+            MadError::BadLayer => "reserved header layer value",
+            MadError::BadBitRate => "forbidden bitrate value",
+            MadError::BadSampleRate => "reserved sample frequency value",
+            MadError::BadEmphasis => "reserved emphasis value",
+            MadError::BadCRC => "CRC check failed",
+            MadError::BadBitAlloc => "forbidden bit allocation value",
+            MadError::BadScaleFactor => "bad scalefactor index",
+            MadError::BadMode => "bad bitrate/mode combination",
+            MadError::BadFrameLen => "bad frame length",
+            MadError::BadBigValues => "bad big_values count",
+            MadError::BadBlockType => "reserved block_type",
+            MadError::BadScFSI => "bad scalefactor selection info",
+            MadError::BadDataPtr => "bad main_data_begin pointer",
+            MadError::BadPart3Len => "bad audio data length",
+            MadError::BadHuffTable => "bad Huffman table select",
+            MadError::BadHuffData => "Huffman data overrun",
+            MadError::BadStereo => "incompatible block_type for JS",
+        }
+    }
+}
+
+impl fmt::Display for MadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
+impl std::error::Error for MadError {}
+
 #[repr(C)]
 pub struct MadBitPtr {
     pub byte: *mut c_char,
@@ -152,6 +197,17 @@ impl Default for MadBitPtr {
     }
 }
 
+/// Ignore CRC errors
+pub const MAD_OPTION_IGNORECRC: c_int = 0x0001;
+/// Generate PCM at 1/2 sample rate
+pub const MAD_OPTION_HALFSAMPLERATE: c_int = 0x0002;
+/// Decode left channel only
+pub const MAD_OPTION_LEFTCHANNEL: c_int = 0x0010;
+/// Decode right channel only
+pub const MAD_OPTION_RIGHTCHANNEL: c_int = 0x0020;
+/// Combine channels
+pub const MAD_OPTION_SINGLECHANNEL: c_int = 0x0030;
+
 #[repr(C)]
 pub struct MadStream {
     pub buffer: *mut c_char,
@@ -210,7 +266,7 @@ impl Default for MadFrame {
     }
 }
 
-#[derive(Clone)]
+#[derive(Default, Clone)]
 #[repr(C)]
 pub struct MadSynth {
     pub filter: [[[[[i32; 8]; 16]; 2]; 2]; 2],
@@ -218,16 +274,6 @@ pub struct MadSynth {
     pub pcm: MadPcm,
 }
 
-impl Default for MadSynth {
-    fn default() -> MadSynth {
-        MadSynth {
-            filter: [[[[[0; 8]; 16]; 2]; 2]; 2],
-            phase: 0,
-            pcm: Default::default(),
-        }
-    }
-}
-
 impl fmt::Debug for MadSynth {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f,
@@ -237,50 +283,35 @@ impl fmt::Debug for MadSynth {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(C)]
 pub enum MadLayer {
+    #[default]
     LayerI = 1,
     LayerII = 2,
     LayerIII = 3,
 }
 
-impl Default for MadLayer {
-    fn default() -> MadLayer {
-        MadLayer::LayerI
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(C)]
 pub enum MadMode {
+    #[default]
     SingleChannel = 0,
     DualChannel = 1,
     JointStereo = 2,
     Stereo = 3,
 }
 
-impl Default for MadMode {
-    fn default() -> MadMode {
-        MadMode::SingleChannel
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(C)]
 pub enum MadEmphasis {
+    #[default]
     None = 0,
     Fifty15Us = 1,
     CcittJ17 = 3,
     Reserved = 2,
 }
 
-impl Default for MadEmphasis {
-    fn default() -> MadEmphasis {
-        MadEmphasis::None
-    }
-}
-
 #[derive(Default, Debug, Clone, Copy)]
 #[repr(C)]
 pub struct MadTimer {
@@ -288,6 +319,36 @@ pub struct MadTimer {
     pub fraction: c_ulong,
 }
 
+/// Number of Layer III private bits
+pub const MAD_FLAG_NPRIVATE_III: c_int = 0x0007;
+/// Header but not data is decoded
+pub const MAD_FLAG_INCOMPLETE: c_int = 0x0008;
+/// Frame has CRC protection
+pub const MAD_FLAG_PROTECTION: c_int = 0x0010;
+/// Frame is copyright
+pub const MAD_FLAG_COPYRIGHT: c_int = 0x0020;
+/// Frame is original (else copy)
+pub const MAD_FLAG_ORIGINAL: c_int = 0x0040;
+/// Frame has additional slot
+pub const MAD_FLAG_PADDING: c_int = 0x0080;
+/// Uses intensity joint stereo
+pub const MAD_FLAG_I_STEREO: c_int = 0x0100;
+/// Uses middle/side joint stereo
+pub const MAD_FLAG_MS_STEREO: c_int = 0x0200;
+/// Uses free format bitrate
+pub const MAD_FLAG_FREEFORMAT: c_int = 0x0400;
+/// Lower sampling freq. extension
+pub const MAD_FLAG_LSF_EXT: c_int = 0x1000;
+/// Multichannel audio extension
+pub const MAD_FLAG_MC_EXT: c_int = 0x2000;
+/// MPEG 2.5 (unofficial) extension
+pub const MAD_FLAG_MPEG_2_5_EXT: c_int = 0x4000;
+
+/// Header private bit
+pub const MAD_PRIVATE_HEADER: c_int = 0x0100;
+/// Layer III private bits (up to 5)
+pub const MAD_PRIVATE_III: c_int = 0x001f;
+
 #[derive(Default, Debug, Clone, Copy)]
 #[repr(C)]
 pub struct MadHeader {
@@ -334,19 +395,14 @@ impl fmt::Debug for MadPcm {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 #[repr(C)]
 pub enum MadDecoderMode {
+    #[default]
     Sync = 0,
     Async = 1,
 }
 
-impl Default for MadDecoderMode {
-    fn default() -> MadDecoderMode {
-        MadDecoderMode::Sync
-    }
-}
-
 #[derive(Default, Debug, Clone, Copy)]
 #[repr(C)]
 struct MadAsyncParameters {